@@ -1,6 +1,8 @@
 use bytes::Bytes;
-use clap::{Parser, ValueHint};
+use clap::{Parser, Subcommand, ValueHint};
 use shiva::core::{Document, DocumentType};
+use shiva::diff::RedlineOptions;
+use shiva::resource::{CachedResolver, FileResolver};
 use std::path::Path;
 
 #[derive(Parser, Debug)]
@@ -9,18 +11,24 @@ use std::path::Path;
     author,
     version,
     about = "CLI Shiva: Converting documents from any format to any",
-    long_about = None
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     #[arg(
         value_name = "INPUT_FILE",
         help = &format!(
             "Input file (possible formats: {})",
             DocumentType::supported_extensions().join(", ")
         ),
-        value_hint = ValueHint::FilePath
+        value_hint = ValueHint::FilePath,
+        required = true
     )]
-    input_file: String,
+    input_file: Option<String>,
 
     #[arg(
         value_name = "OUTPUT_FILE",
@@ -28,73 +36,160 @@ struct Args {
             "Output file (possible formats: {})",
             DocumentType::supported_extensions().join(", ")
         ),
-        value_hint = ValueHint::FilePath
+        value_hint = ValueHint::FilePath,
+        required = true
+    )]
+    output_file: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Also load images outside the directory of the input file, such as ../a.png"
     )]
-    output_file: String,
+    unconfined_images: bool,
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Compares two documents and writes a redline of the changes
+    Diff {
+        #[arg(value_name = "OLD_FILE", value_hint = ValueHint::FilePath)]
+        old_file: String,
 
-    let input_path = Path::new(&args.input_file);
-    let output_path = Path::new(&args.output_file);
+        #[arg(value_name = "NEW_FILE", value_hint = ValueHint::FilePath)]
+        new_file: String,
 
-    let supported_formats = DocumentType::supported_extensions();
+        #[arg(
+            value_name = "OUTPUT_FILE",
+            help = "Redline file (possible formats: html, docx, md)",
+            value_hint = ValueHint::FilePath
+        )]
+        output_file: String,
 
-    let input_format = match input_path.extension() {
-        Some(ext) => ext.to_str().ok_or_else(|| {
-            anyhow::anyhow!(
-                "Invalid extension of the input file. Supported formats are: {}",
-                supported_formats.join(", ")
+        #[arg(
+            long,
+            default_value = "Shiva",
+            help = "Author of the tracked changes in DOCX"
+        )]
+        author: String,
+
+        #[arg(
+            long,
+            help = "Date of the tracked changes in DOCX, e.g. 2024-05-01T12:00:00Z"
+        )]
+        date: Option<String>,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    match args.command {
+        Some(Command::Diff {
+            old_file,
+            new_file,
+            output_file,
+            author,
+            date,
+        }) => {
+            let options = RedlineOptions { author, date };
+            diff(
+                &old_file,
+                &new_file,
+                &output_file,
+                &options,
+                args.unconfined_images,
             )
-        })?,
+        }
         None => {
-            return Err(anyhow::anyhow!(
-                "The input file has no extension. Supported formats are: {}",
-                supported_formats.join(", ")
-            ))
+            let (Some(input_file), Some(output_file)) = (args.input_file, args.output_file) else {
+                return Err(anyhow::anyhow!("INPUT_FILE and OUTPUT_FILE are required"));
+            };
+            convert(&input_file, &output_file, args.unconfined_images)
         }
-    };
+    }
+}
+
+fn convert(input_file: &str, output_file: &str, unconfined_images: bool) -> anyhow::Result<()> {
+    let output_doc_type = document_type(output_file, "output")?;
+    let document = read_document(input_file, unconfined_images)?;
+
+    let output = document.generate(output_doc_type)?;
+
+    std::fs::write(output_file, output)?;
+
+    Ok(())
+}
+
+fn diff(
+    old_file: &str,
+    new_file: &str,
+    output_file: &str,
+    options: &RedlineOptions,
+    unconfined_images: bool,
+) -> anyhow::Result<()> {
+    let output_doc_type = document_type(output_file, "output")?;
+    let old = read_document(old_file, unconfined_images)?;
+    let new = read_document(new_file, unconfined_images)?;
+
+    let diff = shiva::diff::diff(&old, &new);
+    let redline = diff.redline_with_options(output_doc_type, options)?;
 
-    let output_format = match output_path.extension() {
+    std::fs::write(output_file, redline)?;
+
+    let summary = diff.summary();
+    println!(
+        "{} inserted, {} deleted, {} changed",
+        summary.inserted, summary.deleted, summary.changed
+    );
+
+    Ok(())
+}
+
+fn document_type(file: &str, role: &str) -> anyhow::Result<DocumentType> {
+    let supported_formats = DocumentType::supported_extensions();
+
+    let format = match Path::new(file).extension() {
         Some(ext) => ext.to_str().ok_or_else(|| {
             anyhow::anyhow!(
-                "Invalid output file extension. Supported formats are: {}",
+                "Invalid extension of the {role} file. Supported formats are: {}",
                 supported_formats.join(", ")
             )
         })?,
         None => {
             return Err(anyhow::anyhow!(
-                "The output file has no extension. Supported formats are: {}",
+                "The {role} file has no extension. Supported formats are: {}",
                 supported_formats.join(", ")
             ))
         }
     };
 
-    let input_doc_type = DocumentType::from_extension(input_format).ok_or_else(|| {
+    DocumentType::from_extension(format).ok_or_else(|| {
         anyhow::anyhow!(
-            "Unsupported input file format '{}'. Supported formats are: {}",
-            input_format,
+            "Unsupported {role} file format '{}'. Supported formats are: {}",
+            format,
             supported_formats.join(", ")
         )
-    })?;
+    })
+}
 
-    let output_doc_type = DocumentType::from_extension(output_format).ok_or_else(|| {
-        anyhow::anyhow!(
-            "Unsupported output file format '{}'. Supported formats are: {}",
-            output_format,
-            supported_formats.join(", ")
-        )
-    })?;
+fn read_document(file: &str, unconfined_images: bool) -> anyhow::Result<Document> {
+    let input_path = Path::new(file);
+    let input_doc_type = document_type(file, "input")?;
 
-    let input_vec = std::fs::read(&args.input_file)?;
+    let input_vec = std::fs::read(file)?;
     let input_bytes = Bytes::from(input_vec);
 
-    let document = Document::parse(&input_bytes, input_doc_type)?;
-
-    let output = document.generate(output_doc_type)?;
-
-    std::fs::write(&args.output_file, output)?;
-
-    Ok(())
+    // Images are referenced relative to the input file
+    let input_dir = match input_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let resolver = if unconfined_images {
+        FileResolver::unconfined(input_dir)
+    } else {
+        FileResolver::new(input_dir)
+    };
+    let resolver = CachedResolver::new(resolver);
+    Document::parse_with_resolver(&input_bytes, input_doc_type, resolver)
 }
@@ -8,10 +8,14 @@ use strum::{Display, EnumCount, EnumString, IntoStaticStr, VariantArray};
 use thiserror::Error;
 use wasm_bindgen::prelude::wasm_bindgen;
 
+pub use crate::resource::ResourceResolver;
+
 #[cfg(feature = "csv")]
 use crate::csv;
 #[cfg(feature = "docx")]
 use crate::docx;
+#[cfg(feature = "fixed_width")]
+use crate::fixed_width;
 #[cfg(feature = "html")]
 use crate::html;
 #[cfg(feature = "json")]
@@ -26,6 +30,8 @@ use crate::pdf;
 use crate::rtf;
 #[cfg(feature = "text")]
 use crate::text;
+#[cfg(feature = "tsv")]
+use crate::tsv;
 #[cfg(feature = "xls")]
 use crate::xls;
 #[cfg(feature = "xlsx")]
@@ -178,6 +184,25 @@ pub struct Document {
 
     /// Page orientation (Portrait, Landscape) Default is Portrait.
     pub orientation: PageOrientation,
+
+    /// Title, author and description of the document, where the source format has them.
+    #[cfg_attr(feature = "json", serde(default))]
+    pub metadata: Metadata,
+}
+
+/// Descriptive information about a document that is not part of its content.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.author.is_none() && self.description.is_none()
+    }
 }
 
 impl Document {
@@ -186,6 +211,7 @@ impl Document {
             bands: vec![Band::Detail(elements)],
             page_format: PageFormat::default(),
             orientation: PageOrientation::default(),
+            metadata: Metadata::default(),
         }
     }
 
@@ -203,6 +229,7 @@ impl Document {
             ],
             page_format,
             orientation: PageOrientation::default(),
+            metadata: Metadata::default(),
         }
     }
 
@@ -258,10 +285,46 @@ impl Document {
             DocumentType::ODS => ods::Transformer::parse(input_bytes)?,
             #[cfg(not(feature = "ods"))]
             DocumentType::ODS => return Err(anyhow::anyhow!("ODS feature is not enabled")),
+            #[cfg(feature = "tsv")]
+            DocumentType::TSV => tsv::Transformer::parse(input_bytes)?,
+            #[cfg(not(feature = "tsv"))]
+            DocumentType::TSV => return Err(anyhow::anyhow!("TSV feature is not enabled")),
+            #[cfg(feature = "fixed_width")]
+            DocumentType::FixedWidth => fixed_width::Transformer::parse(input_bytes)?,
+            #[cfg(not(feature = "fixed_width"))]
+            DocumentType::FixedWidth => {
+                return Err(anyhow::anyhow!("Fixed-width feature is not enabled"))
+            }
         };
         Ok(document)
     }
 
+    /// Parses a document, loading images and other referenced assets through
+    /// `resolver` instead of the current directory. Formats without external
+    /// references ignore the resolver.
+    #[cfg_attr(
+        not(any(feature = "markdown", feature = "html")),
+        allow(unused_variables)
+    )]
+    pub fn parse_with_resolver<R>(
+        input_bytes: &Bytes,
+        document_type: DocumentType,
+        resolver: R,
+    ) -> anyhow::Result<Document>
+    where
+        R: ResourceResolver,
+    {
+        match document_type {
+            #[cfg(feature = "markdown")]
+            DocumentType::Markdown => {
+                markdown::Transformer::parse_with_loader(input_bytes, resolver)
+            }
+            #[cfg(feature = "html")]
+            DocumentType::HTML => html::Transformer::parse_with_loader(input_bytes, resolver),
+            _ => Document::parse(input_bytes, document_type),
+        }
+    }
+
     pub fn generate(&self, document_type: DocumentType) -> anyhow::Result<Bytes> {
         let output = match document_type {
             #[cfg(feature = "markdown")]
@@ -314,10 +377,44 @@ impl Document {
             DocumentType::ODS => ods::Transformer::generate(self)?,
             #[cfg(not(feature = "ods"))]
             DocumentType::ODS => return Err(anyhow::anyhow!("ODS feature is not enabled")),
+            #[cfg(feature = "tsv")]
+            DocumentType::TSV => tsv::Transformer::generate(self)?,
+            #[cfg(not(feature = "tsv"))]
+            DocumentType::TSV => return Err(anyhow::anyhow!("TSV feature is not enabled")),
+            #[cfg(feature = "fixed_width")]
+            DocumentType::FixedWidth => fixed_width::Transformer::generate(self)?,
+            #[cfg(not(feature = "fixed_width"))]
+            DocumentType::FixedWidth => {
+                return Err(anyhow::anyhow!("Fixed-width feature is not enabled"))
+            }
         };
         Ok(output)
     }
 
+    /// Generates a document, handing the files it references (the images of
+    /// Markdown and HTML) to `saver` instead of writing them to the current
+    /// directory. Formats that embed their images ignore the saver.
+    #[cfg_attr(
+        not(any(feature = "markdown", feature = "html")),
+        allow(unused_variables)
+    )]
+    pub fn generate_with_saver<F>(
+        &self,
+        document_type: DocumentType,
+        saver: F,
+    ) -> anyhow::Result<Bytes>
+    where
+        F: Fn(&Bytes, &str) -> anyhow::Result<()>,
+    {
+        match document_type {
+            #[cfg(feature = "markdown")]
+            DocumentType::Markdown => markdown::Transformer::generate_with_saver(self, saver),
+            #[cfg(feature = "html")]
+            DocumentType::HTML => html::Transformer::generate_with_saver(self, saver),
+            _ => self.generate(document_type),
+        }
+    }
+
     /// Returns all elements from all bands
     pub fn get_all_elements(&self) -> Vec<&Element> {
         let mut elements = Vec::new();
@@ -387,6 +484,14 @@ impl Document {
         self.page_format = page_format;
     }
 
+    pub fn get_metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+
+    pub fn set_metadata(&mut self, metadata: Metadata) {
+        self.metadata = metadata;
+    }
+
     pub fn set_orientation(&mut self, orientation: PageOrientation) {
         self.orientation = orientation;
     }
@@ -507,7 +612,7 @@ pub trait TransformerTrait {
 pub trait TransformerWithImageLoaderSaverTrait {
     fn parse_with_loader<F>(document: &Bytes, image_loader: F) -> anyhow::Result<Document>
     where
-        F: Fn(&str) -> anyhow::Result<Bytes>;
+        F: ResourceResolver;
     fn generate_with_saver<F>(document: &Document, image_saver: F) -> anyhow::Result<Bytes>
     where
         F: Fn(&Bytes, &str) -> anyhow::Result<()>;
@@ -551,12 +656,29 @@ pub enum Element {
         alt: String,
         size: u8,
     },
+    DefinitionList {
+        items: Vec<DefinitionItem>,
+    },
+    /// A LaTeX formula, shown on its own line when `display` is set.
+    Math {
+        latex: String,
+        display: bool,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
 pub struct ListItem {
     pub element: Element,
+    /// `Some` for task list items, holding whether the checkbox is ticked.
+    #[cfg_attr(feature = "json", serde(default))]
+    pub checked: Option<bool>,
+}
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
+pub struct DefinitionItem {
+    pub term: String,
+    pub descriptions: Vec<Element>,
 }
 #[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "json", derive(Serialize, Deserialize))]
@@ -586,6 +708,8 @@ pub struct ImageData {
     image_type: ImageType,
     align: ImageAlignment,
     size: ImageDimension,
+    #[cfg_attr(feature = "json", serde(default))]
+    caption: String,
 }
 
 /**
@@ -607,29 +731,31 @@ impl ImageData {
             image_type: ImageType::default(),
             align: ImageAlignment::default(),
             size,
+            caption: String::new(),
         };
-        image_data.set_image_type(&src_or_type);
+        // The bytes are more reliable than a file name or URL
+        match ImageType::from_bytes(&image_data.bytes) {
+            Some(image_type) => image_data.image_type = image_type,
+            None => image_data.set_image_type(&src_or_type),
+        }
         image_data.set_image_alignment(&alignment);
         image_data
     }
 
+    /// Sets the type from a type name or the extension of a path or URL.
+    /// Unknown types fall back to the default.
     pub fn set_image_type(&mut self, image_type_str: &str) {
         let image_type_str = image_type_str
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
             .split('.')
             .next_back()
             .unwrap_or_default()
             .trim()
             .to_lowercase();
 
-        if image_type_str.trim().is_empty() {
-            self.image_type = ImageType::default();
-            return;
-        }
-
-        match ImageType::from_str(&image_type_str) {
-            Ok(image_type) => self.image_type = image_type,
-            Err(_) => panic!("Invalid image type: {image_type_str}"),
-        }
+        self.image_type = ImageType::from_str(&image_type_str).unwrap_or_default();
     }
 
     pub fn set_image_alignment(&mut self, alignment_str: &str) {
@@ -644,6 +770,9 @@ impl ImageData {
     }
 
     pub fn set_image_bytes(&mut self, bytes: Bytes) {
+        if let Some(image_type) = ImageType::from_bytes(&bytes) {
+            self.image_type = image_type;
+        }
         self.bytes = bytes;
     }
 
@@ -659,6 +788,12 @@ impl ImageData {
         self.size = size;
     }
 
+    /// Sets the figure caption. Captioned images are numbered as figures by
+    /// the generators.
+    pub fn set_image_caption(&mut self, caption: &str) {
+        self.caption = caption.to_string();
+    }
+
     pub fn bytes(&self) -> &Bytes {
         &self.bytes
     }
@@ -682,6 +817,139 @@ impl ImageData {
     pub fn size(&self) -> &ImageDimension {
         &self.size
     }
+
+    pub fn caption(&self) -> &str {
+        &self.caption
+    }
+
+    /// The caption text shown under the image as figure `number`.
+    pub fn figure_label(&self, number: usize) -> String {
+        format!("Figure {number}: {}", self.caption)
+    }
+
+    /// Size in pixels read from the image header, without decoding the image.
+    pub fn intrinsic_size(&self) -> Option<(u32, u32)> {
+        intrinsic_size(&self.bytes, &self.image_type)
+    }
+
+    /// Size in points that every generator should use for the image.
+    /// Explicit lengths win, a missing one keeps the aspect ratio of the
+    /// intrinsic size and pixels are 1/96 inch. Percentages are of the
+    /// `area`, and the result is scaled down to fit into it.
+    pub fn display_size(&self, area: (f32, f32)) -> Option<(f32, f32)> {
+        let (max_width, max_height) = area;
+        let intrinsic = self
+            .intrinsic_size()
+            .map(|(width, height)| (width as f32 * 0.75, height as f32 * 0.75));
+        let width = self.size.width_length().map(|w| w.to_points(max_width));
+        let height = self.size.height_length().map(|h| h.to_points(max_height));
+
+        let (width, height) = match (width, height, intrinsic) {
+            (Some(width), Some(height), _) => (width, height),
+            (Some(width), None, Some((w, h))) if w > 0.0 => (width, width * h / w),
+            (None, Some(height), Some((w, h))) if h > 0.0 => (height * w / h, height),
+            (None, None, Some(size)) => size,
+            _ => return None,
+        };
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+        let ratio = (max_width / width).min(max_height / height).min(1.0);
+        Some((width * ratio, height * ratio))
+    }
+}
+
+/// The largest area in points an image may take in paged output, 16.5 cm by
+/// 29.7 cm.
+pub const IMAGE_AREA: (f32, f32) = (467.7, 841.9);
+
+/// The most pixels an SVG image is rasterized to, larger ones are scaled down.
+pub const MAX_RASTER_PIXELS: u64 = 100_000_000;
+
+/// Reads the pixel size from the header of the supported image formats.
+fn intrinsic_size(bytes: &[u8], image_type: &ImageType) -> Option<(u32, u32)> {
+    let be16 = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32);
+    let le16 = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?) as u32);
+    let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le24 = |at: usize| {
+        let b = bytes.get(at..at + 3)?;
+        Some(u32::from_le_bytes([b[0], b[1], b[2], 0]))
+    };
+    match image_type {
+        ImageType::Png => Some((be32(16)?, be32(20)?)),
+        ImageType::Gif => Some((le16(6)?, le16(8)?)),
+        ImageType::Bmp => {
+            let width = i32::from_le_bytes(bytes.get(18..22)?.try_into().ok()?);
+            let height = i32::from_le_bytes(bytes.get(22..26)?.try_into().ok()?);
+            Some((width.unsigned_abs(), height.unsigned_abs()))
+        }
+        ImageType::Jpeg => {
+            // Walk the segments up to the start of frame marker
+            let mut at = 2;
+            while *bytes.get(at)? == 0xFF {
+                let marker = *bytes.get(at + 1)?;
+                let is_frame =
+                    matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC);
+                if is_frame {
+                    return Some((be16(at + 7)?, be16(at + 5)?));
+                }
+                at += 2 + be16(at + 2)? as usize;
+            }
+            None
+        }
+        ImageType::Webp => match bytes.get(12..16)? {
+            b"VP8 " => Some((le16(26)? & 0x3FFF, le16(28)? & 0x3FFF)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+            }
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            _ => None,
+        },
+        ImageType::SVG => {
+            let text = std::str::from_utf8(bytes).ok()?;
+            let start = text.find("<svg")?;
+            let tag = &text[start..start + text[start..].find('>')?];
+            let attribute = |name: &str| {
+                let pattern = format!("{name}=");
+                let (at, _) = tag
+                    .match_indices(&pattern)
+                    .find(|(at, _)| tag[..*at].ends_with(char::is_whitespace))?;
+                let at = at + pattern.len();
+                // Unquoted values are not read
+                let quote = tag[at..]
+                    .chars()
+                    .next()
+                    .filter(|c| matches!(c, '"' | '\''))?;
+                let value = &tag[at + quote.len_utf8()..];
+                Some(value[..value.find(quote)?].to_string())
+            };
+            let pixels = |value: String| match Length::from_str(&value).ok()? {
+                Length::Percent(_) => None,
+                length => Some((length.to_points(0.0) / 0.75).round() as u32),
+            };
+            match (
+                attribute("width").and_then(pixels),
+                attribute("height").and_then(pixels),
+            ) {
+                (Some(width), Some(height)) => Some((width, height)),
+                _ => {
+                    let view_box = attribute("viewBox")?;
+                    let values: Vec<f32> = view_box
+                        .split([' ', ','])
+                        .filter_map(|value| value.parse().ok())
+                        .collect();
+                    match values[..] {
+                        [_, _, width, height] => {
+                            Some((width.round() as u32, height.round() as u32))
+                        }
+                        _ => None,
+                    }
+                }
+            }
+        }
+        ImageType::Tiff => None,
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Default, Display, EnumString, VariantArray)]
@@ -690,9 +958,14 @@ impl ImageData {
 pub enum ImageType {
     #[default]
     Png,
+    #[strum(to_string = "jpeg", serialize = "jpg")]
     Jpeg,
     Gif,
     SVG,
+    Webp,
+    Bmp,
+    #[strum(to_string = "tiff", serialize = "tif")]
+    Tiff,
 }
 
 impl ImageType {
@@ -702,8 +975,122 @@ impl ImageType {
             ImageType::Jpeg => ".jpeg",
             ImageType::Gif => ".gif",
             ImageType::SVG => ".svg",
+            ImageType::Webp => ".webp",
+            ImageType::Bmp => ".bmp",
+            ImageType::Tiff => ".tiff",
+        }
+    }
+
+    pub fn to_mime_type(&self) -> &str {
+        match self {
+            ImageType::Png => "image/png",
+            ImageType::Jpeg => "image/jpeg",
+            ImageType::Gif => "image/gif",
+            ImageType::SVG => "image/svg+xml",
+            ImageType::Webp => "image/webp",
+            ImageType::Bmp => "image/bmp",
+            ImageType::Tiff => "image/tiff",
+        }
+    }
+
+    /// Detects the format from the signature at the start of the image data.
+    pub fn from_bytes(bytes: &[u8]) -> Option<ImageType> {
+        match bytes {
+            [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, ..] => Some(ImageType::Png),
+            [0xFF, 0xD8, 0xFF, ..] => Some(ImageType::Jpeg),
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Some(ImageType::Gif),
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => {
+                Some(ImageType::Webp)
+            }
+            [b'B', b'M', ..] => Some(ImageType::Bmp),
+            [b'I', b'I', 0x2A, 0x00, ..] | [b'M', b'M', 0x00, 0x2A, ..] => Some(ImageType::Tiff),
+            _ => {
+                // SVG is text, the root element follows an optional XML declaration,
+                // comments or a doctype
+                let start = &bytes[..bytes.len().min(1024)];
+                let text = String::from_utf8_lossy(start);
+                let text = text.trim_start_matches('\u{feff}').trim_start();
+                (text.starts_with('<') && text.contains("<svg")).then_some(ImageType::SVG)
+            }
         }
     }
+
+    #[cfg(feature = "image")]
+    fn to_image_format(&self) -> Option<image::ImageFormat> {
+        match self {
+            ImageType::Png => Some(image::ImageFormat::Png),
+            ImageType::Jpeg => Some(image::ImageFormat::Jpeg),
+            ImageType::Gif => Some(image::ImageFormat::Gif),
+            ImageType::SVG => None,
+            ImageType::Webp => Some(image::ImageFormat::WebP),
+            ImageType::Bmp => Some(image::ImageFormat::Bmp),
+            ImageType::Tiff => Some(image::ImageFormat::Tiff),
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl ImageData {
+    /// Returns a copy of the image encoded as `target`, for output formats that
+    /// only embed some image types. SVG input is rasterized at its own size when
+    /// the `resvg` feature is enabled, scaled down to [`MAX_RASTER_PIXELS`].
+    pub fn convert(&self, target: ImageType) -> anyhow::Result<ImageData> {
+        if self.image_type == target {
+            return Ok(self.clone());
+        }
+        let format = target
+            .to_image_format()
+            .ok_or_else(|| anyhow::anyhow!("Cannot convert images to {target}"))?;
+        let image = match self.image_type {
+            #[cfg(feature = "resvg")]
+            ImageType::SVG => rasterize_svg(&self.bytes, MAX_RASTER_PIXELS)?,
+            #[cfg(not(feature = "resvg"))]
+            ImageType::SVG => anyhow::bail!("SVG images need the resvg feature to be converted"),
+            _ => image::load_from_memory(&self.bytes)?,
+        };
+        // JPEG has no alpha channel
+        let image = match target {
+            ImageType::Jpeg => image::DynamicImage::ImageRgb8(image.to_rgb8()),
+            _ => image,
+        };
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format)?;
+
+        let mut converted = self.clone();
+        converted.bytes = Bytes::from(bytes.into_inner());
+        converted.image_type = target;
+        Ok(converted)
+    }
+}
+
+#[cfg(all(feature = "image", feature = "resvg"))]
+fn rasterize_svg(svg: &[u8], max_pixels: u64) -> anyhow::Result<image::DynamicImage> {
+    use resvg::usvg::TreeParsing;
+
+    let tree = resvg::usvg::Tree::from_data(svg, &resvg::usvg::Options::default())?;
+    let size = tree.size.to_int_size();
+    // The declared size is not trusted, a larger image is rendered scaled down
+    let pixels = u64::from(size.width()) * u64::from(size.height());
+    let scale = if pixels > max_pixels {
+        (max_pixels as f64 / pixels as f64).sqrt()
+    } else {
+        1.0
+    };
+    let width = ((f64::from(size.width()) * scale) as u32).max(1);
+    let height = ((f64::from(size.height()) * scale) as u32).max(1);
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height)
+        .ok_or_else(|| anyhow::anyhow!("SVG image has no size"))?;
+    resvg::render(
+        &tree,
+        resvg::tiny_skia::Transform::from_scale(
+            width as f32 / size.width() as f32,
+            height as f32 / size.height() as f32,
+        ),
+        &mut pixmap.as_mut(),
+    );
+    let image = image::RgbaImage::from_raw(width, height, pixmap.take())
+        .ok_or_else(|| anyhow::anyhow!("Unexpected SVG raster size"))?;
+    Ok(image::DynamicImage::ImageRgba8(image))
 }
 
 #[derive(Debug, Clone, PartialEq, Default, EnumString, Display, VariantArray)]
@@ -724,15 +1111,129 @@ pub struct ImageDimension {
     pub height: Option<String>,
 }
 
+impl ImageDimension {
+    pub fn new(width: Option<Length>, height: Option<Length>) -> Self {
+        Self {
+            width: width.map(|width| width.to_string()),
+            height: height.map(|height| height.to_string()),
+        }
+    }
+
+    /// The width as a typed length, `None` when missing or not understood.
+    pub fn width_length(&self) -> Option<Length> {
+        self.width.as_deref().and_then(|width| width.parse().ok())
+    }
+
+    /// The height as a typed length, `None` when missing or not understood.
+    pub fn height_length(&self) -> Option<Length> {
+        self.height
+            .as_deref()
+            .and_then(|height| height.parse().ok())
+    }
+}
+
+/// An image length. Numbers without a unit are pixels, as in HTML, and
+/// `cm` and `in` are read as millimeters and points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    Px(f32),
+    Pt(f32),
+    Mm(f32),
+    Percent(f32),
+}
+
+impl Length {
+    /// Converts to points (1/72 inch), percentages are of `reference` points.
+    pub fn to_points(&self, reference: f32) -> f32 {
+        match self {
+            Length::Px(value) => value * 0.75,
+            Length::Pt(value) => *value,
+            Length::Mm(value) => value * 72.0 / 25.4,
+            Length::Percent(value) => reference * value / 100.0,
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let value = value.trim().to_lowercase();
+        let split = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(value.len());
+        let (number, unit) = value.split_at(split);
+        let number: f32 = number
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid length: {value}"))?;
+        match unit.trim() {
+            "" | "px" => Ok(Length::Px(number)),
+            "pt" => Ok(Length::Pt(number)),
+            "mm" => Ok(Length::Mm(number)),
+            "cm" => Ok(Length::Mm(number * 10.0)),
+            "in" => Ok(Length::Pt(number * 72.0)),
+            "%" => Ok(Length::Percent(number)),
+            unit => Err(anyhow::anyhow!("Unsupported length unit: {unit}")),
+        }
+    }
+}
+
+impl std::fmt::Display for Length {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Length::Px(value) => write!(f, "{value}px"),
+            Length::Pt(value) => write!(f, "{value}pt"),
+            Length::Mm(value) => write!(f, "{value}mm"),
+            Length::Percent(value) => write!(f, "{value}%"),
+        }
+    }
+}
+
+/// Loads images from `path` and below, references that leave it through `..`
+/// or an absolute path are refused. `data:` URIs are decoded in place.
 pub fn disk_image_loader(path: &str) -> impl Fn(&str) -> anyhow::Result<Bytes> {
-    let path = path.to_string();
-    let image_loader = move |image: &str| -> anyhow::Result<Bytes> {
-        let image_path = format!("{path}/{image}");
-        info!("Loading image: {image_path}");
-        let bytes = std::fs::read(image_path)?;
-        Ok(Bytes::from(bytes))
-    };
-    image_loader
+    file_image_loader(crate::resource::FileResolver::new(path))
+}
+
+/// Loads images relative to `path`, `..` and absolute paths included, for
+/// trusted documents that reference images next to them.
+pub fn unconfined_disk_image_loader(path: &str) -> impl Fn(&str) -> anyhow::Result<Bytes> {
+    file_image_loader(crate::resource::FileResolver::unconfined(path))
+}
+
+fn file_image_loader(
+    resolver: crate::resource::FileResolver,
+) -> impl Fn(&str) -> anyhow::Result<Bytes> {
+    move |image: &str| -> anyhow::Result<Bytes> {
+        info!("Loading image: {image}");
+        #[cfg(feature = "base64")]
+        if image.starts_with("data:") {
+            return crate::resource::DataUriResolver.resolve(image);
+        }
+        resolver.resolve(image)
+    }
+}
+
+/// The loader of [`TransformerTrait::parse`]: images inside the working
+/// directory, and on wasm32, where there is no file system, only `data:` URIs.
+#[cfg(any(feature = "markdown", feature = "html"))]
+pub(crate) fn default_image_loader() -> impl Fn(&str) -> anyhow::Result<Bytes> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        disk_image_loader(".")
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        |image: &str| -> anyhow::Result<Bytes> {
+            #[cfg(feature = "base64")]
+            if image.starts_with("data:") {
+                return crate::resource::DataUriResolver.resolve(image);
+            }
+            Err(anyhow::anyhow!(
+                "Cannot load {image} without a file system, parse with a resolver"
+            ))
+        }
+    }
 }
 
 pub fn disk_image_saver(path: &str) -> impl Fn(&Bytes, &str) -> anyhow::Result<()> {
@@ -768,6 +1269,8 @@ pub enum DocumentType {
     XLS = 9,
     XLSX = 10,
     ODS = 11,
+    TSV = 12,
+    FixedWidth = 13,
 }
 
 impl DocumentType {
@@ -778,6 +1281,7 @@ impl DocumentType {
     fn extension_map() -> HashMap<&'static str, DocumentType> {
         let mut map = HashMap::new();
         map.insert("html", DocumentType::HTML);
+        map.insert("htm", DocumentType::HTML);
         map.insert("md", DocumentType::Markdown);
         map.insert("markdown", DocumentType::Markdown);
         map.insert("txt", DocumentType::Text);
@@ -790,6 +1294,10 @@ impl DocumentType {
         map.insert("xls", DocumentType::XLS);
         map.insert("xlsx", DocumentType::XLSX);
         map.insert("ods", DocumentType::ODS);
+        map.insert("tsv", DocumentType::TSV);
+        map.insert("tab", DocumentType::TSV);
+        map.insert("fwf", DocumentType::FixedWidth);
+        map.insert("prn", DocumentType::FixedWidth);
         map
     }
 
@@ -800,8 +1308,159 @@ impl DocumentType {
     pub fn supported_extensions() -> Vec<&'static str> {
         Self::extension_map().keys().cloned().collect()
     }
+
+    /// The usual file extension, used when naming generated files.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DocumentType::HTML => "html",
+            DocumentType::Markdown => "md",
+            DocumentType::Text => "txt",
+            DocumentType::PDF => "pdf",
+            DocumentType::Json => "json",
+            DocumentType::CSV => "csv",
+            DocumentType::RTF => "rtf",
+            DocumentType::DOCX => "docx",
+            DocumentType::XML => "xml",
+            DocumentType::XLS => "xls",
+            DocumentType::XLSX => "xlsx",
+            DocumentType::ODS => "ods",
+            DocumentType::TSV => "tsv",
+            DocumentType::FixedWidth => "prn",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            DocumentType::HTML => "text/html; charset=utf-8",
+            DocumentType::Markdown => "text/markdown; charset=utf-8",
+            DocumentType::Text | DocumentType::FixedWidth => "text/plain; charset=utf-8",
+            DocumentType::PDF => "application/pdf",
+            DocumentType::Json => "application/json",
+            DocumentType::CSV => "text/csv; charset=utf-8",
+            DocumentType::RTF => "application/rtf",
+            DocumentType::DOCX => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            DocumentType::XML => "application/xml",
+            DocumentType::XLS => "application/vnd.ms-excel",
+            DocumentType::XLSX => {
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+            }
+            DocumentType::ODS => "application/vnd.oasis.opendocument.spreadsheet",
+            DocumentType::TSV => "text/tab-separated-values; charset=utf-8",
+        }
+    }
+
+    /// Whether the feature for this format is compiled in, so that
+    /// [`Document::parse`] can read it.
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            DocumentType::HTML => cfg!(feature = "html"),
+            DocumentType::Markdown => cfg!(feature = "markdown"),
+            DocumentType::Text => cfg!(feature = "text"),
+            DocumentType::PDF => cfg!(feature = "pdf"),
+            DocumentType::Json => cfg!(feature = "json"),
+            DocumentType::CSV => cfg!(feature = "csv"),
+            DocumentType::RTF => cfg!(feature = "rtf"),
+            DocumentType::DOCX => cfg!(feature = "docx"),
+            DocumentType::XML => cfg!(feature = "xml"),
+            DocumentType::XLS => cfg!(feature = "xls"),
+            DocumentType::XLSX => cfg!(feature = "xlsx"),
+            DocumentType::ODS => cfg!(feature = "ods"),
+            DocumentType::TSV => cfg!(feature = "tsv"),
+            DocumentType::FixedWidth => cfg!(feature = "fixed_width"),
+        }
+    }
+
+    /// Whether [`Document::generate`] can write this format. XLS is read only.
+    pub fn can_generate(&self) -> bool {
+        self.is_enabled() && *self != DocumentType::XLS
+    }
+
+    /// Guesses the format from the content: the signatures of the binary
+    /// formats, the root of HTML and XML markup, and the shape of the text
+    /// for JSON, CSV, TSV and Markdown. Text that fits none of them is
+    /// [`DocumentType::Text`], unknown binary data is `None`.
+    pub fn detect(bytes: &[u8]) -> Option<DocumentType> {
+        let contains = |needle: &[u8]| bytes.windows(needle.len()).any(|window| window == needle);
+
+        if bytes.starts_with(b"%PDF-") {
+            return Some(DocumentType::PDF);
+        }
+        if bytes.starts_with(&[0xD0, 0xCF, 0x11, 0xE0]) {
+            return Some(DocumentType::XLS);
+        }
+        if bytes.starts_with(b"PK\x03\x04") {
+            // The entry names of a ZIP archive are stored uncompressed
+            return if contains(b"word/document.xml") {
+                Some(DocumentType::DOCX)
+            } else if contains(b"xl/workbook.xml") {
+                Some(DocumentType::XLSX)
+            } else if contains(b"application/vnd.oasis.opendocument.spreadsheet") {
+                Some(DocumentType::ODS)
+            } else {
+                None
+            };
+        }
+        if bytes.starts_with(b"{\\rtf") {
+            return Some(DocumentType::RTF);
+        }
+
+        let text = std::str::from_utf8(bytes).ok()?;
+        let text = text.trim_start_matches('\u{feff}').trim();
+        if text.starts_with('<') {
+            let head = text.chars().take(1024).collect::<String>().to_lowercase();
+            return Some(
+                if head.starts_with("<!doctype html") || head.contains("<html") {
+                    DocumentType::HTML
+                } else {
+                    DocumentType::XML
+                },
+            );
+        }
+        if (text.starts_with('{') && text.ends_with('}'))
+            || (text.starts_with('[') && text.ends_with(']'))
+        {
+            return Some(DocumentType::Json);
+        }
+
+        let lines: Vec<&str> = text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .take(20)
+            .collect();
+        // Every line has the same number of delimiters
+        let delimited = |delimiter: char| {
+            let count = |line: &&str| line.matches(delimiter).count();
+            lines.len() > 1
+                && count(&lines[0]) > 0
+                && lines.iter().all(|l| count(l) == count(&lines[0]))
+        };
+        if delimited('\t') {
+            return Some(DocumentType::TSV);
+        }
+        if delimited(',') {
+            return Some(DocumentType::CSV);
+        }
+        let markdown = lines.iter().any(|line| {
+            let line = line.trim_start();
+            let heading = line.starts_with('#') && line.trim_start_matches('#').starts_with(' ');
+            heading
+                || ["```", "- ", "* ", "> ", "1. "]
+                    .iter()
+                    .any(|prefix| line.starts_with(prefix))
+                || line.contains("](")
+        });
+        Some(if markdown {
+            DocumentType::Markdown
+        } else {
+            DocumentType::Text
+        })
+    }
 }
 
+pub use bytes;
+
 #[cfg(test)]
 pub mod tests {
     use super::*;
@@ -832,6 +1491,8 @@ pub mod tests {
         DocumentType::XLS,
         DocumentType::XLSX,
         DocumentType::ODS,
+        DocumentType::TSV,
+        DocumentType::FixedWidth,
     ];
 
     #[test]
@@ -867,6 +1528,58 @@ pub mod tests {
             DocumentType::Text,
             DocumentType::from_extension("txt").unwrap()
         );
+        assert_eq!(
+            DocumentType::TSV,
+            DocumentType::from_extension("tsv").unwrap()
+        );
+        assert_eq!(
+            DocumentType::FixedWidth,
+            DocumentType::from_extension("prn").unwrap()
+        );
+        assert_eq!(
+            DocumentType::HTML,
+            DocumentType::from_extension("htm").unwrap()
+        );
+        for document_type in DocumentType::variants() {
+            assert_eq!(
+                Some(*document_type),
+                DocumentType::from_extension(document_type.extension())
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect() -> anyhow::Result<()> {
+        for (file, document_type) in [
+            ("document.pdf", DocumentType::PDF),
+            ("document.docx", DocumentType::DOCX),
+            ("document.xlsx", DocumentType::XLSX),
+            ("document.ods", DocumentType::ODS),
+            ("document.xls", DocumentType::XLS),
+            ("document.rtf", DocumentType::RTF),
+            ("document.xml", DocumentType::XML),
+            ("document.json", DocumentType::Json),
+            ("document.md", DocumentType::Markdown),
+            ("issue_104.html", DocumentType::HTML),
+        ] {
+            let bytes = std::fs::read(format!("test/data/{file}"))?;
+            assert_eq!(DocumentType::detect(&bytes), Some(document_type), "{file}");
+        }
+        assert_eq!(
+            DocumentType::detect(b"name,age\nAnn,30\n"),
+            Some(DocumentType::CSV)
+        );
+        assert_eq!(
+            DocumentType::detect(b"name\tage\nAnn\t30\n"),
+            Some(DocumentType::TSV)
+        );
+        assert_eq!(
+            DocumentType::detect(b"Hello, world"),
+            Some(DocumentType::Text)
+        );
+        assert_eq!(DocumentType::detect(b"PK\x03\x04"), None);
+        assert_eq!(DocumentType::detect(&[0xFF, 0xFE, 0x00]), None);
+        Ok(())
     }
 
     #[test]
@@ -891,6 +1604,18 @@ pub mod tests {
         assert_eq!(ImageType::from_str("gif").unwrap(), ImageType::Gif);
     }
 
+    #[test]
+    fn test_disk_image_loader() -> anyhow::Result<()> {
+        let loader = disk_image_loader("test/data");
+        assert_eq!(loader("small.png")?, std::fs::read("test/data/small.png")?);
+        assert!(loader("../Cargo.toml").is_err());
+        assert!(loader(&std::fs::canonicalize("Cargo.toml")?.to_string_lossy()).is_err());
+
+        let loader = unconfined_disk_image_loader("test/data");
+        assert!(loader("../../Cargo.toml").is_ok());
+        Ok(())
+    }
+
     #[test]
     fn test_image_new() {
         let bytes = Bytes::from("image".as_bytes());
@@ -928,6 +1653,146 @@ pub mod tests {
         assert_eq!("jpeg", ImageType::Jpeg.to_string());
     }
 
+    #[test]
+    fn test_image_type_from_bytes() -> anyhow::Result<()> {
+        let png = std::fs::read("test/data/small.png")?;
+        assert_eq!(ImageType::from_bytes(&png), Some(ImageType::Png));
+        assert_eq!(
+            ImageType::from_bytes(b"\xFF\xD8\xFF\xE0\x00\x10JFIF"),
+            Some(ImageType::Jpeg)
+        );
+        assert_eq!(
+            ImageType::from_bytes(b"RIFF\x24\x00\x00\x00WEBPVP8 "),
+            Some(ImageType::Webp)
+        );
+        assert_eq!(
+            ImageType::from_bytes(
+                b"<?xml version=\"1.0\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\"/>"
+            ),
+            Some(ImageType::SVG)
+        );
+        assert_eq!(ImageType::from_bytes(b"image"), None);
+        assert_eq!(ImageType::from_str("jpg").unwrap(), ImageType::Jpeg);
+        assert_eq!(ImageType::from_str("tif").unwrap(), ImageType::Tiff);
+
+        // The bytes win over a misleading extension
+        let image = ImageData::new(
+            Bytes::from(png),
+            "title".to_string(),
+            "alt".to_string(),
+            "https://example.com/image.jpg?size=large".to_string(),
+            "center".to_string(),
+            ImageDimension::default(),
+        );
+        assert_eq!(image.image_type(), &ImageType::Png);
+        Ok(())
+    }
+
+    #[cfg(feature = "docx")]
+    #[test]
+    fn test_image_convert() -> anyhow::Result<()> {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="20" height="10"><rect width="20" height="10" fill="red"/></svg>"#;
+        let image = ImageData::new(
+            Bytes::from(svg),
+            "title".to_string(),
+            "alt".to_string(),
+            "image.svg".to_string(),
+            "center".to_string(),
+            ImageDimension::default(),
+        );
+        assert_eq!(image.image_type(), &ImageType::SVG);
+
+        let png = image.convert(ImageType::Png)?;
+        assert_eq!(png.image_type(), &ImageType::Png);
+        assert_eq!(ImageType::from_bytes(png.bytes()), Some(ImageType::Png));
+        let jpeg = png.convert(ImageType::Jpeg)?;
+        assert_eq!(ImageType::from_bytes(jpeg.bytes()), Some(ImageType::Jpeg));
+
+        // A huge declared size is rendered scaled down to the pixel limit
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="400000" height="200000"/>"#;
+        let raster = rasterize_svg(svg.as_bytes(), 5_000)?;
+        assert_eq!((raster.width(), raster.height()), (100, 50));
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_display_size() -> anyhow::Result<()> {
+        assert_eq!(Length::from_str("120")?, Length::Px(120.0));
+        assert_eq!(Length::from_str("2.5cm")?, Length::Mm(25.0));
+        assert_eq!(Length::from_str(" 50 % ")?, Length::Percent(50.0));
+        assert!(Length::from_str("auto").is_err());
+        assert_eq!(Length::Pt(12.0).to_string(), "12pt");
+
+        let bytes = Bytes::from(std::fs::read("test/data/small.png")?);
+        let mut image = ImageData::new(
+            bytes,
+            "title".to_string(),
+            "alt".to_string(),
+            "small.png".to_string(),
+            "center".to_string(),
+            ImageDimension::default(),
+        );
+        let (width, height) = image.intrinsic_size().expect("PNG header");
+        let area = (1000.0, 1000.0);
+        assert_eq!(
+            image.display_size(area),
+            Some((width as f32 * 0.75, height as f32 * 0.75))
+        );
+
+        // The height follows the aspect ratio, and percentages are of the area
+        image.set_image_size(ImageDimension::new(Some(Length::Percent(10.0)), None));
+        let (display_width, display_height) = image.display_size(area).unwrap();
+        assert_eq!(display_width, 100.0);
+        assert!((display_height - 100.0 * height as f32 / width as f32).abs() < 0.01);
+
+        // Images larger than the area are scaled down to fit
+        image.set_image_size(ImageDimension::new(
+            Some(Length::Pt(2000.0)),
+            Some(Length::Pt(500.0)),
+        ));
+        assert_eq!(image.display_size(area), Some((1000.0, 250.0)));
+
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 40 30"/>"#;
+        image.set_image_bytes(Bytes::from(svg));
+        assert_eq!(image.intrinsic_size(), Some((40, 30)));
+
+        // Unquoted and non-ASCII values are skipped without panicking
+        let svg = r#"<svg width=é height='20' viewBox="0 0 40 30"/>"#;
+        image.set_image_bytes(Bytes::from(svg));
+        assert_eq!(image.intrinsic_size(), Some((40, 30)));
+        let svg = r#"<svg width=10 height=20 viewBox='0 0 40 30'/>"#;
+        image.set_image_bytes(Bytes::from(svg));
+        assert_eq!(image.intrinsic_size(), Some((40, 30)));
+        Ok(())
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn test_generate_with_saver() -> anyhow::Result<()> {
+        let bytes = Bytes::from(std::fs::read("test/data/small.png")?);
+        let image = ImageData::new(
+            bytes.clone(),
+            "title".to_string(),
+            "alt".to_string(),
+            "small.png".to_string(),
+            "center".to_string(),
+            ImageDimension::default(),
+        );
+        let document = Document::new(vec![Element::Image(image)]);
+
+        let saved = std::cell::RefCell::new(Vec::new());
+        let html = document.generate_with_saver(DocumentType::HTML, |bytes, path| {
+            saved.borrow_mut().push((path.to_string(), bytes.clone()));
+            Ok(())
+        })?;
+        assert_eq!(
+            saved.borrow().as_slice(),
+            [("image0.png".to_string(), bytes)]
+        );
+        assert!(String::from_utf8(html.to_vec())?.contains("src=\"image0.png\""));
+        Ok(())
+    }
+
     #[test]
     fn test_image_alignment() {
         assert_eq!(
@@ -944,5 +1809,3 @@ pub mod tests {
         );
     }
 }
-
-pub use bytes;
@@ -18,103 +18,129 @@ impl TransformerTrait for Transformer {
     /// # Returns
     /// A result containing a `Document` if successful, or an `anyhow::Error` in case of failure.
     fn parse(document: &Bytes) -> anyhow::Result<Document> {
-        // Deserialize the CSV data into a nested Vec structure.
-        let document = serialize_csv(document)?;
+        parse_delimited(document, b',')
+    }
 
-        // Check if the document is empty and return an empty `Document` if so.
-        if document.is_empty() {
-            return Ok(Document::new(Vec::new()));
-        }
+    fn generate(document: &Document) -> anyhow::Result<Bytes> {
+        generate_delimited(document, b',')
+    }
+}
+
+/// Parses delimiter-separated data into a `Document` holding a single `Element::Table`.
+/// The first record becomes the table headers. Shared by the CSV and TSV transformers.
+pub(crate) fn parse_delimited(document: &Bytes, delimiter: u8) -> anyhow::Result<Document> {
+    // Deserialize the CSV data into a nested Vec structure.
+    let document = serialize_delimited(document, delimiter)?;
+
+    // Check if the document is empty and return an empty `Document` if so.
+    if document.is_empty() {
+        return Ok(Document::new(Vec::new()));
+    }
+
+    // Create an iterator over the rows of the CSV data.
+    let mut document_iter = document.iter();
+
+    // Extract the first row, which contains the headers.
+    let headings: &Vec<String> = document_iter.next().unwrap();
+    let mut headers: Vec<TableHeader> = Vec::new();
+
+    // Process each header name to create `TableHeader` elements.
+    for name in headings {
+        headers.push(TableHeader {
+            element: Text {
+                text: name.clone(),
+                size: 8, // Default font size
+            },
+            width: 10.0, // Default width, can be adjusted as needed
+        });
+    }
 
-        // Create an iterator over the rows of the CSV data.
-        let mut document_iter = document.iter();
+    // Prepare to collect the table rows.
+    let mut rows: Vec<TableRow> = Vec::with_capacity(document.len() - 1);
 
-        // Extract the first row, which contains the headers.
-        let headings: &Vec<String> = document_iter.next().unwrap();
-        let mut headers: Vec<TableHeader> = Vec::new();
+    // Process each subsequent row in the CSV data.
+    for lines in document_iter {
+        let mut curr_row: Vec<TableCell> = Vec::with_capacity(headers.len());
 
-        // Process each header name to create `TableHeader` elements.
-        for name in headings {
-            headers.push(TableHeader {
+        // Create a `TableCell` for each cell in the row.
+        for cell in lines {
+            curr_row.push(TableCell {
                 element: Text {
-                    text: name.clone(),
+                    text: cell.clone(),
                     size: 8, // Default font size
                 },
-                width: 10.0, // Default width, can be adjusted as needed
             });
         }
 
-        // Prepare to collect the table rows.
-        let mut rows: Vec<TableRow> = Vec::with_capacity(document.len() - 1);
-
-        // Process each subsequent row in the CSV data.
-        for lines in document_iter {
-            let mut curr_row: Vec<TableCell> = Vec::with_capacity(headers.len());
-
-            // Create a `TableCell` for each cell in the row.
-            for cell in lines {
-                curr_row.push(TableCell {
-                    element: Text {
-                        text: cell.clone(),
-                        size: 8, // Default font size
-                    },
-                });
-            }
-
-            // Add the completed row to the rows collection.
-            rows.push(TableRow { cells: curr_row });
+        // Pad short rows, such as dumps that leave off trailing empty fields, to the header width.
+        while curr_row.len() < headers.len() {
+            curr_row.push(TableCell {
+                element: Text {
+                    text: String::new(),
+                    size: 8, // Default font size
+                },
+            });
         }
 
-        // Construct the `Document` with the table created from the CSV data.
-        Ok(Document::new(vec![Table { headers, rows }]))
+        // Add the completed row to the rows collection.
+        rows.push(TableRow { cells: curr_row });
     }
 
-    fn generate(document: &Document) -> anyhow::Result<Bytes> {
-        let elements = document.get_all_elements();
+    // Construct the `Document` with the table created from the CSV data.
+    Ok(Document::new(vec![Table { headers, rows }]))
+}
 
-        let mut data: Vec<Vec<String>> = Vec::new();
+/// Writes every `Element::Table` of the document as delimiter-separated records.
+pub(crate) fn generate_delimited(document: &Document, delimiter: u8) -> anyhow::Result<Bytes> {
+    let elements = document.get_all_elements();
 
-        for element in elements {
-            if let Table { headers, rows } = element {
-                // Create a new vector for the header row
-                let mut header_line = Vec::new();
-                for header in headers {
-                    if let Text { text, size: _ } = &header.element {
-                        header_line.push(text.clone())
-                    }
+    let mut data: Vec<Vec<String>> = Vec::new();
+
+    for element in elements {
+        if let Table { headers, rows } = element {
+            // Create a new vector for the header row
+            let mut header_line = Vec::new();
+            for header in headers {
+                if let Text { text, size: _ } = &header.element {
+                    header_line.push(text.clone())
                 }
-                // Push header row to data
-                data.push(header_line);
-
-                // Iterate over each row
-                for row in rows {
-                    let mut curr_line = Vec::new(); // This must be inside the loop
-                    for cell in &row.cells {
-                        if let Text { text, size: _ } = &cell.element {
-                            curr_line.push(text.clone())
-                        }
+            }
+            // Push header row to data
+            data.push(header_line);
+
+            // Iterate over each row
+            for row in rows {
+                let mut curr_line = Vec::new(); // This must be inside the loop
+                for cell in &row.cells {
+                    if let Text { text, size: _ } = &cell.element {
+                        curr_line.push(text.clone())
                     }
-                    // Push each row to data
-                    data.push(curr_line);
                 }
+                // Push each row to data
+                data.push(curr_line);
             }
         }
+    }
 
-        // Serialize the data into CSV format and convert it to Bytes
-        let csv_bytes = deserialize_csv(&data)?;
+    // Serialize the data into CSV format and convert it to Bytes
+    let csv_bytes = deserialize_delimited(&data, delimiter)?;
 
-        // Return Bytes and an empty HashMap for images or additional data
-        Ok(csv_bytes)
-    }
+    // Return Bytes and an empty HashMap for images or additional data
+    Ok(csv_bytes)
 }
 
-fn serialize_csv(csv_data: &Bytes) -> anyhow::Result<Vec<Vec<String>>> {
+fn serialize_delimited(csv_data: &Bytes, delimiter: u8) -> anyhow::Result<Vec<Vec<String>>> {
     // Convert Bytes to a UTF-8 string slice
     let data_str = std::str::from_utf8(csv_data)?;
 
     // Create a CSV reader from a string slice
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false) // We consider that we have no headers so that they'll be preserved
+        .delimiter(delimiter)
+        // Records may have fewer fields than the header, they are padded by the caller
+        .flexible(true)
+        // Tab-separated files have no quoting convention, a `"` is a literal character there
+        .quoting(delimiter != b'\t')
         .from_reader(data_str.as_bytes());
 
     let mut data = Vec::new();
@@ -129,13 +155,34 @@ fn serialize_csv(csv_data: &Bytes) -> anyhow::Result<Vec<Vec<String>>> {
     Ok(data)
 }
 
-fn deserialize_csv(data: &Vec<Vec<String>>) -> anyhow::Result<Bytes> {
+fn deserialize_delimited(data: &Vec<Vec<String>>, delimiter: u8) -> anyhow::Result<Bytes> {
     // Create a CSV writer that writes into a string
-    let mut wtr = csv::WriterBuilder::new().from_writer(vec![]);
+    let mut builder = csv::WriterBuilder::new();
+    builder.delimiter(delimiter).flexible(true);
+    if delimiter == b'\t' {
+        builder.quote_style(csv::QuoteStyle::Never);
+    }
+    let mut wtr = builder.from_writer(vec![]);
+
+    // Rows shorter than the header row are padded with empty fields
+    let width = data.first().map_or(0, Vec::len);
 
     // Iterate over the data and write each row to the CSV writer
     for row in data {
-        wtr.write_record(row)?;
+        let mut row = row.clone();
+        if row.len() < width {
+            row.resize(width, String::new());
+        }
+        if delimiter == b'\t' {
+            // TSV cannot represent tabs or line breaks inside a field, replace them with spaces
+            let row: Vec<String> = row
+                .iter()
+                .map(|field| field.replace(['\t', '\r', '\n'], " "))
+                .collect();
+            wtr.write_record(&row)?;
+        } else {
+            wtr.write_record(&row)?;
+        }
     }
 
     // After writing all data, we consume the writer to get the underlying string
@@ -149,7 +196,7 @@ mod tests {
     use log::info;
 
     use crate::core::{tests::init_logger, TransformerTrait};
-    use crate::csv::{self, deserialize_csv, serialize_csv};
+    use crate::csv::{self, deserialize_delimited, serialize_delimited};
     use crate::markdown;
 
     #[test]
@@ -178,7 +225,10 @@ mod tests {
             generated_string,
             // this assures that new changes made to test string won't
             // affect the result of tests if the implementation is correct
-            deserialize_csv(&serialize_csv(&bytes::Bytes::from(document))?)?
+            deserialize_delimited(
+                &serialize_delimited(&bytes::Bytes::from(document), b',')?,
+                b','
+            )?
         );
 
         Ok(())
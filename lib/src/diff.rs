@@ -0,0 +1,1209 @@
+//! Structural comparison of two documents.
+//!
+//! [`diff`] aligns the elements of both documents and reports which were
+//! inserted, deleted or changed. Texts, headers and paragraphs that changed
+//! carry a word diff, lists a diff of their items and tables a diff of their
+//! rows and cells. The content is compared, not the formatting: text sizes
+//! and header levels alone are not changes.
+//!
+//! [`DocumentDiff::redline`] writes the result as a redline in HTML
+//! (`<ins>` and `<del>`), DOCX (tracked changes) or Markdown (CriticMarkup).
+
+use crate::core::{Document, DocumentType, Element, ListItem, TableRow};
+use bytes::Bytes;
+use std::mem::discriminant;
+
+/// Elements of the same kind whose words are at least this similar are
+/// reported as changed instead of deleted and inserted.
+const SIMILARITY: f32 = 0.5;
+
+/// The changes between two documents, in the order of the new document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentDiff {
+    pub changes: Vec<Change>,
+}
+
+/// What became of an element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    /// The element is in both documents, as it is in the new one.
+    Unchanged(Element),
+    Inserted(Element),
+    Deleted(Element),
+    Changed {
+        old: Box<Element>,
+        new: Box<Element>,
+        detail: ElementDiff,
+    },
+}
+
+/// How a changed element differs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementDiff {
+    /// The words of a text, header or paragraph.
+    Words(Vec<WordChange>),
+    /// The items of a list.
+    List(Vec<Change>),
+    Table(TableDiff),
+    /// Elements that are not compared in parts, such as images, or elements
+    /// whose kind changed.
+    Replaced,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WordChange {
+    Equal(String),
+    Inserted(String),
+    Deleted(String),
+}
+
+/// The changes of a table. Cells are compared by their column.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableDiff {
+    pub headers: Vec<Vec<WordChange>>,
+    pub rows: Vec<RowChange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RowChange {
+    Unchanged(TableRow),
+    Inserted(TableRow),
+    Deleted(TableRow),
+    /// The word diff of each cell.
+    Changed(Vec<Vec<WordChange>>),
+}
+
+/// The number of top-level elements that were inserted, deleted or changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffSummary {
+    pub inserted: usize,
+    pub deleted: usize,
+    pub changed: usize,
+}
+
+/// The tracked changes of a DOCX redline are attributed to `author` at `date`,
+/// an ISO 8601 date such as `2024-05-01T12:00:00Z`.
+#[derive(Debug, Clone)]
+pub struct RedlineOptions {
+    pub author: String,
+    pub date: Option<String>,
+}
+
+impl Default for RedlineOptions {
+    fn default() -> Self {
+        RedlineOptions {
+            author: "Shiva".to_string(),
+            date: None,
+        }
+    }
+}
+
+/// Compares the elements of all bands of `old` and `new`.
+pub fn diff(old: &Document, new: &Document) -> DocumentDiff {
+    let old: Vec<Element> = old.get_all_elements().into_iter().cloned().collect();
+    let new: Vec<Element> = new.get_all_elements().into_iter().cloned().collect();
+    DocumentDiff {
+        changes: diff_elements(&old, &new),
+    }
+}
+
+impl DocumentDiff {
+    pub fn has_changes(&self) -> bool {
+        self.changes
+            .iter()
+            .any(|change| !matches!(change, Change::Unchanged(_)))
+    }
+
+    pub fn summary(&self) -> DiffSummary {
+        let mut summary = DiffSummary::default();
+        for change in &self.changes {
+            match change {
+                Change::Unchanged(_) => {}
+                Change::Inserted(_) => summary.inserted += 1,
+                Change::Deleted(_) => summary.deleted += 1,
+                Change::Changed { .. } => summary.changed += 1,
+            }
+        }
+        summary
+    }
+
+    /// Writes the redline as HTML, DOCX or Markdown.
+    pub fn redline(&self, document_type: DocumentType) -> anyhow::Result<Bytes> {
+        self.redline_with_options(document_type, &RedlineOptions::default())
+    }
+
+    #[cfg_attr(not(feature = "docx"), allow(unused_variables))]
+    pub fn redline_with_options(
+        &self,
+        document_type: DocumentType,
+        options: &RedlineOptions,
+    ) -> anyhow::Result<Bytes> {
+        let blocks = changes_blocks(&self.changes, 0);
+        match document_type {
+            DocumentType::HTML => Ok(Bytes::from(html_redline(&blocks))),
+            DocumentType::Markdown => Ok(Bytes::from(markdown_redline(&blocks))),
+            #[cfg(feature = "docx")]
+            DocumentType::DOCX => docx_redline(&blocks, options),
+            _ => Err(anyhow::anyhow!(
+                "A redline cannot be written as {document_type}, use HTML, DOCX or Markdown"
+            )),
+        }
+    }
+}
+
+// region:    ---alignment
+
+/// Aligns equal elements first, then pairs the similar ones left between them.
+fn diff_elements(old: &[Element], new: &[Element]) -> Vec<Change> {
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in common_subsequence(old, new, same_content)
+        .into_iter()
+        .chain([(old.len(), new.len())])
+    {
+        pair_gap(&old[i..next_i], &new[j..next_j], &mut changes);
+        if next_i < old.len() {
+            changes.push(Change::Unchanged(new[next_j].clone()));
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    changes
+}
+
+fn pair_gap(old: &[Element], new: &[Element], changes: &mut Vec<Change>) {
+    let mut next = 0;
+    for old in old {
+        let pair = new[next..]
+            .iter()
+            .position(|new| similar(old, new))
+            .map(|at| next + at);
+        match pair {
+            Some(at) => {
+                changes.extend(new[next..at].iter().cloned().map(Change::Inserted));
+                changes.push(Change::Changed {
+                    old: Box::new(old.clone()),
+                    new: Box::new(new[at].clone()),
+                    detail: element_diff(old, &new[at]),
+                });
+                next = at + 1;
+            }
+            None => changes.push(Change::Deleted(old.clone())),
+        }
+    }
+    changes.extend(new[next..].iter().cloned().map(Change::Inserted));
+}
+
+fn element_diff(old: &Element, new: &Element) -> ElementDiff {
+    match (old, new) {
+        (Element::List { elements: old, .. }, Element::List { elements: new, .. }) => {
+            let item = |item: &ListItem| item.element.clone();
+            let old: Vec<Element> = old.iter().map(item).collect();
+            let new: Vec<Element> = new.iter().map(item).collect();
+            ElementDiff::List(diff_elements(&old, &new))
+        }
+        (
+            Element::Table {
+                headers: old_headers,
+                rows: old_rows,
+            },
+            Element::Table {
+                headers: new_headers,
+                rows: new_rows,
+            },
+        ) => {
+            let old_headers: Vec<String> = old_headers
+                .iter()
+                .map(|header| element_text(&header.element))
+                .collect();
+            let new_headers: Vec<String> = new_headers
+                .iter()
+                .map(|header| element_text(&header.element))
+                .collect();
+            ElementDiff::Table(TableDiff {
+                headers: diff_cells(&old_headers, &new_headers),
+                rows: diff_rows(old_rows, new_rows),
+            })
+        }
+        (Element::Image(_), _) | (Element::Math { .. }, _) => ElementDiff::Replaced,
+        _ if discriminant(old) == discriminant(new) => {
+            ElementDiff::Words(diff_words(&element_text(old), &element_text(new)))
+        }
+        _ => ElementDiff::Replaced,
+    }
+}
+
+fn diff_rows(old: &[TableRow], new: &[TableRow]) -> Vec<RowChange> {
+    let old_cells: Vec<Vec<String>> = old.iter().map(row_texts).collect();
+    let new_cells: Vec<Vec<String>> = new.iter().map(row_texts).collect();
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in common_subsequence(&old_cells, &new_cells, |a, b| a == b)
+        .into_iter()
+        .chain([(old.len(), new.len())])
+    {
+        let mut next = j;
+        for at in i..next_i {
+            let words = old_cells[at].join(" ");
+            let pair = (next..next_j).find(|candidate| {
+                similarity(&words, &new_cells[*candidate].join(" ")) >= SIMILARITY
+            });
+            match pair {
+                Some(pair) => {
+                    changes.extend(new[next..pair].iter().cloned().map(RowChange::Inserted));
+                    changes.push(RowChange::Changed(diff_cells(
+                        &old_cells[at],
+                        &new_cells[pair],
+                    )));
+                    next = pair + 1;
+                }
+                None => changes.push(RowChange::Deleted(old[at].clone())),
+            }
+        }
+        changes.extend(new[next..next_j].iter().cloned().map(RowChange::Inserted));
+        if next_i < old.len() {
+            changes.push(RowChange::Unchanged(new[next_j].clone()));
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    changes
+}
+
+fn diff_cells(old: &[String], new: &[String]) -> Vec<Vec<WordChange>> {
+    (0..old.len().max(new.len()))
+        .map(|column| match (old.get(column), new.get(column)) {
+            (Some(old), Some(new)) => diff_words(old, new),
+            (Some(old), None) => vec![WordChange::Deleted(old.clone())],
+            (None, Some(new)) => vec![WordChange::Inserted(new.clone())],
+            (None, None) => vec![],
+        })
+        .collect()
+}
+
+fn row_texts(row: &TableRow) -> Vec<String> {
+    row.cells
+        .iter()
+        .map(|cell| element_text(&cell.element))
+        .collect()
+}
+
+fn same_content(old: &Element, new: &Element) -> bool {
+    match (old, new) {
+        (Element::Image(_), _) | (Element::Math { .. }, _) => old == new,
+        _ => discriminant(old) == discriminant(new) && element_text(old) == element_text(new),
+    }
+}
+
+fn similar(old: &Element, new: &Element) -> bool {
+    discriminant(old) == discriminant(new)
+        && similarity(&element_text(old), &element_text(new)) >= SIMILARITY
+}
+
+/// The share of the words that both texts have in common, from 0 to 1.
+fn similarity(old: &str, new: &str) -> f32 {
+    let old: Vec<&str> = old.split_whitespace().collect();
+    let new: Vec<&str> = new.split_whitespace().collect();
+    if old.is_empty() && new.is_empty() {
+        return 1.0;
+    }
+    let common = common_subsequence(&old, &new, |a, b| a == b).len();
+    2.0 * common as f32 / (old.len() + new.len()) as f32
+}
+
+/// Above this many comparisons the part between the common prefix and suffix
+/// is not aligned, it is shown as replaced as a whole.
+const MAX_COMPARISONS: usize = 25_000_000;
+
+/// The index pairs of a longest common subsequence. The common prefix and
+/// suffix are matched directly so that only what changed is compared, with
+/// Hirschberg's algorithm in space linear in the length of the input.
+fn common_subsequence<T>(old: &[T], new: &[T], eq: impl Fn(&T, &T) -> bool) -> Vec<(usize, usize)> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| eq(a, b)).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| eq(a, b))
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    if a.len().saturating_mul(b.len()) <= MAX_COMPARISONS {
+        hirschberg(a, b, (prefix, prefix), &eq, &mut pairs);
+    }
+    pairs.extend((0..suffix).map(|k| (old.len() - suffix + k, new.len() - suffix + k)));
+    pairs
+}
+
+/// Splits `a` in half and `b` where the subsequences of both halves are the
+/// longest, then aligns each half on its own. The pairs are pushed in order.
+fn hirschberg<T>(
+    a: &[T],
+    b: &[T],
+    offset: (usize, usize),
+    eq: &impl Fn(&T, &T) -> bool,
+    pairs: &mut Vec<(usize, usize)>,
+) {
+    if a.is_empty() || b.is_empty() {
+        return;
+    }
+    if a.len() == 1 {
+        if let Some(j) = b.iter().position(|b| eq(&a[0], b)) {
+            pairs.push((offset.0, offset.1 + j));
+        }
+        return;
+    }
+    let middle = a.len() / 2;
+    let forward = subsequence_lengths(a[..middle].iter(), b.iter(), eq);
+    let backward = subsequence_lengths(a[middle..].iter().rev(), b.iter().rev(), eq);
+    let split = (0..=b.len())
+        .max_by_key(|&j| (forward[j] + backward[b.len() - j], std::cmp::Reverse(j)))
+        .unwrap_or(0);
+    hirschberg(&a[..middle], &b[..split], offset, eq, pairs);
+    hirschberg(
+        &a[middle..],
+        &b[split..],
+        (offset.0 + middle, offset.1 + split),
+        eq,
+        pairs,
+    );
+}
+
+/// The lengths of the longest common subsequences of all of `a` and each
+/// prefix of `b`, keeping a single row.
+fn subsequence_lengths<'a, T: 'a>(
+    a: impl Iterator<Item = &'a T>,
+    b: impl Iterator<Item = &'a T> + Clone,
+    eq: &impl Fn(&T, &T) -> bool,
+) -> Vec<usize> {
+    let mut lengths = vec![0; b.clone().count() + 1];
+    for a in a {
+        let mut diagonal = 0;
+        for (j, b) in b.clone().enumerate() {
+            let above = lengths[j + 1];
+            lengths[j + 1] = if eq(a, b) {
+                diagonal + 1
+            } else {
+                above.max(lengths[j])
+            };
+            diagonal = above;
+        }
+    }
+    lengths
+}
+
+// endregion: ---alignment
+
+// region:    ---words
+
+/// Diffs the words of two texts. Changed words next to each other are
+/// grouped, the deleted ones before the inserted ones.
+pub fn diff_words(old: &str, new: &str) -> Vec<WordChange> {
+    let old = tokens(old);
+    let new = tokens(new);
+
+    let mut changes: Vec<WordChange> = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (next_i, next_j) in common_subsequence(&old, &new, |a, b| a == b)
+        .into_iter()
+        .chain([(old.len(), new.len())])
+    {
+        push_word(&mut changes, WordChange::Deleted(old[i..next_i].concat()));
+        push_word(&mut changes, WordChange::Inserted(new[j..next_j].concat()));
+        if next_i < old.len() {
+            push_word(&mut changes, WordChange::Equal(old[next_i].to_string()));
+        }
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+    group_words(changes)
+}
+
+/// Words, runs of whitespace and single punctuation marks.
+fn tokens(text: &str) -> Vec<&str> {
+    let mut tokens = vec![];
+    let mut start = 0;
+    let class = |c: char| {
+        if c.is_whitespace() {
+            0
+        } else if c.is_alphanumeric() {
+            1
+        } else {
+            2
+        }
+    };
+    let mut chars = text.char_indices().peekable();
+    while let Some((at, c)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        if class(c) == 2 || next.is_none_or(|next| class(next) != class(c)) {
+            let end = at + c.len_utf8();
+            tokens.push(&text[start..end]);
+            start = end;
+        }
+    }
+    tokens
+}
+
+fn push_word(changes: &mut Vec<WordChange>, change: WordChange) {
+    let merged = match (changes.last_mut(), &change) {
+        (_, WordChange::Equal(text) | WordChange::Inserted(text) | WordChange::Deleted(text))
+            if text.is_empty() =>
+        {
+            true
+        }
+        (Some(WordChange::Equal(last)), WordChange::Equal(text))
+        | (Some(WordChange::Inserted(last)), WordChange::Inserted(text))
+        | (Some(WordChange::Deleted(last)), WordChange::Deleted(text)) => {
+            last.push_str(text);
+            true
+        }
+        _ => false,
+    };
+    if !merged {
+        changes.push(change);
+    }
+}
+
+/// Whitespace between two changes becomes part of them, and each run of
+/// changes is written as one deletion followed by one insertion.
+fn group_words(changes: Vec<WordChange>) -> Vec<WordChange> {
+    let is_change = |change: Option<&WordChange>| {
+        matches!(
+            change,
+            Some(WordChange::Inserted(_) | WordChange::Deleted(_))
+        )
+    };
+
+    let mut grouped = vec![];
+    let (mut deleted, mut inserted) = (String::new(), String::new());
+    for (at, change) in changes.iter().enumerate() {
+        match change {
+            WordChange::Equal(text)
+                if !(text.trim().is_empty()
+                    && at > 0
+                    && is_change(changes.get(at - 1))
+                    && is_change(changes.get(at + 1))) =>
+            {
+                push_word(
+                    &mut grouped,
+                    WordChange::Deleted(std::mem::take(&mut deleted)),
+                );
+                push_word(
+                    &mut grouped,
+                    WordChange::Inserted(std::mem::take(&mut inserted)),
+                );
+                push_word(&mut grouped, change.clone());
+            }
+            WordChange::Equal(text) => {
+                deleted.push_str(text);
+                inserted.push_str(text);
+            }
+            WordChange::Deleted(text) => deleted.push_str(text),
+            WordChange::Inserted(text) => inserted.push_str(text),
+        }
+    }
+    push_word(&mut grouped, WordChange::Deleted(deleted));
+    push_word(&mut grouped, WordChange::Inserted(inserted));
+    grouped
+}
+
+/// The text of an element that is compared, inline texts are joined with a
+/// space unless they already are separated.
+fn element_text(element: &Element) -> String {
+    fn join(texts: impl Iterator<Item = String>, separator: &str) -> String {
+        let mut joined = String::new();
+        for text in texts.filter(|text| !text.is_empty()) {
+            let separated = joined.is_empty()
+                || joined.ends_with(char::is_whitespace)
+                || text.starts_with(char::is_whitespace);
+            if !separated {
+                joined.push_str(separator);
+            }
+            joined.push_str(&text);
+        }
+        joined
+    }
+
+    match element {
+        Element::Text { text, .. } | Element::Header { text, .. } => text.clone(),
+        Element::Paragraph { elements } => join(elements.iter().map(element_text), " "),
+        Element::Hyperlink { title, .. } => title.clone(),
+        Element::Math { latex, .. } => latex.clone(),
+        Element::Image(image) => image.alt().to_string(),
+        Element::List { elements, .. } => join(
+            elements.iter().map(|item| element_text(&item.element)),
+            "\n",
+        ),
+        Element::Table { headers, rows } => join(
+            headers
+                .iter()
+                .map(|header| element_text(&header.element))
+                .chain(rows.iter().map(|row| row_texts(row).join(" "))),
+            "\n",
+        ),
+        Element::DefinitionList { items } => join(
+            items.iter().map(|item| {
+                join(
+                    std::iter::once(item.term.clone())
+                        .chain(item.descriptions.iter().map(element_text)),
+                    " ",
+                )
+            }),
+            "\n",
+        ),
+    }
+}
+
+// endregion: ---words
+
+// region:    ---redline
+
+/// A line of the redline, written the same way in every format.
+enum Block {
+    Header(u8, Vec<WordChange>),
+    Paragraph(Vec<WordChange>),
+    Item {
+        depth: usize,
+        marker: String,
+        text: Vec<WordChange>,
+    },
+    Table(Vec<RedlineRow>),
+}
+
+struct RedlineRow {
+    header: bool,
+    cells: Vec<Vec<WordChange>>,
+}
+
+#[derive(Clone, Copy)]
+enum Mark {
+    Equal,
+    Inserted,
+    Deleted,
+}
+
+impl Mark {
+    fn word(self, text: String) -> Vec<WordChange> {
+        if text.is_empty() {
+            return vec![];
+        }
+        vec![match self {
+            Mark::Equal => WordChange::Equal(text),
+            Mark::Inserted => WordChange::Inserted(text),
+            Mark::Deleted => WordChange::Deleted(text),
+        }]
+    }
+}
+
+fn changes_blocks(changes: &[Change], depth: usize) -> Vec<Block> {
+    changes
+        .iter()
+        .flat_map(|change| change_blocks(change, depth))
+        .collect()
+}
+
+fn change_blocks(change: &Change, depth: usize) -> Vec<Block> {
+    match change {
+        Change::Unchanged(element) => element_blocks(element, Mark::Equal, depth),
+        Change::Inserted(element) => element_blocks(element, Mark::Inserted, depth),
+        Change::Deleted(element) => element_blocks(element, Mark::Deleted, depth),
+        Change::Changed { old, new, detail } => match (detail, new.as_ref()) {
+            (ElementDiff::Words(words), Element::Header { level, .. }) => {
+                vec![Block::Header(*level, words.clone())]
+            }
+            (ElementDiff::Words(words), _) => vec![Block::Paragraph(words.clone())],
+            (ElementDiff::List(items), Element::List { numbered, .. }) => {
+                list_blocks(items, *numbered, depth)
+            }
+            (ElementDiff::Table(table), _) => vec![table_block(table)],
+            _ => {
+                let mut blocks = element_blocks(old, Mark::Deleted, depth);
+                blocks.extend(element_blocks(new, Mark::Inserted, depth));
+                blocks
+            }
+        },
+    }
+}
+
+fn element_blocks(element: &Element, mark: Mark, depth: usize) -> Vec<Block> {
+    match element {
+        Element::Header { level, text } => vec![Block::Header(*level, mark.word(text.clone()))],
+        Element::List { elements, numbered } => {
+            let items: Vec<Change> = elements
+                .iter()
+                .map(|item| match mark {
+                    Mark::Equal => Change::Unchanged(item.element.clone()),
+                    Mark::Inserted => Change::Inserted(item.element.clone()),
+                    Mark::Deleted => Change::Deleted(item.element.clone()),
+                })
+                .collect();
+            list_blocks(&items, *numbered, depth)
+        }
+        Element::Table { headers, rows } => {
+            let mut redline_rows = vec![];
+            if !headers.is_empty() {
+                redline_rows.push(RedlineRow {
+                    header: true,
+                    cells: headers
+                        .iter()
+                        .map(|header| mark.word(element_text(&header.element)))
+                        .collect(),
+                });
+            }
+            redline_rows.extend(rows.iter().map(|row| {
+                RedlineRow {
+                    header: false,
+                    cells: row_texts(row)
+                        .into_iter()
+                        .map(|text| mark.word(text))
+                        .collect(),
+                }
+            }));
+            vec![Block::Table(redline_rows)]
+        }
+        Element::Image(image) => vec![Block::Paragraph(
+            mark.word(format!("[Image: {}]", image.alt())),
+        )],
+        Element::Hyperlink { title, url, .. } => {
+            vec![Block::Paragraph(mark.word(format!("{title} ({url})")))]
+        }
+        Element::DefinitionList { items } => items
+            .iter()
+            .flat_map(|item| {
+                std::iter::once(Block::Paragraph(mark.word(item.term.clone()))).chain(
+                    item.descriptions
+                        .iter()
+                        .map(move |description| Block::Item {
+                            depth,
+                            marker: String::new(),
+                            text: mark.word(element_text(description)),
+                        }),
+                )
+            })
+            .collect(),
+        _ => vec![Block::Paragraph(mark.word(element_text(element)))],
+    }
+}
+
+/// Items are numbered as in the new list, a deleted item shows the number
+/// of the item that follows it. An item deleted right before an inserted one
+/// is shown with it as one changed item.
+fn list_blocks(items: &[Change], numbered: bool, depth: usize) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut number = 0;
+    let mut items = items.iter().peekable();
+    while let Some(item) = items.next() {
+        let replaced = match (item, items.peek()) {
+            (Change::Deleted(old), Some(Change::Inserted(new)))
+                if !matches!(old, Element::List { .. }) && !matches!(new, Element::List { .. }) =>
+            {
+                let mut words = Mark::Deleted.word(element_text(old));
+                words.extend(Mark::Inserted.word(element_text(new)));
+                items.next();
+                Some(Change::Changed {
+                    old: Box::new(old.clone()),
+                    new: Box::new(new.clone()),
+                    detail: ElementDiff::Words(words),
+                })
+            }
+            _ => None,
+        };
+        let item = replaced.as_ref().unwrap_or(item);
+        let element = match item {
+            Change::Unchanged(element) | Change::Inserted(element) | Change::Deleted(element) => {
+                element
+            }
+            Change::Changed { new, .. } => new,
+        };
+        if matches!(element, Element::List { .. }) {
+            blocks.extend(change_blocks(item, depth + 1));
+            continue;
+        }
+        let marker = if !numbered {
+            "-".to_string()
+        } else if matches!(item, Change::Deleted(_)) {
+            format!("{}.", number + 1)
+        } else {
+            number += 1;
+            format!("{number}.")
+        };
+        for block in change_blocks(item, depth) {
+            blocks.push(match block {
+                Block::Paragraph(text) | Block::Header(_, text) => Block::Item {
+                    depth,
+                    marker: marker.clone(),
+                    text,
+                },
+                block => block,
+            });
+        }
+    }
+    blocks
+}
+
+fn table_block(table: &TableDiff) -> Block {
+    let mut rows = vec![];
+    if !table.headers.is_empty() {
+        rows.push(RedlineRow {
+            header: true,
+            cells: table.headers.clone(),
+        });
+    }
+    for row in &table.rows {
+        let cells = match row {
+            RowChange::Unchanged(row) => marked_cells(row, Mark::Equal),
+            RowChange::Inserted(row) => marked_cells(row, Mark::Inserted),
+            RowChange::Deleted(row) => marked_cells(row, Mark::Deleted),
+            RowChange::Changed(cells) => cells.clone(),
+        };
+        rows.push(RedlineRow {
+            header: false,
+            cells,
+        });
+    }
+    Block::Table(rows)
+}
+
+fn marked_cells(row: &TableRow, mark: Mark) -> Vec<Vec<WordChange>> {
+    row_texts(row)
+        .into_iter()
+        .map(|text| mark.word(text))
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn html_words(words: &[WordChange]) -> String {
+    words
+        .iter()
+        .map(|word| match word {
+            WordChange::Equal(text) => escape_html(text),
+            WordChange::Inserted(text) => format!("<ins>{}</ins>", escape_html(text)),
+            WordChange::Deleted(text) => format!("<del>{}</del>", escape_html(text)),
+        })
+        .collect()
+}
+
+fn html_redline(blocks: &[Block]) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n<title>Redline</title>\n\
+         <style>\nins { color: #1a7f37; text-decoration: underline; }\n\
+         del { color: #cf222e; text-decoration: line-through; }\n\
+         table { border-collapse: collapse; }\n\
+         th, td { border: 1px solid #999; padding: 2px 6px; }\n</style>\n</head>\n<body>\n",
+    );
+    for block in blocks {
+        match block {
+            Block::Header(level, text) => {
+                let level = (*level).clamp(1, 6);
+                html.push_str(&format!("<h{level}>{}</h{level}>\n", html_words(text)));
+            }
+            Block::Paragraph(text) => html.push_str(&format!("<p>{}</p>\n", html_words(text))),
+            Block::Item {
+                depth,
+                marker,
+                text,
+            } => html.push_str(&format!(
+                "<p style=\"margin-left: {}em\">{} {}</p>\n",
+                (depth + 1) * 2,
+                escape_html(marker),
+                html_words(text)
+            )),
+            Block::Table(rows) => {
+                html.push_str("<table>\n");
+                for row in rows {
+                    let tag = if row.header { "th" } else { "td" };
+                    html.push_str("<tr>");
+                    for cell in &row.cells {
+                        html.push_str(&format!("<{tag}>{}</{tag}>", html_words(cell)));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                html.push_str("</table>\n");
+            }
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+fn markdown_words(words: &[WordChange]) -> String {
+    words
+        .iter()
+        .map(|word| match word {
+            WordChange::Equal(text) => text.clone(),
+            WordChange::Inserted(text) => format!("{{++{text}++}}"),
+            WordChange::Deleted(text) => format!("{{--{text}--}}"),
+        })
+        .collect()
+}
+
+fn markdown_redline(blocks: &[Block]) -> String {
+    let mut markdown = vec![];
+    for block in blocks {
+        markdown.push(match block {
+            Block::Header(level, text) => {
+                format!("{} {}", "#".repeat(*level as usize), markdown_words(text))
+            }
+            Block::Paragraph(text) => markdown_words(text),
+            Block::Item {
+                depth,
+                marker,
+                text,
+            } => {
+                let marker = if marker.is_empty() { ":" } else { marker };
+                format!("{}{marker} {}", "  ".repeat(*depth), markdown_words(text))
+            }
+            Block::Table(rows) => {
+                let columns = rows.iter().map(|row| row.cells.len()).max().unwrap_or(0);
+                let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+                let cells = |row: &RedlineRow| {
+                    (0..columns)
+                        .map(|column| {
+                            row.cells
+                                .get(column)
+                                .map(|cell| markdown_words(cell).replace('|', "\\|"))
+                                .unwrap_or_default()
+                        })
+                        .collect()
+                };
+                let mut lines = vec![];
+                let mut rows = rows.iter().peekable();
+                match rows.next_if(|row| row.header) {
+                    Some(header) => lines.push(line(cells(header))),
+                    None => lines.push(line(vec![String::new(); columns])),
+                }
+                lines.push(line(vec!["---".to_string(); columns]));
+                lines.extend(rows.map(|row| line(cells(row))));
+                lines.join("\n")
+            }
+        });
+    }
+    // Consecutive items form one list
+    let mut text = String::new();
+    for (at, line) in markdown.iter().enumerate() {
+        if at > 0 {
+            let items = matches!(blocks[at - 1], Block::Item { .. })
+                && matches!(blocks[at], Block::Item { .. });
+            text.push_str(if items { "\n" } else { "\n\n" });
+        }
+        text.push_str(line);
+    }
+    text.push('\n');
+    text
+}
+
+#[cfg(feature = "docx")]
+fn docx_redline(blocks: &[Block], options: &RedlineOptions) -> anyhow::Result<Bytes> {
+    use docx_rs::{Delete, Docx, Insert, Paragraph, Run, Table, TableCell, TableRow};
+
+    let paragraph = |words: &[WordChange], size: usize, bold: bool| {
+        let run = |text: &str, deleted: bool| {
+            let run = if deleted {
+                Run::new().add_delete_text(text)
+            } else {
+                Run::new().add_text(text)
+            };
+            let run = run.size(size);
+            if bold {
+                run.bold()
+            } else {
+                run
+            }
+        };
+        let mut paragraph = Paragraph::new();
+        for word in words {
+            paragraph = match word {
+                WordChange::Equal(text) => paragraph.add_run(run(text, false)),
+                WordChange::Inserted(text) => {
+                    let insert = Insert::new(run(text, false)).author(&options.author);
+                    paragraph.add_insert(match &options.date {
+                        Some(date) => insert.date(date),
+                        None => insert,
+                    })
+                }
+                WordChange::Deleted(text) => {
+                    let delete = Delete::new()
+                        .add_run(run(text, true))
+                        .author(&options.author);
+                    paragraph.add_delete(match &options.date {
+                        Some(date) => delete.date(date),
+                        None => delete,
+                    })
+                }
+            };
+        }
+        paragraph
+    };
+
+    let mut doc = Docx::new();
+    for block in blocks {
+        match block {
+            Block::Header(level, text) => {
+                let size = match level {
+                    1 => 18,
+                    2 => 16,
+                    _ => 14,
+                };
+                doc = doc.add_paragraph(paragraph(text, size * 2, true));
+            }
+            Block::Paragraph(text) => doc = doc.add_paragraph(paragraph(text, 24, false)),
+            Block::Item {
+                depth,
+                marker,
+                text,
+            } => {
+                let mut words = vec![WordChange::Equal(format!("{marker} "))];
+                words.extend(text.iter().cloned());
+                doc = doc.add_paragraph(paragraph(&words, 24, false).indent(
+                    Some(360 * (*depth as i32 + 1)),
+                    None,
+                    None,
+                    None,
+                ));
+            }
+            Block::Table(rows) => {
+                let rows = rows
+                    .iter()
+                    .map(|row| {
+                        TableRow::new(
+                            row.cells
+                                .iter()
+                                .map(|cell| {
+                                    TableCell::new().add_paragraph(paragraph(cell, 24, row.header))
+                                })
+                                .collect(),
+                        )
+                    })
+                    .collect();
+                doc = doc.add_table(Table::new(rows));
+            }
+        }
+    }
+
+    let mut cursor = std::io::Cursor::new(Vec::new());
+    doc.build().pack(&mut cursor)?;
+    Ok(Bytes::from(cursor.into_inner()))
+}
+
+// endregion: ---redline
+
+#[cfg(test)]
+mod tests {
+    use crate::core::tests::init_logger;
+    use crate::core::*;
+    use crate::diff::*;
+    use log::info;
+
+    fn text(text: &str) -> Element {
+        Element::Text {
+            text: text.to_string(),
+            size: 12,
+        }
+    }
+
+    fn table(rows: &[&[&str]]) -> Element {
+        Element::Table {
+            headers: vec![],
+            rows: rows
+                .iter()
+                .map(|cells| TableRow {
+                    cells: cells
+                        .iter()
+                        .map(|cell| TableCell {
+                            element: text(cell),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff_words() {
+        assert_eq!(
+            diff_words("The term is 12 months.", "The term is 24 months."),
+            vec![
+                WordChange::Equal("The term is ".to_string()),
+                WordChange::Deleted("12".to_string()),
+                WordChange::Inserted("24".to_string()),
+                WordChange::Equal(" months.".to_string()),
+            ]
+        );
+        // Whitespace between changed words joins them
+        assert_eq!(
+            diff_words("pay seller", "notify buyer"),
+            vec![
+                WordChange::Deleted("pay seller".to_string()),
+                WordChange::Inserted("notify buyer".to_string()),
+            ]
+        );
+        assert_eq!(
+            diff_words("same", "same"),
+            vec![WordChange::Equal("same".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_common_subsequence() {
+        let old: Vec<char> = "ABCBDAB".chars().collect();
+        let new: Vec<char> = "BDCABA".chars().collect();
+        let pairs = common_subsequence(&old, &new, |a, b| a == b);
+        assert_eq!(pairs.len(), 4);
+        assert!(pairs.windows(2).all(|w| w[0].0 < w[1].0 && w[0].1 < w[1].1));
+        assert!(pairs.iter().all(|&(i, j)| old[i] == new[j]));
+
+        // Long inputs are aligned in linear space
+        let old: Vec<usize> = (0..3000).collect();
+        let mut new = old.clone();
+        new[1] = 5000;
+        new[2998] = 5000;
+        assert_eq!(common_subsequence(&old, &new, |a, b| a == b).len(), 2998);
+
+        // Above the limit only the common prefix and suffix are matched
+        let old: Vec<usize> = (0..6000).collect();
+        let mut new = old.clone();
+        new[1] = 9000;
+        new[5998] = 9000;
+        assert_eq!(
+            common_subsequence(&old, &new, |a, b| a == b),
+            vec![(0, 0), (5999, 5999)]
+        );
+    }
+
+    #[test]
+    fn test_diff() -> anyhow::Result<()> {
+        init_logger();
+        let old = Document::new(vec![
+            Element::Header {
+                level: 1,
+                text: "Agreement".to_string(),
+            },
+            text("The buyer pays within 30 days."),
+            text("This clause is removed entirely."),
+            table(&[&["Fee", "100"], &["Deposit", "50"]]),
+        ]);
+        let new = Document::new(vec![
+            Element::Header {
+                level: 1,
+                text: "Agreement".to_string(),
+            },
+            text("The buyer pays within 14 days."),
+            table(&[&["Fee", "120"], &["Deposit", "50"], &["Penalty", "10"]]),
+            text("A new clause."),
+        ]);
+
+        let diff = diff(&old, &new);
+        info!("{:?}", diff);
+        assert!(diff.has_changes());
+        assert_eq!(
+            diff.summary(),
+            DiffSummary {
+                inserted: 1,
+                deleted: 1,
+                changed: 2,
+            }
+        );
+        assert!(matches!(diff.changes[0], Change::Unchanged(_)));
+        assert!(matches!(
+            &diff.changes[1],
+            Change::Changed { detail: ElementDiff::Words(words), .. }
+                if words.contains(&WordChange::Inserted("14".to_string()))
+        ));
+        assert!(matches!(diff.changes[2], Change::Deleted(_)));
+        let Change::Changed {
+            detail: ElementDiff::Table(table),
+            ..
+        } = &diff.changes[3]
+        else {
+            panic!("Expected a changed table");
+        };
+        assert_eq!(
+            table.rows[0],
+            RowChange::Changed(vec![
+                vec![WordChange::Equal("Fee".to_string())],
+                vec![
+                    WordChange::Deleted("100".to_string()),
+                    WordChange::Inserted("120".to_string()),
+                ],
+            ])
+        );
+        assert!(matches!(table.rows[1], RowChange::Unchanged(_)));
+        assert!(matches!(table.rows[2], RowChange::Inserted(_)));
+        assert!(matches!(diff.changes[4], Change::Inserted(_)));
+
+        assert!(!super::diff(&old, &old).has_changes());
+        Ok(())
+    }
+
+    #[test]
+    fn test_redline() -> anyhow::Result<()> {
+        init_logger();
+        let old = Document::new(vec![
+            text("Payment is due in 30 days."),
+            Element::List {
+                elements: vec![
+                    ListItem {
+                        element: text("First"),
+                        checked: None,
+                    },
+                    ListItem {
+                        element: text("Second"),
+                        checked: None,
+                    },
+                ],
+                numbered: true,
+            },
+        ]);
+        let new = Document::new(vec![
+            text("Payment is due in 14 days."),
+            Element::List {
+                elements: vec![
+                    ListItem {
+                        element: text("First"),
+                        checked: None,
+                    },
+                    ListItem {
+                        element: text("Third"),
+                        checked: None,
+                    },
+                ],
+                numbered: true,
+            },
+            table(&[&["a", "b"]]),
+        ]);
+        let diff = diff(&old, &new);
+
+        let html = String::from_utf8(diff.redline(DocumentType::HTML)?.to_vec())?;
+        info!("{html}");
+        assert!(html.contains("<p>Payment is due in <del>30</del><ins>14</ins> days.</p>"));
+        assert!(html.contains("2. <del>Second</del>"));
+        assert!(html.contains("<td><ins>a</ins></td>"));
+
+        let markdown = String::from_utf8(diff.redline(DocumentType::Markdown)?.to_vec())?;
+        info!("{markdown}");
+        assert_eq!(
+            markdown,
+            "Payment is due in {--30--}{++14++} days.\n\n\
+             1. First\n2. {--Second--}{++Third++}\n\n\
+             |  |  |\n| --- | --- |\n| {++a++} | {++b++} |\n"
+        );
+
+        #[cfg(all(feature = "docx", feature = "zip"))]
+        {
+            let docx = diff.redline(DocumentType::DOCX)?;
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(docx.to_vec()))?;
+            let mut xml = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name("word/document.xml")?, &mut xml)?;
+            assert!(xml.contains("<w:ins "));
+            assert!(xml.contains("<w:delText"));
+        }
+
+        assert!(diff.redline(DocumentType::CSV).is_err());
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::core::{
-    Document, Element, ImageDimension, ListItem, TableCell, TableRow, TransformerTrait,
+    Document, Element, ImageData, ImageType, ListItem, TableCell, TableRow, TransformerTrait,
+    IMAGE_AREA,
 };
 
 use bytes::Bytes;
@@ -13,6 +14,19 @@ use std::io::Cursor;
 
 pub struct Transformer;
 
+//docx-rs panics on bytes it cannot decode, so the image is converted to PNG and measured here
+fn picture(image: &ImageData) -> anyhow::Result<Pic> {
+    let png = image.convert(ImageType::Png)?;
+    let (width, height) = image::ImageReader::new(Cursor::new(png.bytes()))
+        .with_guessed_format()?
+        .into_dimensions()?;
+    Ok(Pic::new_with_dimensions(
+        png.bytes().to_vec(),
+        width,
+        height,
+    ))
+}
+
 //function re_size input picture (if size very big)
 fn re_size_picture(pic: Pic) -> Pic {
     let mut pic = pic;
@@ -43,6 +57,64 @@ fn re_size_picture(pic: Pic) -> Pic {
     pic
 }
 
+/// Task list items have no native checkbox in the generated numbering, so the
+/// state is written as a ballot box in front of the item text.
+fn task_list_element(item: &ListItem) -> Element {
+    match (&item.element, item.checked) {
+        (Element::Text { text, size }, Some(checked)) => Element::Text {
+            text: format!("{} {text}", if checked { '☑' } else { '☐' }),
+            size: *size,
+        },
+        (element, _) => element.clone(),
+    }
+}
+
+const MATH_NAMESPACE: &str =
+    r#"xmlns:m="http://schemas.openxmlformats.org/officeDocument/2006/math""#;
+
+/// docx-rs cannot write Office Math, so equations are added as empty runs with
+/// a run style of their own and swapped for OMML in the serialized
+/// `document.xml`. Text is escaped, so it never looks like the style element.
+fn math_run(index: usize) -> Run {
+    Run::new().style(&format!("ShivaMath{index}"))
+}
+
+fn math_marker(index: usize) -> String {
+    format!("<w:rStyle w:val=\"ShivaMath{index}\"")
+}
+
+fn insert_math(document: &[u8], equations: &[(String, bool)]) -> anyhow::Result<Vec<u8>> {
+    let mut xml = String::from_utf8(document.to_vec())?;
+    for (index, (latex, display)) in equations.iter().enumerate() {
+        let marker = math_marker(index);
+        let Some(position) = xml.find(&marker) else {
+            continue;
+        };
+        let start = xml[..position]
+            .rfind("<w:r>")
+            .ok_or_else(|| anyhow::anyhow!("Math placeholder is not inside a run"))?;
+        let end = position
+            + xml[position..]
+                .find("</w:r>")
+                .ok_or_else(|| anyhow::anyhow!("Math placeholder is not inside a run"))?
+            + "</w:r>".len();
+        let math = format!(
+            "<m:oMath>{}</m:oMath>",
+            crate::math::to_omml(&crate::math::parse(latex))
+        );
+        let math = if *display {
+            format!("<m:oMathPara>{math}</m:oMathPara>")
+        } else {
+            math
+        };
+        xml.replace_range(start..end, &math);
+    }
+    if !equations.is_empty() && !xml.contains("xmlns:m=") {
+        xml = xml.replacen("<w:document ", &format!("<w:document {MATH_NAMESPACE} "), 1);
+    }
+    Ok(xml.into_bytes())
+}
+
 //recursive function for processing nested elements in Element::List
 fn detect_element_in_list(doc: &mut Docx, element: &Element, numbered: bool, depth: usize) {
     match element {
@@ -106,7 +178,8 @@ fn detect_element_in_list(doc: &mut Docx, element: &Element, numbered: bool, dep
 
         Element::List { elements, numbered } => {
             for list_item in elements {
-                detect_element_in_list(doc, &list_item.element, *numbered, depth + 1);
+                let element = task_list_element(list_item);
+                detect_element_in_list(doc, &element, *numbered, depth + 1);
             }
         }
 
@@ -162,6 +235,7 @@ impl TransformerTrait for Transformer {
                                 text: list_text,
                                 size: 12,
                             },
+                            checked: None,
                         };
 
                         let numbered = numbering_property
@@ -183,6 +257,7 @@ impl TransformerTrait for Transformer {
                                 };
                                 list_items.push(ListItem {
                                     element: nested_list,
+                                    checked: None,
                                 });
                             } else if level < last_level {
                                 // Finish the current list and start a new one
@@ -372,6 +447,8 @@ impl TransformerTrait for Transformer {
             .add_abstract_numbering(abstract_numbering)
             .add_numbering(Numbering::new(2, 2));
 
+        let mut equations: Vec<(String, bool)> = vec![];
+        let mut figures = 0;
         // TODO: Consider to refactor this code to use the new #Band Enum (header, footer, etc)
         for element in &document.get_all_elements() {
             match element {
@@ -394,24 +471,29 @@ impl TransformerTrait for Transformer {
                 }
 
                 Element::Paragraph { elements } => {
+                    let mut paragraph = Paragraph::new();
                     for paragraph_element in elements {
                         match paragraph_element {
                             Element::Text { text, size } => {
-                                doc =
-                                    doc.add_paragraph(Paragraph::new().add_run(
-                                        Run::new().add_text(text).size(*size as usize * 2),
-                                    ));
+                                paragraph = paragraph
+                                    .add_run(Run::new().add_text(text).size(*size as usize * 2));
+                            }
+                            Element::Math { latex, display } => {
+                                paragraph = paragraph.add_run(math_run(equations.len()));
+                                equations.push((latex.clone(), *display));
                             }
                             _ => {
                                 error!("Unknown paragraph element");
                             }
                         }
                     }
+                    doc = doc.add_paragraph(paragraph);
                 }
 
                 Element::List { elements, numbered } => {
                     for list_item in elements {
-                        detect_element_in_list(&mut doc, &list_item.element, *numbered, 0);
+                        let element = task_list_element(list_item);
+                        detect_element_in_list(&mut doc, &element, *numbered, 0);
                     }
                 }
 
@@ -431,25 +513,33 @@ impl TransformerTrait for Transformer {
                 }
 
                 Element::Image(image) => {
-                    let mut pic = Pic::new(image.bytes());
-
-                    if let &ImageDimension {
-                        width: Some(width),
-                        height: Some(height),
-                    } = &image.size()
-                    {
-                        let width = width.parse().unwrap_or(0);
-                        let height = height.parse().unwrap_or(0);
-                        if width > 0 && height > 0 {
-                            pic = pic.size(width, height);
+                    let mut pic = match picture(image) {
+                        Ok(pic) => pic,
+                        Err(e) => {
+                            warn!("Skipping image {}: {e}", image.title());
+                            continue;
                         }
-                    }
+                    };
 
-                    pic = re_size_picture(pic);
+                    pic = match image.display_size(IMAGE_AREA) {
+                        // 12700 EMU per point
+                        Some((width, height)) => {
+                            pic.size((width * 12700.0) as u32, (height * 12700.0) as u32)
+                        }
+                        None => re_size_picture(pic),
+                    };
 
                     let paragraph = Paragraph::new().add_run(Run::new().add_image(pic));
 
                     doc = doc.add_paragraph(paragraph);
+
+                    if !image.caption().is_empty() {
+                        figures += 1;
+                        doc = doc
+                            .add_paragraph(Paragraph::new().add_run(
+                                Run::new().add_text(image.figure_label(figures)).italic(),
+                            ));
+                    }
                 }
 
                 Element::Table { headers, rows } => {
@@ -490,13 +580,53 @@ impl TransformerTrait for Transformer {
                     let table = docx_rs::Table::new(table_rows);
                     doc = doc.add_table(table);
                 }
+
+                Element::DefinitionList { items } => {
+                    for item in items {
+                        doc = doc.add_paragraph(
+                            Paragraph::new().add_run(Run::new().add_text(&item.term).bold()),
+                        );
+                        for description in &item.descriptions {
+                            let text = match description {
+                                Element::Text { text, .. } => text.clone(),
+                                Element::Paragraph { elements } => elements
+                                    .iter()
+                                    .filter_map(|element| match element {
+                                        Element::Text { text, .. } => Some(text.as_str()),
+                                        _ => None,
+                                    })
+                                    .collect::<Vec<&str>>()
+                                    .join(" "),
+                                _ => {
+                                    warn!("Unsupported definition list description");
+                                    continue;
+                                }
+                            };
+                            doc = doc.add_paragraph(
+                                Paragraph::new().add_run(Run::new().add_text(text)).indent(
+                                    Some(720),
+                                    None,
+                                    None,
+                                    None,
+                                ),
+                            );
+                        }
+                    }
+                }
+
+                Element::Math { latex, display } => {
+                    doc = doc.add_paragraph(Paragraph::new().add_run(math_run(equations.len())));
+                    equations.push((latex.clone(), *display));
+                }
             }
         }
 
         let buffer = Vec::new();
         let mut cursor = Cursor::new(buffer);
 
-        doc.build().pack(&mut cursor)?;
+        let mut xml = doc.build();
+        xml.document = insert_math(&xml.document, &equations)?;
+        xml.pack(&mut cursor)?;
         let buffer = cursor.into_inner();
 
         Ok(bytes::Bytes::from(buffer))
@@ -558,4 +688,78 @@ mod tests {
         assert_eq!(expected_result, parsed);
         Ok(())
     }
+
+    #[test]
+    fn test_generate_undecodable_image() -> anyhow::Result<()> {
+        let image = crate::core::ImageData::new(
+            Bytes::from("not an image"),
+            "bad".to_string(),
+            "bad".to_string(),
+            "bad.png".to_string(),
+            String::new(),
+            Default::default(),
+        );
+        let document = Document::new(vec![
+            Element::Image(image),
+            Element::Text {
+                text: "after".to_string(),
+                size: 12,
+            },
+        ]);
+        let generated = docx::Transformer::generate(&document)?;
+        let parsed = docx::Transformer::parse(&generated)?;
+        assert!(parsed
+            .get_all_elements()
+            .iter()
+            .all(|element| !matches!(element, Element::Image(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_math() -> anyhow::Result<()> {
+        let text = |text: &str| Element::Text {
+            text: text.to_string(),
+            size: 12,
+        };
+        let document = Document::new(vec![
+            Element::Math {
+                latex: "x^2".to_string(),
+                display: true,
+            },
+            Element::Paragraph {
+                elements: vec![
+                    text("Inline "),
+                    Element::Math {
+                        latex: "y_1".to_string(),
+                        display: false,
+                    },
+                    text(r#" and <w:rStyle w:val="ShivaMath0"/> stay text"#),
+                ],
+            },
+            Element::DefinitionList {
+                items: vec![crate::core::DefinitionItem {
+                    term: "Term".to_string(),
+                    descriptions: vec![text("Meaning")],
+                }],
+            },
+        ]);
+        let generated = docx::Transformer::generate(&document)?;
+        std::fs::write("test/data/output/math.docx", &generated)?;
+
+        #[cfg(feature = "zip")]
+        {
+            let mut archive = zip::ZipArchive::new(std::io::Cursor::new(generated.to_vec()))?;
+            let mut xml = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name("word/document.xml")?, &mut xml)?;
+            assert!(xml.contains(MATH_NAMESPACE));
+            assert!(xml.contains("<m:oMathPara><m:oMath><m:sSup>"));
+            assert!(xml.contains(
+                "<m:oMath><m:sSub><m:e><m:r><m:t xml:space=\"preserve\">y</m:t></m:r></m:e>"
+            ));
+            assert_eq!(xml.matches("<m:oMath>").count(), 2);
+            assert!(xml.contains("&lt;w:rStyle w:val=&quot;ShivaMath0&quot;/&gt; stay text"));
+            assert!(!xml.contains("<w:rStyle w:val=\"ShivaMath"));
+        }
+        Ok(())
+    }
 }
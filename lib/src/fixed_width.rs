@@ -0,0 +1,326 @@
+use crate::core::{
+    Document,
+    Element::{self, Hyperlink, Paragraph, Table, Text},
+    TableCell, TableHeader, TableRow, TransformerTrait,
+};
+use bytes::Bytes;
+
+pub struct Transformer;
+
+/// Options for parsing fixed-width text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FixedWidthOptions {
+    /// Width of every column in characters. The last column always extends to the end of the line.
+    /// When `None`, column boundaries are inferred from the alignment of the header line.
+    pub widths: Option<Vec<usize>>,
+}
+
+impl TransformerTrait for Transformer {
+    fn parse(document: &Bytes) -> anyhow::Result<Document> {
+        Transformer::parse_with_options(document, &FixedWidthOptions::default())
+    }
+
+    /// Writes every `Element::Table` of the document as fixed-width text.
+    /// Each column is padded to `TableHeader.width` characters, widened when a value does not fit.
+    fn generate(document: &Document) -> anyhow::Result<Bytes> {
+        let mut output = String::new();
+
+        for element in document.get_all_elements() {
+            if let Table { headers, rows } = element {
+                if !output.is_empty() {
+                    output.push('\n');
+                }
+
+                let header_line: Vec<String> =
+                    headers.iter().map(|h| element_text(&h.element)).collect();
+                let lines: Vec<Vec<String>> = rows
+                    .iter()
+                    .map(|row| row.cells.iter().map(|c| element_text(&c.element)).collect())
+                    .collect();
+
+                let columns = lines
+                    .iter()
+                    .map(Vec::len)
+                    .chain(std::iter::once(header_line.len()))
+                    .max()
+                    .unwrap_or(0);
+                let mut widths: Vec<usize> = (0..columns)
+                    .map(|i| {
+                        headers
+                            .get(i)
+                            .map_or(0, |h| h.width.max(0.0).round() as usize)
+                    })
+                    .collect();
+                for line in lines.iter().chain(std::iter::once(&header_line)) {
+                    for (i, value) in line.iter().enumerate() {
+                        // keep at least one space between columns so they can be parsed back
+                        widths[i] = widths[i].max(value.chars().count() + 1);
+                    }
+                }
+
+                for line in std::iter::once(&header_line).chain(lines.iter()) {
+                    output.push_str(&pad_line(line, &widths));
+                    output.push('\n');
+                }
+            }
+        }
+
+        Ok(Bytes::from(output))
+    }
+}
+
+impl Transformer {
+    /// Parses fixed-width text into a `Document` holding a single `Element::Table`.
+    /// The first non-empty line is used as the table headers. A ruler line made of
+    /// `-`, `=` or `+` characters right below it is skipped.
+    pub fn parse_with_options(
+        document: &Bytes,
+        options: &FixedWidthOptions,
+    ) -> anyhow::Result<Document> {
+        let document = std::str::from_utf8(document)?;
+        let lines: Vec<Vec<char>> = document
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.trim_end().chars().collect())
+            .collect();
+
+        if lines.is_empty() {
+            return Ok(Document::new(Vec::new()));
+        }
+
+        let mut lines: Vec<&Vec<char>> = lines.iter().collect();
+        let ruler = match lines.get(1) {
+            Some(line) if is_ruler(line) => Some(lines.remove(1)),
+            _ => None,
+        };
+
+        let starts = match &options.widths {
+            Some(widths) => widths
+                .iter()
+                .scan(0, |offset, width| {
+                    let start = *offset;
+                    *offset += width;
+                    Some(start)
+                })
+                .collect(),
+            None => match ruler {
+                Some(ruler) => infer_starts_from_ruler(ruler),
+                None => infer_starts_from_header(&lines),
+            },
+        };
+
+        let mut lines = lines.into_iter();
+        let headers = match lines.next() {
+            Some(header_line) => split_line(header_line, &starts)
+                .into_iter()
+                .enumerate()
+                .map(|(i, text)| TableHeader {
+                    element: Text { text, size: 8 },
+                    width: column_width(&starts, i, header_line.len()) as f32,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let rows = lines
+            .map(|line| TableRow {
+                cells: split_line(line, &starts)
+                    .into_iter()
+                    .map(|text| TableCell {
+                        element: Text { text, size: 8 },
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Document::new(vec![Table { headers, rows }]))
+    }
+}
+
+fn is_ruler(line: &[char]) -> bool {
+    line.iter().any(|c| !c.is_whitespace())
+        && line
+            .iter()
+            .all(|c| c.is_whitespace() || matches!(c, '-' | '=' | '+'))
+}
+
+/// Every run of ruler characters marks a column.
+fn infer_starts_from_ruler(ruler: &[char]) -> Vec<usize> {
+    let mut starts = vec![0];
+    for i in 1..ruler.len() {
+        if ruler[i - 1].is_whitespace() && !ruler[i].is_whitespace() {
+            starts.push(i);
+        }
+    }
+    starts
+}
+
+/// A column starts at a header word when some character between it and the previous
+/// header word is blank on every line. The column starts right after the last such
+/// blank, so that right-aligned values wider than their header stay in it. This keeps
+/// multi-word headers like `First Name` together unless the data below them is split
+/// at the same position.
+fn infer_starts_from_header(lines: &[&Vec<char>]) -> Vec<usize> {
+    let mut starts = vec![0];
+    let Some(header) = lines.first() else {
+        return starts;
+    };
+    let blank_everywhere = |i: usize| {
+        lines
+            .iter()
+            .all(|line| line.get(i).is_none_or(|c| c.is_whitespace()))
+    };
+    let mut word_end = 0;
+    for i in 1..header.len() {
+        if header[i].is_whitespace() {
+            continue;
+        }
+        if header[i - 1].is_whitespace() {
+            if let Some(gap) = (word_end..i).rev().find(|&gap| blank_everywhere(gap)) {
+                starts.push(gap + 1);
+            }
+        }
+        word_end = i + 1;
+    }
+    starts
+}
+
+fn column_width(starts: &[usize], index: usize, line_len: usize) -> usize {
+    match starts.get(index + 1) {
+        Some(next) => next - starts[index],
+        None => line_len.saturating_sub(starts[index]),
+    }
+}
+
+fn split_line(line: &[char], starts: &[usize]) -> Vec<String> {
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts
+                .get(i + 1)
+                .copied()
+                .unwrap_or(line.len())
+                .min(line.len());
+            if start >= end {
+                String::new()
+            } else {
+                line[start..end]
+                    .iter()
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            }
+        })
+        .collect()
+}
+
+fn pad_line(values: &[String], widths: &[usize]) -> String {
+    let mut line = String::new();
+    for (i, width) in widths.iter().enumerate() {
+        let value = values.get(i).map(String::as_str).unwrap_or_default();
+        line.push_str(value);
+        line.push_str(&" ".repeat(width.saturating_sub(value.chars().count())));
+    }
+    line.trim_end().to_string()
+}
+
+fn element_text(element: &Element) -> String {
+    match element {
+        Text { text, .. } => text.clone(),
+        Hyperlink { title, .. } => title.clone(),
+        Paragraph { elements } => elements
+            .iter()
+            .map(element_text)
+            .collect::<Vec<String>>()
+            .join(" "),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::tests::init_logger;
+    use crate::core::*;
+    use crate::fixed_width::*;
+    use log::info;
+
+    fn table(document: &Document) -> (Vec<String>, Vec<Vec<String>>) {
+        let Table { headers, rows } = document.get_all_elements()[0] else {
+            panic!("Expected a table");
+        };
+        (
+            headers.iter().map(|h| element_text(&h.element)).collect(),
+            rows.iter()
+                .map(|r| r.cells.iter().map(|c| element_text(&c.element)).collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_inferred_from_header() -> anyhow::Result<()> {
+        init_logger();
+        let document = r#"
+ACCOUNT   First Name   BALANCE
+000123    John Smith    100.00
+004567    Jane           25.50
+"#;
+        let parsed = Transformer::parse(&Bytes::from(document))?;
+        let (headers, rows) = table(&parsed);
+        assert_eq!(headers, vec!["ACCOUNT", "First Name", "BALANCE"]);
+        assert_eq!(rows[0], vec!["000123", "John Smith", "100.00"]);
+        assert_eq!(rows[1], vec!["004567", "Jane", "25.50"]);
+
+        // Right-aligned values wider than their header
+        let document = "NAME     QTY\nApple  12345\nPear      10\n";
+        let parsed = Transformer::parse(&Bytes::from(document))?;
+        let (headers, rows) = table(&parsed);
+        assert_eq!(headers, vec!["NAME", "QTY"]);
+        assert_eq!(rows, vec![vec!["Apple", "12345"], vec!["Pear", "10"]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_with_ruler_and_options() -> anyhow::Result<()> {
+        init_logger();
+        let document = "CODE NAME\n---- --------\nA1   Widget\nB22  Gadget\n";
+        let parsed = Transformer::parse(&Bytes::from(document))?;
+        assert_eq!(table(&parsed).1[1], vec!["B22", "Gadget"]);
+
+        // Only the line below the headers is a ruler, data made of dashes is kept
+        let document = "CODE NAME\nA1   Widget\n---  ----\n";
+        let parsed = Transformer::parse(&Bytes::from(document))?;
+        assert_eq!(
+            table(&parsed).1,
+            vec![vec!["A1", "Widget"], vec!["---", "----"]]
+        );
+
+        let document = "ID NAME\n01Widget\n02Gadget\n";
+        let options = FixedWidthOptions {
+            widths: Some(vec![2, 6]),
+        };
+        let parsed = Transformer::parse_with_options(&Bytes::from(document), &options)?;
+        let (headers, rows) = table(&parsed);
+        assert_eq!(headers, vec!["ID", "NAME"]);
+        assert_eq!(rows, vec![vec!["01", "Widget"], vec!["02", "Gadget"]]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate() -> anyhow::Result<()> {
+        init_logger();
+        let csv = "Name,Amount\nAlice,100\nBartholomew,5\n";
+        let parsed = crate::csv::Transformer::parse(&Bytes::from(csv))?;
+        let generated = Transformer::generate(&parsed)?;
+        let generated_text = std::str::from_utf8(&generated)?;
+        info!("{}", generated_text);
+        assert_eq!(
+            generated_text,
+            "Name        Amount\nAlice       100\nBartholomew 5\n"
+        );
+
+        let reparsed = Transformer::parse(&generated)?;
+        assert_eq!(table(&reparsed), table(&parsed));
+        Ok(())
+    }
+}
@@ -1,15 +1,17 @@
 use crate::core::*;
+use base64::Engine;
 use bytes::Bytes;
 use ego_tree::{iter::Children, NodeRef};
 
 use crate::core::Element::{Header, Hyperlink, Image, List, Paragraph, Table, Text};
 use scraper::{Html, Node};
+use std::collections::HashMap;
 
 pub struct Transformer;
 
 impl TransformerTrait for Transformer {
     fn parse(document: &Bytes) -> anyhow::Result<Document> {
-        Transformer::parse_with_loader(document, disk_image_loader("."))
+        Transformer::parse_with_loader(document, default_image_loader())
     }
 
     fn generate(document: &Document) -> anyhow::Result<Bytes> {
@@ -19,7 +21,97 @@ impl TransformerTrait for Transformer {
 impl TransformerWithImageLoaderSaverTrait for Transformer {
     fn parse_with_loader<F>(document: &Bytes, image_loader: F) -> anyhow::Result<Document>
     where
-        F: Fn(&str) -> anyhow::Result<Bytes>,
+        F: ResourceResolver,
+    {
+        Transformer::parse_with_options(document, &ParseOptions::default(), image_loader)
+    }
+
+    fn generate_with_saver<F>(document: &Document, image_saver: F) -> anyhow::Result<Bytes>
+    where
+        F: Fn(&Bytes, &str) -> anyhow::Result<()>,
+    {
+        Transformer::generate_with_options(document, &GenerateOptions::default(), image_saver)
+    }
+}
+
+/// Built-in stylesheets for full documents.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Theme {
+    /// No `<style>` element.
+    None,
+    /// Readable defaults: a centered column, system fonts and bordered tables.
+    #[default]
+    Plain,
+    /// Close to how GitHub renders markdown.
+    Github,
+    /// Serif text sized in points, for printing or saving as PDF.
+    Print,
+}
+
+impl Theme {
+    pub fn css(&self) -> &'static str {
+        match self {
+            Theme::None => "",
+            Theme::Plain => PLAIN_CSS,
+            Theme::Github => GITHUB_CSS,
+            Theme::Print => PRINT_CSS,
+        }
+    }
+}
+
+const PLAIN_CSS: &str = "body { max-width: 50em; margin: 2em auto; padding: 0 1em; font-family: system-ui, sans-serif; line-height: 1.5; color: #222; }
+img { max-width: 100%; height: auto; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #999; padding: 0.25em 0.5em; text-align: left; }
+th { background: #f0f0f0; }
+";
+
+const GITHUB_CSS: &str = "body { box-sizing: border-box; max-width: 980px; margin: 0 auto; padding: 45px; font-family: -apple-system, BlinkMacSystemFont, \"Segoe UI\", \"Noto Sans\", Helvetica, Arial, sans-serif; font-size: 16px; line-height: 1.5; color: #1f2328; }
+h1, h2 { padding-bottom: 0.3em; border-bottom: 1px solid #d1d9e0; }
+a { color: #0969da; text-decoration: none; }
+a:hover { text-decoration: underline; }
+img { max-width: 100%; }
+table { border-spacing: 0; border-collapse: collapse; margin: 0 0 16px; }
+th, td { padding: 6px 13px; border: 1px solid #d1d9e0; }
+th { font-weight: 600; }
+tr:nth-child(2n) { background-color: #f6f8fa; }
+code, .math { font-family: ui-monospace, SFMono-Regular, Menlo, Consolas, monospace; }
+";
+
+const PRINT_CSS: &str = "@page { margin: 2cm; }
+body { font-family: Georgia, \"Times New Roman\", serif; font-size: 11pt; line-height: 1.4; color: #000; }
+h1, h2, h3, h4, h5, h6 { page-break-after: avoid; }
+img, table, pre { page-break-inside: avoid; max-width: 100%; }
+table { border-collapse: collapse; }
+th, td { border: 0.5pt solid #000; padding: 2pt 4pt; }
+a { color: #000; }
+a[href^=\"http\"]::after { content: \" (\" attr(href) \")\"; font-size: 90%; }
+";
+
+/// Options for generating HTML.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerateOptions {
+    /// Document title. Defaults to the text of the first heading.
+    pub title: Option<String>,
+    pub theme: Theme,
+    /// CSS added after the theme, so its rules take precedence.
+    pub stylesheet: Option<String>,
+    /// Write images as `data:` URIs instead of passing them to the image saver,
+    /// which makes the output a single self-contained file.
+    pub embed_images: bool,
+    /// Only write the body content, without doctype, `<head>` or `<body>`, for
+    /// embedding into another page.
+    pub fragment: bool,
+}
+
+impl Transformer {
+    pub fn parse_with_options<F>(
+        document: &Bytes,
+        options: &ParseOptions,
+        image_loader: F,
+    ) -> anyhow::Result<Document>
+    where
+        F: ResourceResolver,
     {
         let html = String::from_utf8(document.to_vec())?;
         let document = Html::parse_document(&html);
@@ -28,20 +120,33 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
         let image_loader = ImageLoader {
             function: image_loader,
         };
-        parse_html(
-            document.root_element().children(),
-            &mut elements,
-            &image_loader,
-        )?;
-        Ok(Document::new(elements))
+        let mut metadata = parse_metadata(&document);
+        let root = *document.root_element();
+        let root = if options.main_content {
+            if metadata.author.is_none() {
+                metadata.author = find_byline(root);
+            }
+            main_content(root).unwrap_or(root)
+        } else {
+            root
+        };
+        parse_html(root.children(), &mut elements, &image_loader, options)?;
+
+        let mut document = Document::new(elements);
+        document.set_metadata(metadata);
+        Ok(document)
     }
 
-    fn generate_with_saver<F>(document: &Document, image_saver: F) -> anyhow::Result<Bytes>
+    pub fn generate_with_options<F>(
+        document: &Document,
+        options: &GenerateOptions,
+        image_saver: F,
+    ) -> anyhow::Result<Bytes>
     where
         F: Fn(&Bytes, &str) -> anyhow::Result<()>,
     {
         let mut html = String::new();
-        let mut image_num: i32 = 0;
+        let mut counters = Counters::default();
         let image_saver = ImageSaver {
             function: image_saver,
         };
@@ -61,17 +166,20 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
             }
         });
 
-        html.push_str("<!DOCTYPE html>\n<html>\n<body>\n");
+        if !options.fragment {
+            html.push_str(&head(document, options));
+            html.push_str("<body>\n");
+        }
 
         let all_elements: Vec<&Element> = document.get_all_elements();
 
         for element in &all_elements {
             match element {
                 Element::Header { level, text } => {
-                    html.push_str(&format!("<h{level}>{text}</h{level}>\n"));
+                    html.push_str(&format!("<h{level}>{}</h{level}>\n", escape_text(text)));
                 }
                 Element::Text { text, size: _ } => {
-                    html.push_str(&format!("<p>{text}</p>\n"));
+                    html.push_str(&format!("<p>{}</p>\n", escape_text(text)));
                 }
                 Paragraph { elements } => {
                     html.push_str("<p>");
@@ -79,8 +187,9 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                     for child in elements {
                         html.push_str(&generate_html_for_element(
                             child,
-                            &mut image_num,
+                            &mut counters,
                             &image_saver,
+                            options,
                         )?);
                     }
 
@@ -90,12 +199,13 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                     elements: _,
                     numbered: _,
                 } => {
-                    let list = generate_html_for_element(element, &mut image_num, &image_saver)?;
+                    let list =
+                        generate_html_for_element(element, &mut counters, &image_saver, options)?;
 
                     html.push_str(&list);
                 }
                 Table { headers, rows } => {
-                    let mut table_html = String::from("<table  border=\"1\">\n");
+                    let mut table_html = String::from("<table>\n");
 
                     if !headers.is_empty() {
                         table_html.push_str("<tr>\n");
@@ -103,8 +213,9 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                         for header in headers {
                             let header_html = generate_html_for_element(
                                 &header.element,
-                                &mut image_num,
+                                &mut counters,
                                 &image_saver,
+                                options,
                             )?;
 
                             table_html.push_str(&format!("<th>{header_html}</th>\n"));
@@ -118,8 +229,9 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                         for cell in &row.cells {
                             let cell_html = generate_html_for_element(
                                 &cell.element,
-                                &mut image_num,
+                                &mut counters,
                                 &image_saver,
+                                options,
                             )?;
 
                             table_html.push_str(&format!("<td>{cell_html}</td>\n"));
@@ -131,19 +243,165 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                     table_html.push_str("</table>\n");
                     html.push_str(&table_html)
                 }
-                _ => {}
+                Image(image) if !image.caption().is_empty() => {
+                    let figure =
+                        generate_html_for_element(element, &mut counters, &image_saver, options)?;
+                    html.push_str(&format!("{figure}\n"));
+                }
+                Image(_) | Hyperlink { .. } => {
+                    let inline =
+                        generate_html_for_element(element, &mut counters, &image_saver, options)?;
+                    html.push_str(&format!("<p>{inline}</p>\n"));
+                }
+                Element::DefinitionList { .. } | Element::Math { .. } => {
+                    let block =
+                        generate_html_for_element(element, &mut counters, &image_saver, options)?;
+                    html.push_str(&block);
+                }
             }
         }
 
-        html.push_str("</body>\n</html>");
+        if !options.fragment {
+            html.push_str("</body>\n</html>");
+        }
 
         Ok(Bytes::from(html))
     }
 }
 
+fn head(document: &Document, options: &GenerateOptions) -> String {
+    let metadata = document.get_metadata();
+    let title = options
+        .title
+        .clone()
+        .or(metadata.title.clone())
+        .or_else(|| {
+            document
+                .get_all_elements()
+                .iter()
+                .find_map(|element| match element {
+                    Header { text, .. } => Some(text.clone()),
+                    _ => None,
+                })
+        });
+    let mut head = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n");
+    head.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\" />\n");
+    if let Some(title) = title {
+        head.push_str(&format!("<title>{}</title>\n", escape_text(&title)));
+    }
+    if let Some(author) = &metadata.author {
+        head.push_str(&format!(
+            "<meta name=\"author\" content=\"{}\" />\n",
+            escape_attribute(author)
+        ));
+    }
+    if let Some(description) = &metadata.description {
+        head.push_str(&format!(
+            "<meta name=\"description\" content=\"{}\" />\n",
+            escape_attribute(description)
+        ));
+    }
+    let mut css = options.theme.css().to_string();
+    if let Some(stylesheet) = &options.stylesheet {
+        css.push_str(stylesheet);
+        if !css.ends_with('\n') {
+            css.push('\n');
+        }
+    }
+    if !css.is_empty() {
+        // `</style` would end the element early
+        head.push_str(&format!("<style>\n{}</style>\n", css.replace("</", "<\\/")));
+    }
+    head.push_str("</head>\n");
+    head
+}
+
+/// Options for parsing HTML.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseOptions {
+    /// Treat the input as untrusted: `script`, `style`, `iframe` and other
+    /// executable or embedded content is dropped with its children, tags outside
+    /// the known set are unwrapped, and links or images with `javascript:`-like
+    /// URLs lose their target. Attributes such as event handlers are never
+    /// carried into the document model.
+    pub sanitize: bool,
+    /// Keep only the main article of a web page, the way reader views do.
+    /// Navigation, banners, sidebars and footers are left out, and the byline
+    /// is moved into the document metadata.
+    pub main_content: bool,
+}
+
+/// Tags kept when sanitizing.
+const ALLOWED_TAGS: &[&str] = &[
+    "html",
+    "head",
+    "body",
+    "title",
+    "p",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "img",
+    "ul",
+    "ol",
+    "li",
+    "a",
+    "table",
+    "thead",
+    "tbody",
+    "tfoot",
+    "tr",
+    "th",
+    "td",
+    "caption",
+    "div",
+    "span",
+    "section",
+    "article",
+    "main",
+    "header",
+    "footer",
+    "aside",
+    "figure",
+    "figcaption",
+    "blockquote",
+    "pre",
+    "code",
+    "b",
+    "strong",
+    "i",
+    "em",
+    "u",
+    "s",
+    "del",
+    "ins",
+    "mark",
+    "small",
+    "sub",
+    "sup",
+    "abbr",
+    "cite",
+    "q",
+    "br",
+    "hr",
+    "dl",
+    "dt",
+    "dd",
+];
+
+/// Tags removed together with their content when sanitizing.
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "iframe", "frame", "frameset", "object", "embed", "applet", "noscript",
+    "template", "svg", "math", "form", "input", "button", "select", "textarea", "link", "meta",
+    "base",
+];
+
 struct ImageLoader<F>
 where
-    F: Fn(&str) -> anyhow::Result<Bytes>,
+    F: ResourceResolver,
 {
     pub function: F,
 }
@@ -159,214 +417,798 @@ fn parse_html<F>(
     children: Children<Node>,
     elements: &mut Vec<Element>,
     image_loader: &ImageLoader<F>,
+    options: &ParseOptions,
 ) -> anyhow::Result<()>
 where
-    F: Fn(&str) -> anyhow::Result<Bytes>,
+    F: ResourceResolver,
 {
+    // Text, links and images between block elements, merged into one paragraph
+    let mut inline: Vec<Element> = Vec::new();
     for child in children {
         match child.value() {
-            Node::Element(ref element) => match element.name() {
-                "table" => {
-                    let mut headers: Vec<TableHeader> = Vec::new();
-                    let mut rows: Vec<TableRow> = Vec::new();
-                    for table_child in child.children() {
-                        for child in table_child.children() {
-                            match child.value() {
-                                Node::Element(ref table_element) => match table_element.name() {
-                                    "tr" => {
-                                        let mut cells: Vec<TableCell> = Vec::new();
-                                        let mut is_header = false;
-                                        for tr_child in child.children() {
-                                            match tr_child.value() {
-                                                Node::Element(ref tr_element) => {
-                                                    match tr_element.name() {
-                                                        "th" => {
-                                                            is_header = true;
-                                                            let mut header_elements: Vec<Element> =
-                                                                Vec::new();
-                                                            parse_html(
-                                                                tr_child.children(),
-                                                                &mut header_elements,
-                                                                image_loader,
-                                                            )?;
-                                                            headers.extend(
-                                                                header_elements.into_iter().map(
-                                                                    |element| TableHeader {
-                                                                        element,
-                                                                        width: 10.0,
-                                                                    },
-                                                                ),
-                                                            );
-                                                        }
-                                                        "td" => {
-                                                            let mut cell_elements: Vec<Element> =
-                                                                Vec::new();
-                                                            parse_html(
-                                                                tr_child.children(),
-                                                                &mut cell_elements,
-                                                                image_loader,
-                                                            )?;
-                                                            cells.extend(
-                                                                cell_elements.into_iter().map(
-                                                                    |element| TableCell { element },
-                                                                ),
-                                                            );
+            // Executable and embedded content is dropped, unknown tags are unwrapped
+            Node::Element(ref element)
+                if options.sanitize && DROPPED_TAGS.contains(&element.name()) => {}
+            Node::Element(ref element)
+                if options.sanitize && !ALLOWED_TAGS.contains(&element.name()) =>
+            {
+                flush_inline(&mut inline, elements);
+                parse_html(child.children(), elements, image_loader, options)?;
+            }
+            Node::Element(ref element) if is_skipped(element) => {}
+            Node::Element(ref element) if options.main_content && is_unlikely(element) => {}
+            Node::Element(ref element) if INLINE_TAGS.contains(&element.name()) => {
+                parse_inline(child, &mut inline, image_loader, options)?;
+            }
+            Node::Element(ref element) => {
+                flush_inline(&mut inline, elements);
+                match element.name() {
+                    "table" => {
+                        let mut headers: Vec<TableHeader> = Vec::new();
+                        let mut rows: Vec<TableRow> = Vec::new();
+                        for table_child in child.children() {
+                            for child in table_child.children() {
+                                match child.value() {
+                                    Node::Element(ref table_element) => {
+                                        match table_element.name() {
+                                            "tr" => {
+                                                let mut cells: Vec<TableCell> = Vec::new();
+                                                let mut is_header = false;
+                                                for tr_child in child.children() {
+                                                    match tr_child.value() {
+                                                        Node::Element(ref tr_element) => {
+                                                            match tr_element.name() {
+                                                                "th" => {
+                                                                    is_header = true;
+                                                                    let mut header_elements: Vec<
+                                                                        Element,
+                                                                    > = Vec::new();
+                                                                    parse_html(
+                                                                        tr_child.children(),
+                                                                        &mut header_elements,
+                                                                        image_loader,
+                                                                        options,
+                                                                    )?;
+                                                                    headers.extend(
+                                                                        header_elements
+                                                                            .into_iter()
+                                                                            .map(|element| {
+                                                                                TableHeader {
+                                                                                    element,
+                                                                                    width: 10.0,
+                                                                                }
+                                                                            }),
+                                                                    );
+                                                                }
+                                                                "td" => {
+                                                                    let mut cell_elements: Vec<
+                                                                        Element,
+                                                                    > = Vec::new();
+                                                                    parse_html(
+                                                                        tr_child.children(),
+                                                                        &mut cell_elements,
+                                                                        image_loader,
+                                                                        options,
+                                                                    )?;
+                                                                    cells.extend(
+                                                                        cell_elements
+                                                                            .into_iter()
+                                                                            .map(|element| {
+                                                                                TableCell {
+                                                                                    element,
+                                                                                }
+                                                                            }),
+                                                                    );
+                                                                }
+                                                                _ => { /*  */ }
+                                                            }
                                                         }
                                                         _ => { /*  */ }
                                                     }
                                                 }
-                                                _ => { /*  */ }
+                                                if !is_header {
+                                                    rows.push(TableRow { cells });
+                                                }
                                             }
-                                        }
-                                        if !is_header {
-                                            rows.push(TableRow { cells });
+                                            _ => { /*  */ }
                                         }
                                     }
                                     _ => { /*  */ }
-                                },
-                                _ => { /*  */ }
+                                }
+                            }
+                        }
+                        if !headers.is_empty() || !rows.is_empty() {
+                            elements.push(Table { headers, rows });
+                        }
+                    }
+                    // Read by the enclosing figure
+                    "figcaption" if is_figure_caption(child) => {}
+                    "figure" => {
+                        let mut figure: Vec<Element> = Vec::new();
+                        parse_html(child.children(), &mut figure, image_loader, options)?;
+                        let caption = child
+                            .children()
+                            .find(|node| is_figure_caption(*node))
+                            .map(|node| collapse_whitespace(&text_content(node)).trim().to_string())
+                            .unwrap_or_default();
+                        if let [Paragraph { elements: inline }] = figure.as_mut_slice() {
+                            if let [Image(_)] = inline.as_slice() {
+                                figure = std::mem::take(inline);
                             }
                         }
+                        match figure.as_mut_slice() {
+                            [Image(image)] => image.set_image_caption(&caption),
+                            _ if !caption.is_empty() => figure.push(Paragraph {
+                                elements: vec![Text {
+                                    text: caption,
+                                    size: 8,
+                                }],
+                            }),
+                            _ => {}
+                        }
+                        elements.extend(figure);
                     }
-                    if !headers.is_empty() || !rows.is_empty() {
-                        elements.push(Table { headers, rows });
+                    "p" | "title" | "figcaption" => {
+                        let mut paragraph_elements: Vec<Element> = Vec::new();
+                        parse_inline_children(
+                            child,
+                            &mut paragraph_elements,
+                            image_loader,
+                            options,
+                        )?;
+                        trim_inline(&mut paragraph_elements);
+                        if !paragraph_elements.is_empty() {
+                            elements.push(Paragraph {
+                                elements: paragraph_elements,
+                            });
+                        }
                     }
-                }
-                "p" | "title" => {
-                    let mut paragraph_elements: Vec<Element> = Vec::new();
-                    parse_html(child.children(), &mut paragraph_elements, image_loader)?;
-                    elements.push(Paragraph {
-                        elements: paragraph_elements,
-                    });
-                }
-                "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
-                    let level = element.name().as_bytes()[1] - b'0';
-                    // Retrieve the deepest text within any nested structure of the same header tag
-                    let mut text = retrieve_deep_text(child, element.name()).trim().to_string();
+                    "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                        let level = element.name().as_bytes()[1] - b'0';
+                        let text = collapse_whitespace(&text_content(child)).trim().to_string();
 
-                    if text.is_empty() {
-                        continue;
-                    }
+                        if text.is_empty() {
+                            continue;
+                        }
 
-                    if text.contains("\n") {
-                        text = text
-                            .lines()
-                            .map(str::trim)
-                            .filter(|line| !line.is_empty()) // handles multiple consecutive newlines (\n\n)
-                            .collect::<Vec<_>>()
-                            .join(" ");
+                        elements.push(Header { text, level });
                     }
-
-                    elements.push(Header { text, level });
-                }
-                "img" => {
-                    let src = element.attr("src").unwrap_or_default();
-                    let title = element.attr("title").unwrap_or_default();
-                    let alt = element.attr("alt").unwrap_or_default();
-                    let align = element.attr("align").unwrap_or_default();
-                    let width = element.attr("width").and_then(|s| s.parse().ok());
-                    let height = element.attr("height").and_then(|s| s.parse().ok());
-                    let image_bytes = (image_loader.function)(src)?;
-                    elements.push(Image(ImageData::new(
-                        image_bytes,
-                        title.to_string(),
-                        alt.to_string(),
-                        src.to_string(),
-                        align.to_string(),
-                        ImageDimension { width, height },
-                    )));
-                }
-                "ul" | "ol" => {
-                    let mut list_items: Vec<ListItem> = Vec::new();
-                    let numbered = element.name() == "ol";
-                    for list_child in child.children() {
-                        if let Node::Element(ref li_element) = list_child.value() {
-                            if li_element.name() == "li" {
-                                let mut item_elements: Vec<Element> = Vec::new();
-                                parse_html(
-                                    list_child.children(),
-                                    &mut item_elements,
-                                    image_loader,
-                                )?;
-                                list_items.extend(
-                                    item_elements
-                                        .into_iter()
-                                        .map(|element| ListItem { element }),
-                                );
+                    "ul" | "ol" => {
+                        let mut list_items: Vec<ListItem> = Vec::new();
+                        let numbered = element.name() == "ol";
+                        for list_child in child.children() {
+                            if let Node::Element(ref li_element) = list_child.value() {
+                                if li_element.name() == "li" {
+                                    let mut item_elements: Vec<Element> = Vec::new();
+                                    parse_html(
+                                        list_child.children(),
+                                        &mut item_elements,
+                                        image_loader,
+                                        options,
+                                    )?;
+                                    let mut checked = task_list_checkbox(list_child);
+                                    list_items.extend(item_elements.into_iter().map(|element| {
+                                        ListItem {
+                                            element,
+                                            checked: checked.take(),
+                                        }
+                                    }));
+                                }
                             }
                         }
+                        elements.push(List {
+                            elements: list_items,
+                            numbered,
+                        });
                     }
-                    elements.push(List {
-                        elements: list_items,
-                        numbered,
-                    });
-                }
-                "a" => {
-                    let href = element.attr("href").unwrap_or_default().to_string();
-                    let text = child
-                        .children()
-                        .filter_map(|n| {
-                            if let Node::Text(ref text) = n.value() {
-                                Some(text.clone().to_string())
-                            } else {
-                                None
+                    "dl" => {
+                        let mut items: Vec<DefinitionItem> = Vec::new();
+                        for dl_child in child.children() {
+                            let Node::Element(ref dl_element) = dl_child.value() else {
+                                continue;
+                            };
+                            match dl_element.name() {
+                                "dt" => items.push(DefinitionItem {
+                                    term: collapse_whitespace(&text_content(dl_child))
+                                        .trim()
+                                        .to_string(),
+                                    descriptions: vec![],
+                                }),
+                                "dd" => {
+                                    let mut descriptions: Vec<Element> = Vec::new();
+                                    parse_html(
+                                        dl_child.children(),
+                                        &mut descriptions,
+                                        image_loader,
+                                        options,
+                                    )?;
+                                    match items.last_mut() {
+                                        Some(item) => item.descriptions.extend(descriptions),
+                                        None => items.push(DefinitionItem {
+                                            term: String::new(),
+                                            descriptions,
+                                        }),
+                                    }
+                                }
+                                _ => {}
                             }
-                        })
-                        .collect::<Vec<String>>()
-                        .join("");
-                    elements.push(Hyperlink {
-                        title: text,
-                        url: href,
-                        alt: "".to_owned(),
-                        size: 8,
-                    });
-                }
-                _ => {
-                    parse_html(child.children(), elements, image_loader)?;
+                        }
+                        if !items.is_empty() {
+                            elements.push(Element::DefinitionList { items });
+                        }
+                    }
+                    "pre" => {
+                        let text = text_content(child);
+                        let text = text.strip_prefix('\n').unwrap_or(&text).trim_end();
+                        if !text.is_empty() {
+                            elements.push(Text {
+                                text: text.to_string(),
+                                size: 8,
+                            });
+                        }
+                    }
+                    "div" if has_class(element, "math") => {
+                        elements.push(math_element(child, true));
+                    }
+                    "hr" => {}
+                    _ => {
+                        parse_html(child.children(), elements, image_loader, options)?;
+                    }
                 }
-            },
-            Node::Text(ref text) => {
-                let txt_strings = text.lines().map(str::trim).filter(|p| !p.is_empty());
-                for text_str in txt_strings {
-                    elements.push(Text {
-                        text: text_str.to_owned(),
-                        size: 8,
-                    });
+            }
+            Node::Text(ref text) => push_text(&mut inline, text),
+            _ => {}
+        }
+    }
+    flush_inline(&mut inline, elements);
+    Ok(())
+}
+
+/// Title, author and description from `<title>` and the `<meta>` tags of the head,
+/// preferring the Open Graph variants.
+fn parse_metadata(document: &Html) -> Metadata {
+    let mut meta: HashMap<String, String> = HashMap::new();
+    let mut title = None;
+    for node in document.root_element().descendants() {
+        let Node::Element(ref element) = node.value() else {
+            continue;
+        };
+        match element.name() {
+            "title" if title.is_none() => {
+                title = Some(collapse_whitespace(&text_content(node)).trim().to_string());
+            }
+            "meta" => {
+                let name = element.attr("property").or(element.attr("name"));
+                if let (Some(name), Some(content)) = (name, element.attr("content")) {
+                    meta.entry(name.to_ascii_lowercase())
+                        .or_insert_with(|| content.trim().to_string());
                 }
             }
             _ => {}
         }
     }
+    let mut value = |names: &[&str]| {
+        names
+            .iter()
+            .find_map(|name| meta.remove(*name))
+            .filter(|value| !value.is_empty())
+    };
+    Metadata {
+        title: value(&["og:title", "twitter:title"])
+            .or(title)
+            .filter(|title| !title.is_empty()),
+        author: value(&["author", "article:author", "dc.creator"]),
+        description: value(&["description", "og:description", "twitter:description"]),
+    }
+}
+
+const POSITIVE_NAMES: &[&str] = &[
+    "article", "body", "content", "entry", "main", "page", "post", "text", "blog", "story",
+];
+
+const NEGATIVE_NAMES: &[&str] = &[
+    "comment",
+    "meta",
+    "footer",
+    "footnote",
+    "sidebar",
+    "banner",
+    "cookie",
+    "consent",
+    "nav",
+    "menu",
+    "share",
+    "social",
+    "related",
+    "promo",
+    "advert",
+    "sponsor",
+    "popup",
+    "modal",
+    "newsletter",
+    "subscribe",
+    "breadcrumb",
+    "masthead",
+    "widget",
+    "skip",
+];
+
+/// Class and id words that hint at the role of an element.
+fn name_weight(element: &scraper::node::Element) -> f64 {
+    let names = format!(
+        "{} {}",
+        element.attr("class").unwrap_or_default(),
+        element.id().unwrap_or_default()
+    )
+    .to_ascii_lowercase();
+    let mut weight = 0.0;
+    if POSITIVE_NAMES.iter().any(|name| names.contains(name)) {
+        weight += 25.0;
+    }
+    if NEGATIVE_NAMES.iter().any(|name| names.contains(name)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+/// Page furniture that is left out of the main content, and the byline which
+/// goes into the metadata instead.
+fn is_unlikely(element: &scraper::node::Element) -> bool {
+    if is_byline(element) {
+        return true;
+    }
+    match element.name() {
+        "header" | "footer" | "aside" | "form" | "dialog" => true,
+        "html" | "body" | "main" | "article" => false,
+        _ => {
+            name_weight(element) < 0.0
+                || matches!(
+                    element.attr("role"),
+                    Some("navigation" | "banner" | "complementary" | "contentinfo" | "dialog")
+                )
+        }
+    }
+}
+
+fn tag_weight(element: &scraper::node::Element) -> f64 {
+    match element.name() {
+        "article" | "main" => 25.0,
+        "div" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    }
+}
+
+/// Share of the text that is inside links. Navigation is mostly links, articles
+/// mostly are not.
+fn link_density(node: NodeRef<Node>) -> f64 {
+    let length = collapse_whitespace(&text_content(node))
+        .trim()
+        .chars()
+        .count();
+    if length == 0 {
+        return 0.0;
+    }
+    let link_length: usize = node
+        .descendants()
+        .filter(|descendant| {
+            matches!(descendant.value(), Node::Element(element) if element.name() == "a")
+        })
+        .map(|link| collapse_whitespace(&text_content(link)).trim().chars().count())
+        .sum();
+    link_length as f64 / length as f64
+}
+
+/// Finds the element holding the article. Every paragraph scores by its length
+/// and number of commas, and passes the score to its parent and, halved, to its
+/// grandparent. Containers start with a weight for their tag and class names,
+/// and their total is reduced by their link density.
+fn main_content(root: NodeRef<Node>) -> Option<NodeRef<Node>> {
+    let mut scores: HashMap<ego_tree::NodeId, f64> = HashMap::new();
+    for node in root.descendants() {
+        let Node::Element(ref element) = node.value() else {
+            continue;
+        };
+        if !matches!(element.name(), "p" | "pre" | "td" | "blockquote") {
+            continue;
+        }
+        let excluded = node.ancestors().any(|ancestor| {
+            matches!(ancestor.value(), Node::Element(element) if is_skipped(element) || is_unlikely(element))
+        });
+        if excluded {
+            continue;
+        }
+        let text = collapse_whitespace(&text_content(node));
+        let length = text.trim().chars().count();
+        if length < 25 {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (length as f64 / 100.0).min(3.0);
+        let ancestors = node
+            .ancestors()
+            .filter(|ancestor| ancestor.value().is_element())
+            .take(3);
+        for (level, ancestor) in ancestors.enumerate() {
+            let Node::Element(ref element) = ancestor.value() else {
+                continue;
+            };
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                _ => level as f64 * 3.0,
+            };
+            *scores
+                .entry(ancestor.id())
+                .or_insert_with(|| tag_weight(element) + name_weight(element)) += score / divider;
+        }
+    }
+    scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let node = root.tree().get(id)?;
+            Some((node, score * (1.0 - link_density(node))))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(node, _)| node)
+}
+
+fn is_byline(element: &scraper::node::Element) -> bool {
+    let names = format!(
+        "{} {}",
+        element.attr("class").unwrap_or_default(),
+        element.id().unwrap_or_default()
+    )
+    .to_ascii_lowercase();
+    element.attr("rel") == Some("author")
+        || element.attr("itemprop") == Some("author")
+        || names.contains("byline")
+        || names.split_whitespace().any(|name| name == "author")
+}
+
+/// Author from a `rel="author"` link or an element named like a byline.
+fn find_byline(root: NodeRef<Node>) -> Option<String> {
+    root.descendants().find_map(|node| {
+        let Node::Element(ref element) = node.value() else {
+            return None;
+        };
+        if !is_byline(element) {
+            return None;
+        }
+        let text = collapse_whitespace(&text_content(node)).trim().to_string();
+        let text = text
+            .strip_prefix("By ")
+            .or(text.strip_prefix("by "))
+            .unwrap_or(&text)
+            .to_string();
+        (!text.is_empty() && text.chars().count() < 100).then_some(text)
+    })
+}
+
+/// Elements that continue the surrounding paragraph instead of starting a block.
+const INLINE_TAGS: &[&str] = &[
+    "a", "abbr", "b", "bdi", "bdo", "br", "cite", "code", "data", "del", "dfn", "em", "font", "i",
+    "img", "input", "ins", "kbd", "label", "mark", "q", "s", "samp", "small", "span", "strike",
+    "strong", "sub", "sup", "time", "tt", "u", "var", "wbr",
+];
+
+/// Elements without readable content: scripts, styles, navigation and form
+/// controls. Also skipped is everything the page hides with the `hidden`
+/// attribute, `aria-hidden="true"` or an inline `display: none` style.
+fn is_skipped(element: &scraper::node::Element) -> bool {
+    if matches!(
+        element.name(),
+        "script" | "style" | "nav" | "noscript" | "template" | "button" | "select"
+    ) {
+        return true;
+    }
+    if element.attr("hidden").is_some() || element.attr("aria-hidden") == Some("true") {
+        return true;
+    }
+    element.attr("style").is_some_and(|style| {
+        let style: String = style
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        style.contains("display:none") || style.contains("visibility:hidden")
+    })
+}
+
+/// The value of a CSS property in the inline `style` attribute.
+fn style_property(element: &scraper::node::Element, name: &str) -> Option<String> {
+    element.attr("style")?.split(';').find_map(|declaration| {
+        let (property, value) = declaration.split_once(':')?;
+        (property.trim().eq_ignore_ascii_case(name)).then(|| value.trim().to_string())
+    })
+}
+
+fn has_class(element: &scraper::node::Element, class: &str) -> bool {
+    element.classes().any(|name| name == class)
+}
+
+fn parse_inline<F>(
+    node: NodeRef<Node>,
+    inline: &mut Vec<Element>,
+    image_loader: &ImageLoader<F>,
+    options: &ParseOptions,
+) -> anyhow::Result<()>
+where
+    F: ResourceResolver,
+{
+    let Node::Element(ref element) = node.value() else {
+        return Ok(());
+    };
+    match element.name() {
+        "br" => match inline.last_mut() {
+            Some(Text { text, .. }) => {
+                text.truncate(text.trim_end_matches(' ').len());
+                text.push('\n');
+            }
+            _ => inline.push(Text {
+                text: "\n".to_string(),
+                size: 8,
+            }),
+        },
+        "img" if options.sanitize && is_unsafe_url(element.attr("src").unwrap_or_default()) => {}
+        "img" => {
+            let src = element.attr("src").unwrap_or_default();
+            let title = element.attr("title").unwrap_or_default();
+            let alt = element.attr("alt").unwrap_or_default();
+            let align = element.attr("align").unwrap_or_default();
+            let width = element
+                .attr("width")
+                .map(str::to_string)
+                .or_else(|| style_property(element, "width"));
+            let height = element
+                .attr("height")
+                .map(str::to_string)
+                .or_else(|| style_property(element, "height"));
+            let image_bytes = image_loader.function.resolve(src)?;
+            inline.push(Image(ImageData::new(
+                image_bytes,
+                title.to_string(),
+                alt.to_string(),
+                src.to_string(),
+                align.to_string(),
+                ImageDimension { width, height },
+            )));
+        }
+        "a" => {
+            let href = element.attr("href").unwrap_or_default().to_string();
+            let text = collapse_whitespace(&text_content(node)).trim().to_string();
+            if href.is_empty() || options.sanitize && is_unsafe_url(&href) {
+                push_text(inline, &format!(" {text} "));
+            } else {
+                inline.push(Hyperlink {
+                    title: text,
+                    url: href,
+                    alt: "".to_owned(),
+                    size: 8,
+                });
+            }
+        }
+        // Checkboxes of task list items are read by the list
+        "input" => {}
+        "span" if has_class(element, "math") => inline.push(math_element(node, false)),
+        _ => parse_inline_children(node, inline, image_loader, options)?,
+    }
+    Ok(())
+}
+
+fn parse_inline_children<F>(
+    node: NodeRef<Node>,
+    inline: &mut Vec<Element>,
+    image_loader: &ImageLoader<F>,
+    options: &ParseOptions,
+) -> anyhow::Result<()>
+where
+    F: ResourceResolver,
+{
+    for child in node.children() {
+        match child.value() {
+            Node::Text(ref text) => push_text(inline, text),
+            Node::Element(ref element)
+                if is_skipped(element)
+                    || options.sanitize && DROPPED_TAGS.contains(&element.name()) => {}
+            Node::Element(_) => parse_inline(child, inline, image_loader, options)?,
+            _ => {}
+        }
+    }
     Ok(())
 }
 
+/// Whitespace in text is collapsed to single spaces, the way browsers render it.
+fn push_text(inline: &mut Vec<Element>, text: &str) {
+    let text = collapse_whitespace(text);
+    if text.is_empty() {
+        return;
+    }
+    match inline.last_mut() {
+        Some(Text { text: last, .. }) => {
+            if last.ends_with([' ', '\n']) {
+                last.push_str(text.trim_start());
+            } else {
+                last.push_str(&text);
+            }
+        }
+        _ => inline.push(Text { text, size: 8 }),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !collapsed.ends_with(' ') {
+                collapsed.push(' ');
+            }
+        } else {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+/// Removes whitespace at the edges of a paragraph and around line breaks.
+fn trim_inline(inline: &mut Vec<Element>) {
+    let last = inline.len().saturating_sub(1);
+    for (index, element) in inline.iter_mut().enumerate() {
+        if let Text { text, .. } = element {
+            let lines: Vec<&str> = text.split('\n').collect();
+            let mut trimmed = lines
+                .iter()
+                .enumerate()
+                .map(|(line_index, line)| {
+                    let line = if line_index > 0 {
+                        line.trim_start_matches(' ')
+                    } else {
+                        line
+                    };
+                    if line_index + 1 < lines.len() {
+                        line.trim_end_matches(' ')
+                    } else {
+                        line
+                    }
+                })
+                .collect::<Vec<&str>>()
+                .join("\n");
+            if index == 0 {
+                trimmed = trimmed.trim_start().to_string();
+            }
+            if index == last {
+                trimmed = trimmed.trim_end().to_string();
+            }
+            *text = trimmed;
+        }
+    }
+    inline.retain(|element| !matches!(element, Text { text, .. } if text.trim().is_empty()));
+}
+
+/// Inline content outside of paragraphs becomes a text, link or image of its own
+/// when there is only one, or a paragraph otherwise.
+fn flush_inline(inline: &mut Vec<Element>, elements: &mut Vec<Element>) {
+    trim_inline(inline);
+    match inline.len() {
+        0 => {}
+        1 => elements.push(inline.remove(0)),
+        _ => elements.push(Paragraph {
+            elements: std::mem::take(inline),
+        }),
+    }
+}
+
+/// The text of all descendants, as it is in the source.
+fn text_content(node: NodeRef<Node>) -> String {
+    node.descendants()
+        .filter_map(|descendant| match descendant.value() {
+            Node::Text(text) => Some(&**text),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Checked state of a checkbox in a list item, outside of its nested lists.
+fn task_list_checkbox(item: NodeRef<Node>) -> Option<bool> {
+    item.descendants()
+        .filter(|descendant| {
+            !descendant
+                .ancestors()
+                .take_while(|ancestor| ancestor.id() != item.id())
+                .any(|ancestor| {
+                    matches!(ancestor.value(), Node::Element(e) if matches!(e.name(), "ul" | "ol"))
+                })
+        })
+        .filter_map(|descendant| match descendant.value() {
+            Node::Element(element) => Some(element),
+            _ => None,
+        })
+        .find(|element| element.name() == "input")
+        .filter(|input| input.attr("type") == Some("checkbox"))
+        .map(|input| input.attr("checked").is_some())
+}
+
+/// Equations in the `\(...\)` and `\[...\]` markup written by the generator.
+fn math_element(node: NodeRef<Node>, display: bool) -> Element {
+    let text = text_content(node);
+    let text = text.trim();
+    let latex = text
+        .strip_prefix("\\(")
+        .and_then(|latex| latex.strip_suffix("\\)"))
+        .or_else(|| {
+            text.strip_prefix("\\[")
+                .and_then(|latex| latex.strip_suffix("\\]"))
+        })
+        .unwrap_or(text);
+    Element::Math {
+        latex: latex.trim().to_string(),
+        display,
+    }
+}
+
+/// Whether `node` is the caption of a `<figure>`.
+fn is_figure_caption(node: NodeRef<Node>) -> bool {
+    let is_element = |node: Option<NodeRef<Node>>, name: &str| matches!(node.map(|node| node.value()), Some(Node::Element(element)) if element.name() == name);
+    is_element(Some(node), "figcaption") && is_element(node.parent(), "figure")
+}
+
+/// Numbers saved image files and captioned figures across the document.
+#[derive(Default)]
+struct Counters {
+    images: i32,
+    figures: usize,
+}
+
+/// Pixel lengths are written as attributes, as HTML expects. Other absolute
+/// units are converted to pixels so the physical size matches the paged
+/// outputs, and percentages stay relative through `style`.
+fn dimension_attribute(name: &str, length: Option<Length>, style: &mut Vec<String>) -> String {
+    match length {
+        Some(Length::Percent(value)) => {
+            style.push(format!("{name}: {value}%"));
+            String::new()
+        }
+        Some(length) => format!(" {name}=\"{}\"", (length.to_points(0.0) / 0.75).round()),
+        None => String::new(),
+    }
+}
+
 fn generate_html_for_element(
     element: &Element,
-    image_num: &mut i32,
+    counters: &mut Counters,
     image_saver: &ImageSaver<impl Fn(&Bytes, &str) -> anyhow::Result<()>>,
+    options: &GenerateOptions,
 ) -> anyhow::Result<String> {
     match element {
-        Text { text, size: _ } => Ok(text.to_string()),
+        Text { text, size: _ } => Ok(escape_text(text)),
         Paragraph { elements } => {
             let mut paragraph_html = String::from("<p>");
             for child in elements {
-                paragraph_html
-                    .push_str(generate_html_for_element(child, image_num, image_saver)?.as_str());
+                paragraph_html.push_str(
+                    generate_html_for_element(child, counters, image_saver, options)?.as_str(),
+                );
             }
             paragraph_html.push_str("</p>");
             Ok(paragraph_html)
         }
-        Header { level, text } => Ok(format!("<h{level}>{text}</h{level}>")),
+        Header { level, text } => Ok(format!("<h{level}>{}</h{level}>", escape_text(text))),
         List { elements, numbered } => {
             let tag = if *numbered { "ol" } else { "ul" };
             let mut list_html = format!("<{tag}>");
             list_html.push('\n');
             for item in elements {
-                let item_html = generate_html_for_element(&item.element, image_num, image_saver)?;
+                let item_html =
+                    generate_html_for_element(&item.element, counters, image_saver, options)?;
                 if let List { .. } = item.element {
                     list_html.push_str(&item_html.to_string());
                 } else {
-                    list_html.push_str(&format!("<li>{item_html}</li>"));
+                    let checkbox = match item.checked {
+                        Some(true) => "<input type=\"checkbox\" disabled checked /> ",
+                        Some(false) => "<input type=\"checkbox\" disabled /> ",
+                        None => "",
+                    };
+                    list_html.push_str(&format!("<li>{checkbox}{item_html}</li>"));
                     list_html.push('\n');
                 }
             }
@@ -375,58 +1217,120 @@ fn generate_html_for_element(
             Ok(list_html)
         }
         Image(image) => {
-            let image_path = format!("image{image_num}.png");
-            // images.insert(image_path.to_string(), bytes.clone());
-            (image_saver.function)(image.bytes(), &image_path)?;
-            *image_num += 1;
+            let image_path = if options.embed_images {
+                format!(
+                    "data:{};base64,{}",
+                    image.image_type().to_mime_type(),
+                    base64::engine::general_purpose::STANDARD.encode(image.bytes())
+                )
+            } else {
+                let image_path = format!(
+                    "image{}{}",
+                    counters.images,
+                    image.image_type().to_extension()
+                );
+                (image_saver.function)(image.bytes(), &image_path)?;
+                counters.images += 1;
+                image_path
+            };
 
             let align_str = match image.align() {
                 ImageAlignment::None => String::new(),
                 _ => format!(" align=\"{}\"", image.align()),
             };
 
-            let width_str = match &image.size().width {
-                Some(width) => format!(" width=\"{width}\""),
-                None => String::new(),
+            let size = image.size();
+            let mut style = Vec::new();
+            let width_str = dimension_attribute("width", size.width_length(), &mut style);
+            let height_str = dimension_attribute("height", size.height_length(), &mut style);
+            let style_str = match style.is_empty() {
+                true => String::new(),
+                false => format!(" style=\"{}\"", style.join("; ")),
             };
 
-            let height_str = match &image.size().height {
-                Some(height) => format!(" height=\"{height}\""),
-                None => String::new(),
-            };
+            let img = format!(
+                "<img src=\"{image_path}\" alt=\"{}\" title=\"{}\"{align_str}{width_str}{height_str}{style_str} />",
+                escape_attribute(image.alt()),
+                escape_attribute(image.title())
+            );
+            if image.caption().is_empty() {
+                return Ok(img);
+            }
+            counters.figures += 1;
             Ok(format!(
-                "<img src=\"{image_path}\" alt=\"{}\" title=\"{}\"{align_str}{width_str}{height_str} />",
-                image.alt(),
-                image.title()
+                "<figure>{img}<figcaption>{}</figcaption></figure>",
+                escape_text(&image.figure_label(counters.figures))
             ))
         }
         Hyperlink {
             title, url, alt, ..
-        } => Ok(format!("<a href=\"{url}\" title=\"{alt}\">{title}</a>")),
+        } => Ok(format!(
+            "<a href=\"{}\" title=\"{}\">{}</a>",
+            escape_attribute(safe_url(url)),
+            escape_attribute(alt),
+            escape_text(title)
+        )),
+        Element::DefinitionList { items } => {
+            let mut list_html = String::from("<dl>\n");
+            for item in items {
+                list_html.push_str(&format!("<dt>{}</dt>\n", escape_text(&item.term)));
+                for description in &item.descriptions {
+                    let description_html =
+                        generate_html_for_element(description, counters, image_saver, options)?;
+                    list_html.push_str(&format!("<dd>{description_html}</dd>\n"));
+                }
+            }
+            list_html.push_str("</dl>\n");
+            Ok(list_html)
+        }
+        // Delimited the way KaTeX and MathJax auto-render expect
+        Element::Math { latex, display } => {
+            let latex = escape_text(latex);
+            if *display {
+                Ok(format!("<div class=\"math display\">\\[{latex}\\]</div>\n"))
+            } else {
+                Ok(format!("<span class=\"math inline\">\\({latex}\\)</span>"))
+            }
+        }
         _ => Ok("".to_string()),
     }
 }
 
-fn retrieve_deep_text(node: NodeRef<Node>, tag_name: &str) -> String {
-    let mut text = String::new();
-    let mut current_node = Some(node);
-    while let Some(n) = current_node {
-        if let Node::Element(ref el) = n.value() {
-            if el.name() == tag_name {
-                current_node = n.children().next(); // Move deeper if the same tag is nested
-            } else {
-                break; // Stop if a different tag is encountered
-            }
-        } else if let Node::Text(ref txt) = n.value() {
-            text = txt.to_string(); // Set text if text node is found
+fn escape_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
 
-            break;
-        } else {
-            break; // Break on encountering any other type of node
-        }
-    }
+fn escape_attribute(value: &str) -> String {
+    escape_text(value)
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// URL schemes that run code when the link is followed.
+const UNSAFE_SCHEMES: [&str; 3] = ["javascript:", "vbscript:", "data:text/html"];
 
-    text
+/// Browsers ignore whitespace and control characters inside the scheme and its
+/// case, so `JaVa\tScript:` is checked like `javascript:`.
+fn is_unsafe_url(url: &str) -> bool {
+    let normalized: String = url
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .take(32)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    UNSAFE_SCHEMES
+        .iter()
+        .any(|scheme| normalized.starts_with(scheme))
+}
+
+fn safe_url(url: &str) -> &str {
+    if is_unsafe_url(url) {
+        "#"
+    } else {
+        url
+    }
 }
 
 #[cfg(test)]
@@ -504,4 +1408,322 @@ mod tests {
         info!("{}", String::from_utf8(markdown.to_vec())?);
         Ok(())
     }
+
+    #[test]
+    fn test_escaping_and_sanitize() -> anyhow::Result<()> {
+        init_logger();
+        let document = Document::new(vec![
+            Element::Header {
+                level: 1,
+                text: "Fish & <Chips>".to_string(),
+            },
+            Element::Paragraph {
+                elements: vec![Element::Hyperlink {
+                    title: "<b>click</b>".to_string(),
+                    url: " JavaScript:alert(1)".to_string(),
+                    alt: "say \"hi\"".to_string(),
+                    size: 8,
+                }],
+            },
+        ]);
+        let no_images = |_: &Bytes, _: &str| Ok(());
+        let generated = Transformer::generate_with_saver(&document, no_images)?;
+        let generated = std::str::from_utf8(&generated)?;
+        info!("{}", generated);
+        assert!(generated.contains("<h1>Fish &amp; &lt;Chips&gt;</h1>"));
+        assert!(generated
+            .contains("<a href=\"#\" title=\"say &quot;hi&quot;\">&lt;b&gt;click&lt;/b&gt;</a>"));
+
+        let document_html = r#"
+        <html>
+        <head><style>p { color: red }</style></head>
+        <body>
+        <script>alert(1)</script>
+        <custom-card onclick="steal()"><p>Kept</p></custom-card>
+        <p><a href="javascript:steal()">Link</a><img src="javascript:steal()"></p>
+        </body>
+        </html>
+        "#;
+        let no_loader = |_: &str| -> anyhow::Result<Bytes> { anyhow::bail!("no images") };
+        let options = ParseOptions {
+            sanitize: true,
+            ..Default::default()
+        };
+        let parsed =
+            Transformer::parse_with_options(&Bytes::from(document_html), &options, no_loader)?;
+        debug!("{:#?}", parsed);
+        assert_eq!(
+            parsed.get_all_elements(),
+            vec![
+                &Element::Paragraph {
+                    elements: vec![Element::Text {
+                        text: "Kept".to_string(),
+                        size: 8,
+                    }],
+                },
+                &Element::Paragraph {
+                    elements: vec![Element::Text {
+                        text: "Link".to_string(),
+                        size: 8,
+                    }],
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_with_options() -> anyhow::Result<()> {
+        init_logger();
+        let document = Document::new(vec![
+            Element::Header {
+                level: 1,
+                text: "Report".to_string(),
+            },
+            Element::Image(ImageData::new(
+                Bytes::from_static(b"GIF89a"),
+                "logo".to_string(),
+                "Logo".to_string(),
+                "logo.gif".to_string(),
+                "".to_string(),
+                ImageDimension::default(),
+            )),
+        ]);
+        let saver = |_: &Bytes, _: &str| anyhow::bail!("images are embedded");
+
+        let options = GenerateOptions {
+            theme: Theme::Github,
+            stylesheet: Some("h1 { color: navy; }".to_string()),
+            embed_images: true,
+            ..Default::default()
+        };
+        let generated = Transformer::generate_with_options(&document, &options, saver)?;
+        let generated = std::str::from_utf8(&generated)?;
+        info!("{}", generated);
+        assert!(
+            generated.starts_with("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n")
+        );
+        assert!(generated.contains("<title>Report</title>\n"));
+        assert!(generated.contains("tr:nth-child(2n)"));
+        assert!(generated.contains("h1 { color: navy; }\n</style>"));
+        assert!(generated.contains("<img src=\"data:image/gif;base64,R0lGODlh\" alt=\"Logo\""));
+
+        let options = GenerateOptions {
+            fragment: true,
+            embed_images: true,
+            ..Default::default()
+        };
+        let generated = Transformer::generate_with_options(&document, &options, saver)?;
+        let generated = std::str::from_utf8(&generated)?;
+        assert!(generated.starts_with("<h1>Report</h1>\n<p><img src=\"data:image/gif;base64,"));
+        assert!(!generated.contains("<body>"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_structure() -> anyhow::Result<()> {
+        init_logger();
+        let document_html = r#"
+        <html>
+        <body>
+        <nav><a href="/">Home</a></nav>
+        <article>
+          <section>
+            <h2>Intro <em>part</em></h2>
+            <div>Some <strong>bold</strong> and <em>emphasized</em> text,<br>
+              then a <a href="https://example.com">link</a>.</div>
+            <div hidden>Hidden</div>
+            <p style="display: none">Invisible</p>
+          </section>
+          <blockquote><p>Quoted</p></blockquote>
+          <pre>
+fn main() {
+    println!("hi");
+}
+</pre>
+          <dl><dt>Term</dt><dd>First</dd><dd>Second</dd></dl>
+          <ul>
+            <li><input type="checkbox" disabled checked /> done</li>
+            <li><input type="checkbox" disabled /> todo</li>
+          </ul>
+          <p>Area <span class="math inline">\(\pi r^2\)</span></p>
+          <div class="math display">\[x &lt; y\]</div>
+        </article>
+        <script>var hidden = true;</script>
+        <style>p { color: red }</style>
+        </body>
+        </html>
+        "#;
+        let no_loader = |_: &str| -> anyhow::Result<Bytes> { anyhow::bail!("no images") };
+        let parsed = Transformer::parse_with_loader(&Bytes::from(document_html), no_loader)?;
+        debug!("{:#?}", parsed);
+        let text = |text: &str| Element::Text {
+            text: text.to_string(),
+            size: 8,
+        };
+        let expected = Document::new(vec![
+            Element::Header {
+                level: 2,
+                text: "Intro part".to_string(),
+            },
+            Element::Paragraph {
+                elements: vec![
+                    text("Some bold and emphasized text,\nthen a "),
+                    Element::Hyperlink {
+                        title: "link".to_string(),
+                        url: "https://example.com".to_string(),
+                        alt: "".to_string(),
+                        size: 8,
+                    },
+                    text("."),
+                ],
+            },
+            Element::Paragraph {
+                elements: vec![text("Quoted")],
+            },
+            text("fn main() {\n    println!(\"hi\");\n}"),
+            Element::DefinitionList {
+                items: vec![DefinitionItem {
+                    term: "Term".to_string(),
+                    descriptions: vec![text("First"), text("Second")],
+                }],
+            },
+            Element::List {
+                elements: vec![
+                    ListItem {
+                        element: text("done"),
+                        checked: Some(true),
+                    },
+                    ListItem {
+                        element: text("todo"),
+                        checked: Some(false),
+                    },
+                ],
+                numbered: false,
+            },
+            Element::Paragraph {
+                elements: vec![
+                    text("Area "),
+                    Element::Math {
+                        latex: "\\pi r^2".to_string(),
+                        display: false,
+                    },
+                ],
+            },
+            Element::Math {
+                latex: "x < y".to_string(),
+                display: true,
+            },
+        ]);
+        assert_eq!(parsed, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_main_content() -> anyhow::Result<()> {
+        init_logger();
+        let document_html = r#"
+        <html>
+        <head>
+          <title>Growing tomatoes | Garden Weekly</title>
+          <meta property="og:title" content="Growing tomatoes">
+          <meta name="description" content="A short guide.">
+        </head>
+        <body>
+          <header class="masthead"><a href="/">Garden Weekly</a> <a href="/news">News</a></header>
+          <div id="cookie-banner"><p>We use cookies to improve your experience, please accept them.</p></div>
+          <div class="layout">
+            <div class="post-content">
+              <h1>Growing tomatoes</h1>
+              <p class="byline">By Jane Doe</p>
+              <p>Tomatoes need sun, warmth and regular watering, which makes them a summer crop in most gardens.</p>
+              <p>Plant them deep, stake them early, and pinch out side shoots, so the plant puts its energy into fruit.</p>
+              <div class="share"><a href="/share">Share this article on social media</a></div>
+            </div>
+            <div class="sidebar"><p>Related: how to grow cucumbers, peppers, and beans in small spaces.</p></div>
+          </div>
+          <footer><p>Copyright Garden Weekly, all rights reserved, since 1999.</p></footer>
+        </body>
+        </html>
+        "#;
+        let no_loader = |_: &str| -> anyhow::Result<Bytes> { anyhow::bail!("no images") };
+        let options = ParseOptions {
+            main_content: true,
+            ..Default::default()
+        };
+        let parsed =
+            Transformer::parse_with_options(&Bytes::from(document_html), &options, no_loader)?;
+        debug!("{:#?}", parsed);
+        assert_eq!(
+            parsed.get_metadata(),
+            &Metadata {
+                title: Some("Growing tomatoes".to_string()),
+                author: Some("Jane Doe".to_string()),
+                description: Some("A short guide.".to_string()),
+            }
+        );
+        let paragraph = |text: &str| Element::Paragraph {
+            elements: vec![Element::Text {
+                text: text.to_string(),
+                size: 8,
+            }],
+        };
+        assert_eq!(
+            parsed.get_all_elements(),
+            vec![
+                &Element::Header {
+                    level: 1,
+                    text: "Growing tomatoes".to_string(),
+                },
+                &paragraph("Tomatoes need sun, warmth and regular watering, which makes them a summer crop in most gardens."),
+                &paragraph("Plant them deep, stake them early, and pinch out side shoots, so the plant puts its energy into fruit."),
+            ]
+        );
+
+        let generated = Transformer::generate_with_options(
+            &parsed,
+            &GenerateOptions::default(),
+            |_: &Bytes, _: &str| Ok(()),
+        )?;
+        let generated = std::str::from_utf8(&generated)?;
+        assert!(generated.contains("<meta name=\"author\" content=\"Jane Doe\" />"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_figures_and_sizes() -> anyhow::Result<()> {
+        let document_html = r#"<html><body>
+            <figure>
+                <img src="small.png" alt="Small" style="width: 50%">
+                <figcaption>A small image</figcaption>
+            </figure>
+            <p><img src="small.png" alt="Sized" width="20mm"></p>
+        </body></html>"#;
+        let parsed = Transformer::parse_with_loader(
+            &Bytes::from(document_html),
+            disk_image_loader("test/data"),
+        )?;
+        let elements = parsed.get_all_elements();
+        let Element::Image(figure) = elements[0] else {
+            panic!("Expected a figure, got {:?}", elements[0]);
+        };
+        assert_eq!(figure.caption(), "A small image");
+        assert_eq!(figure.size().width_length(), Some(Length::Percent(50.0)));
+
+        let generated = Transformer::generate_with_options(
+            &parsed,
+            &GenerateOptions {
+                fragment: true,
+                ..GenerateOptions::default()
+            },
+            |_: &Bytes, _: &str| Ok(()),
+        )?;
+        let generated = std::str::from_utf8(&generated)?;
+        assert!(generated.contains(
+            r#"style="width: 50%" /><figcaption>Figure 1: A small image</figcaption></figure>"#
+        ));
+        // 20 mm are 76 CSS pixels
+        assert!(generated.contains(r#"alt="Sized" title="" width="76" />"#));
+        Ok(())
+    }
 }
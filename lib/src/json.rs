@@ -1,6 +1,6 @@
 use crate::core::{
-    Document, Element, ImageData, ImageDimension, ImageType, ListItem, PageDimensions, PageFormat,
-    TableCell, TableHeader, TableRow, TransformerTrait,
+    DefinitionItem, Document, Element, ImageData, ImageDimension, ImageType, ListItem,
+    PageDimensions, PageFormat, TableCell, TableHeader, TableRow, TransformerTrait,
 };
 use base64::Engine;
 use bytes::Bytes;
@@ -68,8 +68,11 @@ impl TransformerTrait for Transformer {
             page_margin_right,
         });
 
-        let document =
+        let mut document =
             Document::new_with_dimensions(page_header, elements, page_footer, page_custom_format);
+        if let Some(metadata) = root.get("metadata") {
+            document.set_metadata(serde_json::from_value(metadata.clone())?);
+        }
         Ok(document)
     }
 
@@ -142,6 +145,9 @@ impl TransformerTrait for Transformer {
                             let mut item_map = Map::new();
                             item_map
                                 .insert("element".to_string(), serialize_element(&item.element));
+                            if let Some(checked) = item.checked {
+                                item_map.insert("checked".to_string(), Value::Bool(checked));
+                            }
                             Value::Object(item_map)
                         })
                         .collect();
@@ -188,6 +194,12 @@ impl TransformerTrait for Transformer {
                         size_map.insert("height".to_string(), Value::String(height.clone()));
                     }
                     map.insert("size".to_string(), Value::Object(size_map));
+                    if !image_data.caption().is_empty() {
+                        map.insert(
+                            "caption".to_string(),
+                            Value::String(image_data.caption().to_string()),
+                        );
+                    }
                     Value::Object(map)
                 }
                 Element::Hyperlink {
@@ -204,6 +216,37 @@ impl TransformerTrait for Transformer {
                     map.insert("size".to_string(), Value::Number((*size).into()));
                     Value::Object(map)
                 }
+                Element::DefinitionList { items } => {
+                    let items_json: Vec<Value> = items
+                        .iter()
+                        .map(|item| {
+                            let mut item_map = Map::new();
+                            item_map.insert("term".to_string(), Value::String(item.term.clone()));
+                            item_map.insert(
+                                "descriptions".to_string(),
+                                Value::Array(
+                                    item.descriptions.iter().map(serialize_element).collect(),
+                                ),
+                            );
+                            Value::Object(item_map)
+                        })
+                        .collect();
+
+                    let mut map = Map::new();
+                    map.insert(
+                        "type".to_string(),
+                        Value::String("DefinitionList".to_string()),
+                    );
+                    map.insert("items".to_string(), Value::Array(items_json));
+                    Value::Object(map)
+                }
+                Element::Math { latex, display } => {
+                    let mut map = Map::new();
+                    map.insert("type".to_string(), Value::String("Math".to_string()));
+                    map.insert("latex".to_string(), Value::String(latex.clone()));
+                    map.insert("display".to_string(), Value::Bool(*display));
+                    Value::Object(map)
+                }
             }
         }
 
@@ -286,6 +329,13 @@ impl TransformerTrait for Transformer {
             .collect();
         doc_map.insert("page_footer".to_string(), Value::Array(page_footer_json));
 
+        if !document.get_metadata().is_empty() {
+            doc_map.insert(
+                "metadata".to_string(),
+                serde_json::to_value(document.get_metadata())?,
+            );
+        }
+
         // Create the final JSON value
         let doc_value = Value::Object(doc_map);
 
@@ -429,14 +479,18 @@ fn parse_element(value: &Value) -> anyhow::Result<Element> {
                 .and_then(|v| v.as_str())
                 .map(|s| s.to_string());
             let size = ImageDimension { width, height };
-            Ok(Element::Image(ImageData::new(
+            let mut image = ImageData::new(
                 bytes,
                 title,
                 alt,
                 image_type.to_extension().to_string(), // Updated to use to_extension
                 align_str.to_string(),
                 size,
-            )))
+            );
+            if let Some(caption) = obj.get("caption").and_then(|v| v.as_str()) {
+                image.set_image_caption(caption);
+            }
+            Ok(Element::Image(image))
         }
         "Hyperlink" => {
             let title = obj
@@ -464,6 +518,28 @@ fn parse_element(value: &Value) -> anyhow::Result<Element> {
                 size,
             })
         }
+        "DefinitionList" => {
+            let items = obj
+                .get("items")
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| anyhow::anyhow!("DefinitionList element missing 'items' field"))?
+                .iter()
+                .map(parse_definition_item)
+                .collect::<anyhow::Result<Vec<DefinitionItem>>>()?;
+            Ok(Element::DefinitionList { items })
+        }
+        "Math" => {
+            let latex = obj
+                .get("latex")
+                .and_then(|v| v.as_str())
+                .ok_or_else(|| anyhow::anyhow!("Math element missing 'latex' field"))?
+                .to_string();
+            let display = obj
+                .get("display")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            Ok(Element::Math { latex, display })
+        }
         _ => Err(anyhow::anyhow!("Unknown element type: {}", type_str)),
     }
 }
@@ -536,7 +612,24 @@ fn parse_list_item(value: &Value) -> anyhow::Result<ListItem> {
             .ok_or_else(|| anyhow::anyhow!("ListItem missing 'element' field"))?
             .clone(),
     )?;
-    Ok(ListItem { element })
+    let checked = obj.get("checked").and_then(|v| v.as_bool());
+    Ok(ListItem { element, checked })
+}
+
+fn parse_definition_item(value: &Value) -> anyhow::Result<DefinitionItem> {
+    let obj = value
+        .as_object()
+        .ok_or_else(|| anyhow::anyhow!("DefinitionItem is not an object"))?;
+    let term = obj
+        .get("term")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("DefinitionItem missing 'term' field"))?
+        .to_string();
+    let descriptions = parse_elements(
+        obj.get("descriptions")
+            .ok_or_else(|| anyhow::anyhow!("DefinitionItem missing 'descriptions' field"))?,
+    )?;
+    Ok(DefinitionItem { term, descriptions })
 }
 
 #[cfg(test)]
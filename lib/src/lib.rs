@@ -1,4 +1,9 @@
 pub mod core;
+pub mod diff;
+pub mod resource;
+
+#[cfg(any(feature = "typst", feature = "docx"))]
+mod math;
 
 #[cfg(feature = "text")]
 pub mod text;
@@ -24,6 +29,12 @@ pub mod xml;
 #[cfg(feature = "csv")]
 pub mod csv;
 
+#[cfg(feature = "tsv")]
+pub mod tsv;
+
+#[cfg(feature = "fixed_width")]
+pub mod fixed_width;
+
 #[cfg(feature = "docx")]
 pub mod docx;
 
@@ -10,7 +10,7 @@ pub struct Transformer;
 
 impl TransformerTrait for Transformer {
     fn parse(document: &Bytes) -> anyhow::Result<Document> {
-        Transformer::parse_with_loader(document, disk_image_loader("."))
+        Transformer::parse_with_loader(document, default_image_loader())
     }
 
     fn generate(document: &Document) -> anyhow::Result<Bytes> {
@@ -27,7 +27,7 @@ where
 impl TransformerWithImageLoaderSaverTrait for Transformer {
     fn parse_with_loader<F>(document: &Bytes, image_loader: F) -> anyhow::Result<Document>
     where
-        F: Fn(&str) -> anyhow::Result<Bytes>,
+        F: ResourceResolver,
         Self: Sized,
     {
         fn create_element_list(children: Option<Vec<ListItem>>, numbered: bool) -> Element {
@@ -63,6 +63,7 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                             Element::Hyperlink { .. } | Element::Header { .. } => {
                                 if let Some(ListItem {
                                     element: Text { .. },
+                                    ..
                                 }) = list_elements.last()
                                 {
                                     list_elements.pop();
@@ -75,6 +76,7 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                         if matches!(new_el, Element::List { .. }) {
                             let list_item_children = ListItem {
                                 element: create_element_list(None, *numbered),
+                                checked: None,
                             };
 
                             if let Element::List {
@@ -90,7 +92,10 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                             }
                         }
 
-                        let li = ListItem { element: new_el };
+                        let li = ListItem {
+                            element: new_el,
+                            checked: None,
+                        };
                         list_elements.push(li);
                     }
                 }
@@ -100,6 +105,28 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
             }
         }
 
+        /// Items of the list nested `depth` levels deep, following the last item
+        /// of each level like the text events do.
+        fn innermost_list_items(items: &mut Vec<ListItem>, depth: i32) -> &mut Vec<ListItem> {
+            let last = items.len().wrapping_sub(1);
+            if depth > 1
+                && matches!(
+                    items.get(last),
+                    Some(ListItem {
+                        element: List { .. },
+                        ..
+                    })
+                )
+            {
+                match &mut items[last].element {
+                    List { elements, .. } => innermost_list_items(elements, depth - 1),
+                    _ => unreachable!(),
+                }
+            } else {
+                items
+            }
+        }
+
         let document_str = std::str::from_utf8(document)?;
         let mut doc_elements: Vec<Element> = Vec::new();
 
@@ -109,6 +136,8 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
         options.insert(Options::ENABLE_SMART_PUNCTUATION);
         options.insert(Options::ENABLE_MATH);
         options.insert(Options::ENABLE_GFM);
+        options.insert(Options::ENABLE_TASKLISTS);
+        options.insert(Options::ENABLE_DEFINITION_LIST);
 
         let parser = Parser::new_ext(document_str, options);
         let md_iterator = TextMergeStream::new(parser);
@@ -116,18 +145,68 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
         let mut current_element: Option<Element> = None;
         let mut list_depth = 0;
         let mut table_element: Option<(bool, Element)> = None;
+        let mut definition_list: Option<Vec<DefinitionItem>> = None;
         for event in md_iterator {
+            if let Some(items) = definition_list.as_mut() {
+                match event {
+                    Event::Start(Tag::DefinitionListTitle) => items.push(DefinitionItem {
+                        term: String::new(),
+                        descriptions: vec![],
+                    }),
+                    Event::Start(Tag::DefinitionListDefinition) => {
+                        if let Some(item) = items.last_mut() {
+                            item.descriptions.push(Text {
+                                text: String::new(),
+                                size: 14,
+                            });
+                        }
+                    }
+                    Event::Text(text) | Event::Code(text) => {
+                        if let Some(item) = items.last_mut() {
+                            match item.descriptions.last_mut() {
+                                Some(Text {
+                                    text: description, ..
+                                }) => description.push_str(&text),
+                                _ => item.term.push_str(&text),
+                            }
+                        }
+                    }
+                    Event::SoftBreak | Event::End(TagEnd::Paragraph) => {
+                        if let Some(Text { text, .. }) = items
+                            .last_mut()
+                            .and_then(|item| item.descriptions.last_mut())
+                        {
+                            if !text.is_empty() && !text.ends_with(' ') {
+                                text.push(' ');
+                            }
+                        }
+                    }
+                    Event::End(TagEnd::DefinitionList) => {
+                        let mut items = definition_list.take().unwrap_or_default();
+                        for item in &mut items {
+                            for description in &mut item.descriptions {
+                                if let Text { text, .. } = description {
+                                    text.truncate(text.trim_end().len());
+                                }
+                            }
+                        }
+                        doc_elements.push(Element::DefinitionList { items });
+                    }
+                    _ => {}
+                }
+                continue;
+            }
             match event {
                 Event::Start(tag) => {
                     match tag {
-                        Tag::Paragraph => {
-                            if !matches!(current_element, Some(Element::List { .. })) {
-                                process_element_creation(
-                                    &mut current_element,
-                                    Element::Paragraph { elements: vec![] },
-                                    &mut list_depth,
-                                );
-                            }
+                        Tag::Paragraph
+                            if !matches!(current_element, Some(Element::List { .. })) =>
+                        {
+                            process_element_creation(
+                                &mut current_element,
+                                Element::Paragraph { elements: vec![] },
+                                &mut list_depth,
+                            );
                         }
                         Tag::Heading { level, .. } => {
                             let level = match level {
@@ -175,6 +254,9 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                                 &mut list_depth,
                             );
                         }
+                        Tag::DefinitionList if current_element.is_none() => {
+                            definition_list = Some(vec![]);
+                        }
                         Tag::Table(_) => {
                             let table_el = Table {
                                 headers: vec![],
@@ -192,7 +274,7 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                             dest_url, title, ..
                         } => {
                             let img_type = dest_url.to_string();
-                            let bytes = image_loader(&dest_url)?;
+                            let bytes = image_loader.resolve(&dest_url)?;
                             let img_el = Element::Image(ImageData::new(
                                 bytes,
                                 title.to_string(),
@@ -327,16 +409,54 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
                         }
                     }
                 }
+                Event::TaskListMarker(checked) => {
+                    if let Some(Element::List { elements, .. }) = current_element.as_mut() {
+                        let list_items = innermost_list_items(elements, list_depth);
+                        if let Some(item) = list_items.last_mut() {
+                            item.checked = Some(checked);
+                        }
+                    }
+                }
+                Event::InlineMath(ref latex) | Event::DisplayMath(ref latex) => {
+                    let display = matches!(event, Event::DisplayMath(_));
+                    match current_element.as_mut() {
+                        Some(Element::Paragraph { elements }) => elements.push(Element::Math {
+                            latex: latex.to_string(),
+                            display,
+                        }),
+                        Some(Element::List { elements, .. }) => {
+                            let list_items = innermost_list_items(elements, list_depth);
+                            if let Some(ListItem {
+                                element: Text { text, .. },
+                                ..
+                            }) = list_items.last_mut()
+                            {
+                                let fence = if display { "$$" } else { "$" };
+                                text.push_str(&format!("{fence}{latex}{fence}"));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
                 Event::End(tag) => match tag {
-                    TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Link | TagEnd::Image => {
-                        if !matches!(current_element, Some(Element::List { .. })) {
-                            let curr_el = current_element.take();
-                            if let Some(curr_el) = curr_el {
-                                match curr_el {
-                                    List { .. } => current_element = Some(curr_el),
-                                    _ => {
-                                        doc_elements.push(curr_el);
-                                    }
+                    TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Link | TagEnd::Image
+                        if !matches!(current_element, Some(Element::List { .. })) =>
+                    {
+                        let curr_el = current_element.take();
+                        if let Some(curr_el) = curr_el {
+                            match curr_el {
+                                List { .. } => current_element = Some(curr_el),
+                                // A paragraph holding only a display equation is a block of its own
+                                Element::Paragraph { mut elements }
+                                    if matches!(
+                                        elements.as_slice(),
+                                        [Element::Math { display: true, .. }]
+                                    ) =>
+                                {
+                                    doc_elements.push(elements.remove(0));
+                                }
+                                _ => {
+                                    doc_elements.push(curr_el);
                                 }
                             }
                         }
@@ -375,8 +495,109 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
     where
         F: Fn(&Bytes, &str) -> anyhow::Result<()>,
     {
+        Transformer::generate_with_options(document, &GenerateOptions::default(), image_saver)
+    }
+}
+
+/// Markdown dialect the generator targets. Each flavor only uses the syntax its
+/// readers understand, e.g. tables are written as HTML in plain CommonMark.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Flavor {
+    CommonMark,
+    /// GitHub Flavored Markdown.
+    #[default]
+    Gfm,
+    MultiMarkdown,
+    Pandoc,
+}
+
+impl Flavor {
+    pub fn supports_tables(&self) -> bool {
+        !matches!(self, Flavor::CommonMark)
+    }
+
+    pub fn supports_task_lists(&self) -> bool {
+        matches!(self, Flavor::Gfm | Flavor::Pandoc)
+    }
+
+    pub fn supports_strikethrough(&self) -> bool {
+        matches!(self, Flavor::Gfm | Flavor::Pandoc)
+    }
+
+    pub fn supports_footnotes(&self) -> bool {
+        !matches!(self, Flavor::CommonMark)
+    }
+
+    pub fn supports_math(&self) -> bool {
+        !matches!(self, Flavor::CommonMark)
+    }
+
+    pub fn supports_definition_lists(&self) -> bool {
+        matches!(self, Flavor::MultiMarkdown | Flavor::Pandoc)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HeadingStyle {
+    /// `# Title`
+    #[default]
+    Atx,
+    /// `Title` underlined with `=` or `-`. Only levels 1 and 2 exist in this style,
+    /// deeper headings are still written as ATX.
+    Setext,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BulletChar {
+    #[default]
+    Dash,
+    Star,
+    Plus,
+}
+
+/// Options for generating markdown.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerateOptions {
+    pub flavor: Flavor,
+    pub heading_style: HeadingStyle,
+    pub bullet: BulletChar,
+    /// Maximum line length of paragraphs. `None` keeps every paragraph on one line.
+    pub wrap_width: Option<usize>,
+    /// Metadata written before the content, as a YAML block or as
+    /// MultiMarkdown `Key: Value` lines depending on the flavor.
+    pub front_matter: Vec<(String, String)>,
+}
+
+impl GenerateOptions {
+    fn comrak_options(&self) -> comrak::Options<'static> {
+        let mut options = comrak::Options::default();
+        options.extension.table = self.flavor.supports_tables();
+        options.extension.tasklist = self.flavor.supports_task_lists();
+        options.extension.strikethrough = self.flavor.supports_strikethrough();
+        options.extension.footnotes = self.flavor.supports_footnotes();
+        options.extension.math_dollars = self.flavor.supports_math();
+        options.extension.description_lists = self.flavor.supports_definition_lists();
+        options.render.width = self.wrap_width.unwrap_or(0);
+        options.render.list_style = match self.bullet {
+            BulletChar::Dash => comrak::ListStyleType::Dash,
+            BulletChar::Star => comrak::ListStyleType::Star,
+            BulletChar::Plus => comrak::ListStyleType::Plus,
+        };
+        options
+    }
+}
+
+impl Transformer {
+    pub fn generate_with_options<F>(
+        document: &Document,
+        options: &GenerateOptions,
+        image_saver: F,
+    ) -> anyhow::Result<Bytes>
+    where
+        F: Fn(&Bytes, &str) -> anyhow::Result<()>,
+    {
+        use comrak::format_commonmark;
         use comrak::nodes::LineColumn;
-        use comrak::{format_commonmark, Arena, Options};
         use std::cell::RefCell;
 
         let arena = Arena::new();
@@ -395,22 +616,171 @@ impl TransformerWithImageLoaderSaverTrait for Transformer {
         let all_elements: Vec<&Element> = document.get_all_elements();
 
         for element in all_elements {
-            let node = element_to_ast_node(&arena, element, &image_num, &image_saver)?;
-            root.append(node);
+            let node = element_to_ast_node(&arena, element, &image_num, &image_saver, options)?;
+            if matches!(node.data.borrow().value, NodeValue::Document) {
+                // Elements written as several blocks come back in a document node
+                for child in node.children().collect::<Vec<_>>() {
+                    child.detach();
+                    root.append(child);
+                }
+            } else {
+                root.append(node);
+            }
         }
 
         let mut md = vec![];
 
-        format_commonmark(root, &Options::default(), &mut md)?;
+        format_commonmark(root, &options.comrak_options(), &mut md)?;
+
+        let mut md = String::from_utf8(md)?;
+        if options.heading_style == HeadingStyle::Setext {
+            md = setext_headings(&md);
+        }
+        if !options.front_matter.is_empty() {
+            md = front_matter(&options.front_matter, options.flavor) + &md;
+        }
 
         Ok(Bytes::from(md))
     }
 }
 
+/// Rewrites level 1 and 2 ATX headings. Text that starts with `#` is escaped by
+/// the renderer, so only heading lines can match.
+fn setext_headings(md: &str) -> String {
+    let mut output = String::new();
+    for line in md.lines() {
+        let heading = [("# ", '='), ("## ", '-')]
+            .into_iter()
+            .find_map(|(prefix, underline)| Some((line.strip_prefix(prefix)?, underline)));
+        match heading {
+            Some((text, underline)) if !text.trim().is_empty() => {
+                output.push_str(text);
+                output.push('\n');
+                output.push_str(&underline.to_string().repeat(text.chars().count().max(3)));
+            }
+            _ => output.push_str(line),
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn front_matter(entries: &[(String, String)], flavor: Flavor) -> String {
+    if flavor == Flavor::MultiMarkdown {
+        let mut output: String = entries
+            .iter()
+            .map(|(key, value)| format!("{key}: {value}\n"))
+            .collect();
+        output.push('\n');
+        return output;
+    }
+
+    let mut output = String::from("---\n");
+    for (key, value) in entries {
+        output.push_str(&format!("{key}: {}\n", yaml_scalar(value)));
+    }
+    output.push_str("---\n\n");
+    output
+}
+
+/// Quotes a value when YAML would otherwise read it as something other than a plain string.
+fn yaml_scalar(value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || yaml_typed(value)
+        || value != value.trim()
+        || value.contains([':', '#', '"', '\'', '\n'])
+        || value.starts_with(['-', '[', '{', '&', '*', '!', '|', '>', '%', '@', '`']);
+    if needs_quotes {
+        format!(
+            "\"{}\"",
+            value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        )
+    } else {
+        value.to_string()
+    }
+}
+
+/// Whether YAML 1.1 or 1.2 reads the plain value as a null, a boolean or a number.
+fn yaml_typed(value: &str) -> bool {
+    const WORDS: [&str; 10] = [
+        "~", "null", "true", "false", "yes", "no", "y", "n", "on", "off",
+    ];
+    let value = value.to_ascii_lowercase();
+    if WORDS.contains(&value.as_str()) {
+        return true;
+    }
+    let number = value
+        .strip_prefix(['+', '-'])
+        .unwrap_or(&value)
+        .replace('_', "");
+    let digits =
+        |rest: &str, radix: u32| !rest.is_empty() && rest.chars().all(|c| c.is_digit(radix));
+    if number == ".inf" || number == ".nan" {
+        return true;
+    }
+    match number.get(..2) {
+        Some("0x") => digits(&number[2..], 16),
+        Some("0o") => digits(&number[2..], 8),
+        Some("0b") => digits(&number[2..], 2),
+        _ => {
+            number.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+                && number.parse::<f64>().is_ok()
+        }
+    }
+}
+
 use comrak::nodes::{
-    Ast, AstNode, LineColumn, NodeHeading, NodeLink, NodeList, NodeTable, NodeValue, TableAlignment,
+    Ast, AstNode, LineColumn, NodeCode, NodeDescriptionItem, NodeHeading, NodeHtmlBlock, NodeLink,
+    NodeList, NodeMath, NodeTable, NodeValue, TableAlignment,
 };
 
+/// Raw HTML is the only way to express a table in CommonMark.
+fn html_table(headers: &[TableHeader], rows: &[TableRow]) -> String {
+    fn cell_html(element: &Element) -> String {
+        match element {
+            Element::Text { text, .. } => escape_html(text),
+            Element::Hyperlink { title, url, .. } => {
+                format!(
+                    "<a href=\"{}\">{}</a>",
+                    escape_html(url),
+                    escape_html(title)
+                )
+            }
+            Element::Paragraph { elements } => elements
+                .iter()
+                .map(cell_html)
+                .collect::<Vec<String>>()
+                .join(" "),
+            _ => String::new(),
+        }
+    }
+
+    let mut html = String::from("<table>\n<thead>\n<tr>\n");
+    for header in headers {
+        html.push_str(&format!("<th>{}</th>\n", cell_html(&header.element)));
+    }
+    html.push_str("</tr>\n</thead>\n<tbody>\n");
+    for row in rows {
+        html.push_str("<tr>\n");
+        for cell in &row.cells {
+            html.push_str(&format!("<td>{}</td>\n", cell_html(&cell.element)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 fn is_parent_list(list_item: &ListItem) -> bool {
     if let Element::List { elements, .. } = &list_item.element {
         let first = elements.first();
@@ -484,6 +854,7 @@ fn element_to_ast_node<'a, F>(
     element: &Element,
     image_num: &RefCell<i32>,
     image_saver: &ImageSaver<F>,
+    options: &GenerateOptions,
 ) -> anyhow::Result<&'a AstNode<'a>>
 where
     F: Fn(&Bytes, &str) -> anyhow::Result<()>,
@@ -521,7 +892,8 @@ where
             ))));
 
             for child_element in elements {
-                let child_node = element_to_ast_node(arena, child_element, image_num, image_saver)?;
+                let child_node =
+                    element_to_ast_node(arena, child_element, image_num, image_saver, options)?;
                 paragraph.append(child_node);
             }
             Ok(paragraph)
@@ -543,6 +915,7 @@ where
                                 &children.element,
                                 image_num,
                                 image_saver,
+                                options,
                             )?;
 
                             let parent_element = text_to_paragraph(parent.element.clone());
@@ -552,6 +925,7 @@ where
                                 &parent_element,
                                 image_num,
                                 image_saver,
+                                options,
                             )?;
 
                             item_node.append(list_item_content);
@@ -561,10 +935,35 @@ where
                         }
                     }
                 } else {
-                    let list_item_element = text_to_paragraph(list_item.element.clone());
-
-                    let list_item_content =
-                        element_to_ast_node(arena, &list_item_element, image_num, image_saver)?;
+                    let item_node = match list_item.checked {
+                        Some(checked) if options.flavor.supports_task_lists() => {
+                            arena.alloc(Node::new(RefCell::new(Ast::new(
+                                NodeValue::TaskItem(checked.then_some('x')),
+                                LineColumn { line: 0, column: 0 },
+                            ))))
+                        }
+                        _ => item_node,
+                    };
+                    let list_item_element = match (&list_item.element, list_item.checked) {
+                        (Element::Text { text, size }, Some(checked))
+                            if !options.flavor.supports_task_lists() =>
+                        {
+                            Element::Text {
+                                text: format!("{} {text}", if checked { '☑' } else { '☐' }),
+                                size: *size,
+                            }
+                        }
+                        (element, _) => element.clone(),
+                    };
+                    let list_item_element = text_to_paragraph(list_item_element);
+
+                    let list_item_content = element_to_ast_node(
+                        arena,
+                        &list_item_element,
+                        image_num,
+                        image_saver,
+                        options,
+                    )?;
                     item_node.append(list_item_content);
                     list_node.append(item_node);
                 }
@@ -608,6 +1007,138 @@ where
             Ok(link_node)
         }
 
+        Element::Math { latex, display } if options.flavor.supports_math() => {
+            let math = arena.alloc(Node::new(RefCell::new(Ast::new(
+                NodeValue::Math(NodeMath {
+                    dollar_math: true,
+                    display_math: *display,
+                    literal: latex.clone(),
+                }),
+                LineColumn { line: 0, column: 0 },
+            ))));
+            if !display {
+                return Ok(math);
+            }
+            let paragraph = arena.alloc(Node::new(RefCell::new(Ast::new(
+                NodeValue::Paragraph,
+                LineColumn { line: 0, column: 0 },
+            ))));
+            paragraph.append(math);
+            Ok(paragraph)
+        }
+
+        Element::Math { latex, display } => {
+            let code = arena.alloc(Node::new(RefCell::new(Ast::new(
+                NodeValue::Code(NodeCode {
+                    num_backticks: 1,
+                    literal: latex.clone(),
+                }),
+                LineColumn { line: 0, column: 0 },
+            ))));
+            if !display {
+                return Ok(code);
+            }
+            let paragraph = arena.alloc(Node::new(RefCell::new(Ast::new(
+                NodeValue::Paragraph,
+                LineColumn { line: 0, column: 0 },
+            ))));
+            paragraph.append(code);
+            Ok(paragraph)
+        }
+
+        Element::DefinitionList { items } if options.flavor.supports_definition_lists() => {
+            let list = arena.alloc(Node::new(RefCell::new(Ast::new(
+                NodeValue::DescriptionList,
+                LineColumn { line: 0, column: 0 },
+            ))));
+            for item in items {
+                let item_node = arena.alloc(Node::new(RefCell::new(Ast::new(
+                    NodeValue::DescriptionItem(NodeDescriptionItem::default()),
+                    LineColumn { line: 0, column: 0 },
+                ))));
+                let term = arena.alloc(Node::new(RefCell::new(Ast::new(
+                    NodeValue::DescriptionTerm,
+                    LineColumn { line: 0, column: 0 },
+                ))));
+                let term_paragraph = element_to_ast_node(
+                    arena,
+                    &text_to_paragraph(Text {
+                        text: item.term.clone(),
+                        size: 14,
+                    }),
+                    image_num,
+                    image_saver,
+                    options,
+                )?;
+                term.append(term_paragraph);
+                item_node.append(term);
+                for description in &item.descriptions {
+                    let details = arena.alloc(Node::new(RefCell::new(Ast::new(
+                        NodeValue::DescriptionDetails,
+                        LineColumn { line: 0, column: 0 },
+                    ))));
+                    let content = element_to_ast_node(
+                        arena,
+                        &text_to_paragraph(description.clone()),
+                        image_num,
+                        image_saver,
+                        options,
+                    )?;
+                    details.append(content);
+                    item_node.append(details);
+                }
+                list.append(item_node);
+            }
+            Ok(list)
+        }
+
+        // Without definition lists the term is written in bold, followed by its
+        // descriptions as paragraphs.
+        Element::DefinitionList { items } => {
+            let container = arena.alloc(Node::new(RefCell::new(Ast::new(
+                NodeValue::Document,
+                LineColumn { line: 0, column: 0 },
+            ))));
+            for item in items {
+                let term = arena.alloc(Node::new(RefCell::new(Ast::new(
+                    NodeValue::Paragraph,
+                    LineColumn { line: 0, column: 0 },
+                ))));
+                let strong = arena.alloc(Node::new(RefCell::new(Ast::new(
+                    NodeValue::Strong,
+                    LineColumn { line: 0, column: 0 },
+                ))));
+                strong.append(arena.alloc(Node::new(RefCell::new(Ast::new(
+                    NodeValue::Text(item.term.clone()),
+                    LineColumn { line: 0, column: 0 },
+                )))));
+                term.append(strong);
+                container.append(term);
+                for description in &item.descriptions {
+                    let content = element_to_ast_node(
+                        arena,
+                        &text_to_paragraph(description.clone()),
+                        image_num,
+                        image_saver,
+                        options,
+                    )?;
+                    container.append(content);
+                }
+            }
+            Ok(container)
+        }
+
+        Element::Table { headers, rows } if !options.flavor.supports_tables() => {
+            let node = arena.alloc(Node::new(RefCell::new(Ast::new(
+                NodeValue::HtmlBlock(NodeHtmlBlock {
+                    block_type: 6,
+                    literal: html_table(headers, rows),
+                }),
+                LineColumn { line: 0, column: 0 },
+            ))));
+            Ok(node)
+        }
+
         Element::Table { headers, rows } => {
             let num_columns = headers.len() as u32;
             let num_rows = rows.len() as u32 + 1;
@@ -635,7 +1166,7 @@ where
                     LineColumn { line: 0, column: 0 },
                 ))));
                 let cell_content =
-                    element_to_ast_node(arena, &header.element, image_num, image_saver)?;
+                    element_to_ast_node(arena, &header.element, image_num, image_saver, options)?;
                 cell_node.append(cell_content);
                 header_row_node.append(cell_node);
             }
@@ -653,7 +1184,7 @@ where
                         LineColumn { line: 0, column: 0 },
                     ))));
                     let cell_content =
-                        element_to_ast_node(arena, &cell.element, image_num, image_saver)?;
+                        element_to_ast_node(arena, &cell.element, image_num, image_saver, options)?;
                     cell_node.append(cell_content);
                     row_node.append(cell_node);
                 }
@@ -887,8 +1418,172 @@ blabla2 bla bla blabla bla bla blabla bla bla blabla bla bla bla"#;
             Transformer::parse_with_loader(&parsed_html_bytes, disk_image_loader("test/data"));
         info!("{:#?}", doc_from_markdown);
         info!("{}", std::str::from_utf8(&parsed_html_bytes)?);
-        assert!(true);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_flavors() -> anyhow::Result<()> {
+        init_logger();
+        let document = Document::new(vec![
+            Element::Header {
+                level: 1,
+                text: "Title".to_string(),
+            },
+            Element::Header {
+                level: 3,
+                text: "Section".to_string(),
+            },
+            Element::List {
+                elements: vec![ListItem {
+                    element: Element::Text {
+                        text: "item".to_string(),
+                        size: 8,
+                    },
+                    checked: None,
+                }],
+                numbered: false,
+            },
+            Element::Table {
+                headers: vec![TableHeader {
+                    element: Element::Text {
+                        text: "A & B".to_string(),
+                        size: 8,
+                    },
+                    width: 10.0,
+                }],
+                rows: vec![TableRow {
+                    cells: vec![TableCell {
+                        element: Element::Text {
+                            text: "1".to_string(),
+                            size: 8,
+                        },
+                    }],
+                }],
+            },
+        ]);
+        let no_images = |_: &Bytes, _: &str| Ok(());
+
+        let options = GenerateOptions {
+            flavor: Flavor::CommonMark,
+            heading_style: HeadingStyle::Setext,
+            bullet: BulletChar::Star,
+            front_matter: vec![
+                ("title".to_string(), "Notes: draft".to_string()),
+                ("author".to_string(), "Jane".to_string()),
+            ],
+            ..Default::default()
+        };
+        let generated = Transformer::generate_with_options(&document, &options, no_images)?;
+        let generated_text = std::str::from_utf8(&generated)?;
+        info!("{}", generated_text);
+        assert_eq!(
+            generated_text,
+            "---\ntitle: \"Notes: draft\"\nauthor: Jane\n---\n\nTitle\n=====\n\n### Section\n\n* item\n\n<table>\n<thead>\n<tr>\n<th>A &amp; B</th>\n</tr>\n</thead>\n<tbody>\n<tr>\n<td>1</td>\n</tr>\n</tbody>\n</table>\n"
+        );
 
+        let options = GenerateOptions {
+            flavor: Flavor::MultiMarkdown,
+            front_matter: vec![("Title".to_string(), "Notes".to_string())],
+            ..Default::default()
+        };
+        let generated = Transformer::generate_with_options(&document, &options, no_images)?;
+        let generated_text = std::str::from_utf8(&generated)?;
+        info!("{}", generated_text);
+        assert!(generated_text.starts_with("Title: Notes\n\n# Title\n"));
+        assert!(generated_text.contains("| A & B |\n| --- |\n| 1 |"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_yaml_scalar() {
+        for value in [
+            "true", "No", "null", "~", "1.0", "0x10", "-12", "1_000", ".inf", "1e3",
+        ] {
+            assert_eq!(yaml_scalar(value), format!("\"{value}\""));
+        }
+        for value in ["Jane", "2.0 beta", "0xZ", "yesterday", "v1.0"] {
+            assert_eq!(yaml_scalar(value), value);
+        }
+    }
+    #[test]
+    fn test_task_lists_definitions_math() -> anyhow::Result<()> {
+        init_logger();
+        let markdown = "- [x] done\n- [ ] todo\n\nTerm\n: Meaning of the term\n\nEnergy is $E = mc^2$.\n\n$$\\frac{a}{b}$$\n";
+        let parsed = Transformer::parse(&Bytes::from(markdown))?;
+        debug!("{:#?}", parsed);
+        let expected = Document::new(vec![
+            Element::List {
+                elements: vec![
+                    ListItem {
+                        element: Element::Text {
+                            text: "done".to_string(),
+                            size: 14,
+                        },
+                        checked: Some(true),
+                    },
+                    ListItem {
+                        element: Element::Text {
+                            text: "todo".to_string(),
+                            size: 14,
+                        },
+                        checked: Some(false),
+                    },
+                ],
+                numbered: false,
+            },
+            Element::DefinitionList {
+                items: vec![DefinitionItem {
+                    term: "Term".to_string(),
+                    descriptions: vec![Element::Text {
+                        text: "Meaning of the term".to_string(),
+                        size: 14,
+                    }],
+                }],
+            },
+            Element::Paragraph {
+                elements: vec![
+                    Element::Text {
+                        text: "Energy is ".to_string(),
+                        size: 14,
+                    },
+                    Element::Math {
+                        latex: "E = mc^2".to_string(),
+                        display: false,
+                    },
+                    Element::Text {
+                        text: ".".to_string(),
+                        size: 14,
+                    },
+                ],
+            },
+            Element::Math {
+                latex: "\\frac{a}{b}".to_string(),
+                display: true,
+            },
+        ]);
+        assert_eq!(parsed, expected);
+
+        let no_images = |_: &Bytes, _: &str| Ok(());
+        let options = GenerateOptions {
+            flavor: Flavor::Pandoc,
+            ..Default::default()
+        };
+        let generated = Transformer::generate_with_options(&parsed, &options, no_images)?;
+        let generated_text = std::str::from_utf8(&generated)?;
+        info!("{}", generated_text);
+        assert_eq!(Transformer::parse(&generated)?, expected);
+
+        let options = GenerateOptions {
+            flavor: Flavor::CommonMark,
+            ..Default::default()
+        };
+        let generated = Transformer::generate_with_options(&parsed, &options, no_images)?;
+        let generated_text = std::str::from_utf8(&generated)?;
+        info!("{}", generated_text);
+        assert_eq!(
+            generated_text,
+            "- ☑ done\n- ☐ todo\n\n**Term**\n\nMeaning of the term\n\nEnergy is `E = mc^2`.\n\n`\\frac{a}{b}`\n"
+        );
         Ok(())
     }
 }
@@ -0,0 +1,611 @@
+//! A small LaTeX math reader shared by the generators that need native math markup.
+//!
+//! It understands the subset of LaTeX that shows up in markdown documents: letters, numbers,
+//! operators, Greek letters and common symbols, `\frac`, `\sqrt`, sub- and superscripts,
+//! `\left`/`\right` delimiters and `\text`-like commands. Anything else is kept as text so
+//! the output stays valid even when it is not typeset perfectly.
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum MathNode {
+    Identifier(char),
+    Number(String),
+    Operator(String),
+    /// Upright name such as `sin` or the content of `\mathrm`.
+    Function(String),
+    Text(String),
+    Fraction(Vec<MathNode>, Vec<MathNode>),
+    Root {
+        degree: Option<Vec<MathNode>>,
+        radicand: Vec<MathNode>,
+    },
+    Scripts {
+        base: Box<MathNode>,
+        sub: Option<Vec<MathNode>>,
+        sup: Option<Vec<MathNode>>,
+    },
+    Group(Vec<MathNode>),
+    Fenced {
+        open: String,
+        close: String,
+        content: Vec<MathNode>,
+    },
+}
+
+const SYMBOLS: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("int", "∫"),
+    ("iint", "∬"),
+    ("oint", "∮"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("infty", "∞"),
+    ("cdot", "⋅"),
+    ("times", "×"),
+    ("div", "÷"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("leq", "≤"),
+    ("le", "≤"),
+    ("geq", "≥"),
+    ("ge", "≥"),
+    ("neq", "≠"),
+    ("ne", "≠"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("propto", "∝"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("leftrightarrow", "↔"),
+    ("Leftrightarrow", "⇔"),
+    ("mapsto", "↦"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("supset", "⊃"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("emptyset", "∅"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("neg", "¬"),
+    ("land", "∧"),
+    ("lor", "∨"),
+    ("circ", "∘"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("cdots", "⋯"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+    ("lbrace", "{"),
+    ("rbrace", "}"),
+    ("{", "{"),
+    ("}", "}"),
+    ("|", "‖"),
+    ("%", "%"),
+    ("$", "$"),
+    ("&", "&"),
+    ("#", "#"),
+    ("_", "_"),
+];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "log", "ln", "lg", "exp", "lim", "max", "min", "sup", "inf", "det", "gcd", "deg", "dim", "ker",
+    "arg",
+];
+
+/// Commands that only change the font of their argument.
+const STYLES: &[&str] = &[
+    "mathbf",
+    "mathit",
+    "mathbb",
+    "mathcal",
+    "mathsf",
+    "mathtt",
+    "mathfrak",
+    "boldsymbol",
+    "bm",
+];
+
+/// Spacing commands carry no content.
+const SPACES: &[&str] = &[",", ";", ":", "!", " ", "quad", "qquad"];
+
+pub(crate) fn parse(latex: &str) -> Vec<MathNode> {
+    let chars: Vec<char> = latex.chars().collect();
+    let mut position = 0;
+    let mut nodes = parse_sequence(&chars, &mut position, None);
+    // an unbalanced `}` ends a sequence early, skip it and read on
+    while position < chars.len() {
+        position += 1;
+        nodes.extend(parse_sequence(&chars, &mut position, None));
+    }
+    nodes
+}
+
+/// Parses nodes until the end of input, a closing `}` or, when `until` is set, the given command.
+fn parse_sequence(chars: &[char], position: &mut usize, until: Option<&str>) -> Vec<MathNode> {
+    let mut nodes: Vec<MathNode> = vec![];
+    while *position < chars.len() {
+        let c = chars[*position];
+        if c == '}' {
+            break;
+        }
+        if c == '\\' {
+            if let Some(until) = until {
+                if peek_command(chars, *position).as_deref() == Some(until) {
+                    break;
+                }
+            }
+        }
+        if c == '_' || c == '^' {
+            *position += 1;
+            let script = parse_argument(chars, position);
+            let (base, mut sub, mut sup) = match nodes.pop() {
+                Some(MathNode::Scripts { base, sub, sup }) => (base, sub, sup),
+                Some(node) => (Box::new(node), None, None),
+                None => (Box::new(MathNode::Group(vec![])), None, None),
+            };
+            if c == '_' {
+                sub = Some(script);
+            } else {
+                sup = Some(script);
+            }
+            nodes.push(MathNode::Scripts { base, sub, sup });
+            continue;
+        }
+        if let Some(node) = parse_atom(chars, position) {
+            nodes.push(node);
+        }
+    }
+    nodes
+}
+
+fn peek_command(chars: &[char], position: usize) -> Option<String> {
+    let mut end = position + 1;
+    while end < chars.len() && chars[end].is_ascii_alphabetic() {
+        end += 1;
+    }
+    if end == position + 1 {
+        chars.get(end).map(|c| c.to_string())
+    } else {
+        Some(chars[position + 1..end].iter().collect())
+    }
+}
+
+/// Reads a `{...}` group or a single atom.
+fn parse_argument(chars: &[char], position: &mut usize) -> Vec<MathNode> {
+    skip_whitespace(chars, position);
+    match parse_atom(chars, position) {
+        Some(MathNode::Group(nodes)) => nodes,
+        Some(node) => vec![node],
+        None => vec![],
+    }
+}
+
+fn parse_raw_group(chars: &[char], position: &mut usize) -> String {
+    skip_whitespace(chars, position);
+    if chars.get(*position) != Some(&'{') {
+        return String::new();
+    }
+    *position += 1;
+    let mut depth = 1;
+    let mut text = String::new();
+    while let Some(&c) = chars.get(*position) {
+        *position += 1;
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
+            _ => {}
+        }
+        text.push(c);
+    }
+    text
+}
+
+fn skip_whitespace(chars: &[char], position: &mut usize) {
+    while chars.get(*position).is_some_and(|c| c.is_whitespace()) {
+        *position += 1;
+    }
+}
+
+fn parse_atom(chars: &[char], position: &mut usize) -> Option<MathNode> {
+    let c = *chars.get(*position)?;
+    if c.is_whitespace() {
+        *position += 1;
+        return None;
+    }
+    if c == '{' {
+        *position += 1;
+        let nodes = parse_sequence(chars, position, None);
+        if chars.get(*position) == Some(&'}') {
+            *position += 1;
+        }
+        return Some(MathNode::Group(nodes));
+    }
+    if c == '\\' {
+        return parse_command(chars, position);
+    }
+    *position += 1;
+    if c.is_ascii_digit() {
+        let mut number = c.to_string();
+        while let Some(&next) = chars.get(*position) {
+            let decimal_point =
+                next == '.' && chars.get(*position + 1).is_some_and(|c| c.is_ascii_digit());
+            if !next.is_ascii_digit() && !decimal_point {
+                break;
+            }
+            number.push(next);
+            *position += 1;
+        }
+        return Some(MathNode::Number(number));
+    }
+    if c.is_alphabetic() {
+        return Some(MathNode::Identifier(c));
+    }
+    Some(MathNode::Operator(c.to_string()))
+}
+
+fn parse_command(chars: &[char], position: &mut usize) -> Option<MathNode> {
+    let Some(name) = peek_command(chars, *position) else {
+        // A backslash at the end of the formula is kept as it is
+        *position += 1;
+        return Some(MathNode::Operator("\\".to_string()));
+    };
+    *position += 1 + name.chars().count();
+
+    if SPACES.contains(&name.as_str()) {
+        return None;
+    }
+    if name == "\\" {
+        return Some(MathNode::Operator("\n".to_string()));
+    }
+    if let Some((_, symbol)) = SYMBOLS.iter().find(|(command, _)| *command == name) {
+        return Some(MathNode::Operator(symbol.to_string()));
+    }
+    if FUNCTIONS.contains(&name.as_str()) {
+        return Some(MathNode::Function(name));
+    }
+    if STYLES.contains(&name.as_str()) {
+        return Some(MathNode::Group(parse_argument(chars, position)));
+    }
+
+    match name.as_str() {
+        "frac" | "dfrac" | "tfrac" => {
+            let numerator = parse_argument(chars, position);
+            let denominator = parse_argument(chars, position);
+            Some(MathNode::Fraction(numerator, denominator))
+        }
+        "sqrt" => {
+            skip_whitespace(chars, position);
+            let degree = if chars.get(*position) == Some(&'[') {
+                let end = chars[*position..].iter().position(|c| *c == ']')? + *position;
+                let degree: String = chars[*position + 1..end].iter().collect();
+                *position = end + 1;
+                Some(parse(&degree))
+            } else {
+                None
+            };
+            let radicand = parse_argument(chars, position);
+            Some(MathNode::Root { degree, radicand })
+        }
+        "text" | "textrm" | "textit" | "textbf" | "mbox" => {
+            Some(MathNode::Text(parse_raw_group(chars, position)))
+        }
+        "mathrm" | "operatorname" => Some(MathNode::Function(parse_raw_group(chars, position))),
+        "left" => {
+            let open = parse_delimiter(chars, position);
+            let content = parse_sequence(chars, position, Some("right"));
+            let close = if peek_command(chars, *position).as_deref() == Some("right") {
+                *position += "\\right".len();
+                parse_delimiter(chars, position)
+            } else {
+                String::new()
+            };
+            Some(MathNode::Fenced {
+                open,
+                close,
+                content,
+            })
+        }
+        // a `\right` without `\left` only closes a delimiter
+        "right" => {
+            parse_delimiter(chars, position);
+            None
+        }
+        _ => Some(MathNode::Text(name)),
+    }
+}
+
+/// Reads the delimiter after `\left` or `\right`. `.` stands for no delimiter.
+fn parse_delimiter(chars: &[char], position: &mut usize) -> String {
+    skip_whitespace(chars, position);
+    match parse_atom(chars, position) {
+        Some(MathNode::Operator(delimiter)) if delimiter == "." => String::new(),
+        Some(MathNode::Operator(delimiter)) => delimiter,
+        _ => String::new(),
+    }
+}
+
+/// Writes nodes as typst math. Letters are separated so that typst does not read
+/// them as a multi-letter variable name.
+#[cfg(feature = "typst")]
+pub(crate) fn to_typst(nodes: &[MathNode]) -> String {
+    let mut output = String::new();
+    for node in nodes {
+        if !output.is_empty() && !output.ends_with(' ') {
+            output.push(' ');
+        }
+        output.push_str(&node_to_typst(node));
+    }
+    output
+}
+
+#[cfg(feature = "typst")]
+fn node_to_typst(node: &MathNode) -> String {
+    fn argument(nodes: &[MathNode]) -> String {
+        format!("({})", to_typst(nodes))
+    }
+
+    fn string(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    match node {
+        MathNode::Identifier(c) => c.to_string(),
+        MathNode::Number(number) => number.clone(),
+        MathNode::Operator(operator) => match operator.as_str() {
+            "\n" => "\\".to_string(),
+            // commas would separate function arguments
+            "," => string(","),
+            "/" | "_" | "^" | "&" | "#" | "$" | "\"" | "\\" | "{" | "}" | "@" | "<" | ">" | "'"
+            | "*" | "[" | "]" => format!("\\{operator}"),
+            _ => operator.clone(),
+        },
+        MathNode::Function(name) => format!("op({})", string(name)),
+        MathNode::Text(text) => string(text),
+        MathNode::Fraction(numerator, denominator) => {
+            format!("frac({}, {})", to_typst(numerator), to_typst(denominator))
+        }
+        MathNode::Root {
+            degree: Some(degree),
+            radicand,
+        } => format!("root({}, {})", to_typst(degree), to_typst(radicand)),
+        MathNode::Root {
+            degree: None,
+            radicand,
+        } => format!("sqrt{}", argument(radicand)),
+        MathNode::Scripts { base, sub, sup } => {
+            let mut output = match base.as_ref() {
+                MathNode::Group(nodes) if nodes.is_empty() => "\"\"".to_string(),
+                base => node_to_typst(base),
+            };
+            if let Some(sub) = sub {
+                output.push('_');
+                output.push_str(&argument(sub));
+            }
+            if let Some(sup) = sup {
+                output.push('^');
+                output.push_str(&argument(sup));
+            }
+            output
+        }
+        MathNode::Group(nodes) => to_typst(nodes),
+        MathNode::Fenced {
+            open,
+            close,
+            content,
+        } => {
+            let delimiter = |d: &str| match d {
+                "(" | ")" | "{" | "}" | "[" | "]" => format!("\\{d}"),
+                _ => d.to_string(),
+            };
+            format!(
+                "lr({} {} {})",
+                delimiter(open),
+                to_typst(content),
+                delimiter(close)
+            )
+        }
+    }
+}
+
+/// Writes nodes as Office Math Markup (the content of an `m:oMath` element).
+#[cfg(feature = "docx")]
+pub(crate) fn to_omml(nodes: &[MathNode]) -> String {
+    nodes.iter().map(node_to_omml).collect()
+}
+
+#[cfg(feature = "docx")]
+fn node_to_omml(node: &MathNode) -> String {
+    fn run(text: &str, properties: &str) -> String {
+        format!(
+            "<m:r>{properties}<m:t xml:space=\"preserve\">{}</m:t></m:r>",
+            escape_xml(text)
+        )
+    }
+
+    match node {
+        MathNode::Identifier(c) => run(&c.to_string(), ""),
+        MathNode::Number(text) | MathNode::Operator(text) => run(text, ""),
+        MathNode::Function(name) => run(name, "<m:rPr><m:sty m:val=\"p\"/></m:rPr>"),
+        MathNode::Text(text) => run(text, "<m:rPr><m:nor/></m:rPr>"),
+        MathNode::Fraction(numerator, denominator) => format!(
+            "<m:f><m:num>{}</m:num><m:den>{}</m:den></m:f>",
+            to_omml(numerator),
+            to_omml(denominator)
+        ),
+        MathNode::Root { degree, radicand } => match degree {
+            Some(degree) => format!(
+                "<m:rad><m:deg>{}</m:deg><m:e>{}</m:e></m:rad>",
+                to_omml(degree),
+                to_omml(radicand)
+            ),
+            None => format!(
+                "<m:rad><m:radPr><m:degHide m:val=\"1\"/></m:radPr><m:deg/><m:e>{}</m:e></m:rad>",
+                to_omml(radicand)
+            ),
+        },
+        MathNode::Scripts { base, sub, sup } => {
+            let base = node_to_omml(base);
+            match (sub, sup) {
+                (Some(sub), Some(sup)) => format!(
+                    "<m:sSubSup><m:e>{base}</m:e><m:sub>{}</m:sub><m:sup>{}</m:sup></m:sSubSup>",
+                    to_omml(sub),
+                    to_omml(sup)
+                ),
+                (Some(sub), None) => format!(
+                    "<m:sSub><m:e>{base}</m:e><m:sub>{}</m:sub></m:sSub>",
+                    to_omml(sub)
+                ),
+                (None, Some(sup)) => format!(
+                    "<m:sSup><m:e>{base}</m:e><m:sup>{}</m:sup></m:sSup>",
+                    to_omml(sup)
+                ),
+                (None, None) => base,
+            }
+        }
+        MathNode::Group(nodes) => to_omml(nodes),
+        MathNode::Fenced {
+            open,
+            close,
+            content,
+        } => format!(
+            "<m:d><m:dPr><m:begChr m:val=\"{}\"/><m:endChr m:val=\"{}\"/></m:dPr><m:e>{}</m:e></m:d>",
+            escape_xml(open),
+            escape_xml(close),
+            to_omml(content)
+        ),
+    }
+}
+
+#[cfg(feature = "docx")]
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::*;
+
+    #[test]
+    fn test_parse() {
+        let nodes = parse(r"\frac{a+1}{\sqrt{x}} = x_i^2 \cdot \alpha");
+        assert_eq!(
+            nodes[0],
+            MathNode::Fraction(
+                vec![
+                    MathNode::Identifier('a'),
+                    MathNode::Operator("+".to_string()),
+                    MathNode::Number("1".to_string()),
+                ],
+                vec![MathNode::Root {
+                    degree: None,
+                    radicand: vec![MathNode::Identifier('x')],
+                }],
+            )
+        );
+        assert_eq!(
+            nodes[2],
+            MathNode::Scripts {
+                base: Box::new(MathNode::Identifier('x')),
+                sub: Some(vec![MathNode::Identifier('i')]),
+                sup: Some(vec![MathNode::Number("2".to_string())]),
+            }
+        );
+        assert_eq!(nodes[4], MathNode::Operator("α".to_string()));
+    }
+
+    #[cfg(feature = "typst")]
+    #[test]
+    fn test_to_typst() {
+        let nodes = parse(r"\sum_{i=1}^{n} \frac{xy}{2} + \left( a, b \right) \text{ok}");
+        assert_eq!(
+            to_typst(&nodes),
+            r#"∑_(i = 1)^(n) frac(x y, 2) + lr(\( a "," b \)) "ok""#
+        );
+    }
+
+    #[cfg(feature = "docx")]
+    #[test]
+    fn test_to_omml() {
+        let nodes = parse(r"x^2 < \sin y");
+        assert_eq!(
+            to_omml(&nodes),
+            "<m:sSup><m:e><m:r><m:t xml:space=\"preserve\">x</m:t></m:r></m:e><m:sup><m:r><m:t xml:space=\"preserve\">2</m:t></m:r></m:sup></m:sSup>\
+<m:r><m:t xml:space=\"preserve\">&lt;</m:t></m:r>\
+<m:r><m:rPr><m:sty m:val=\"p\"/></m:rPr><m:t xml:space=\"preserve\">sin</m:t></m:r>\
+<m:r><m:t xml:space=\"preserve\">y</m:t></m:r>"
+        );
+    }
+
+    #[test]
+    fn test_parse_backslash() {
+        // A formula that ends with a backslash once never finished parsing
+        assert_eq!(
+            parse(r"a\"),
+            vec![
+                MathNode::Identifier('a'),
+                MathNode::Operator(r"\".to_string())
+            ]
+        );
+        assert_eq!(
+            parse(r"a\,b\%"),
+            vec![
+                MathNode::Identifier('a'),
+                MathNode::Identifier('b'),
+                MathNode::Operator("%".to_string())
+            ]
+        );
+    }
+}
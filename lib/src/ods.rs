@@ -104,10 +104,8 @@ impl TransformerTrait for Transformer {
             }
             Ok(())
         }
-        let mut sheet_index = 1;
-        for element in &document.get_all_elements() {
+        for (sheet_index, element) in (1..).zip(document.get_all_elements()) {
             generate_element(element, &mut workbook, sheet_index)?;
-            sheet_index += 1;
         }
 
         let mut ods_data = vec![];
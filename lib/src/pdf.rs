@@ -1,12 +1,13 @@
 use crate::core::Element::{List, Paragraph, Text};
 use crate::core::{Document, Element, ListItem, ParserError, TransformerTrait};
 
+use crate::typst::ShivaWorld;
 use anyhow;
 use bytes::Bytes;
 use log::{debug, warn};
 use lopdf::content::Content;
 use lopdf::{Document as PdfDocument, Object, ObjectId};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use typst::{eval::Tracer, foundations::Smart};
 
 pub struct Transformer;
@@ -24,12 +25,37 @@ impl TransformerTrait for Transformer {
         Ok(Document::new(elements))
     }
     fn generate(document: &Document) -> anyhow::Result<Bytes> {
+        Transformer::generate_with_options(document, &GenerateOptions::default())
+    }
+}
+
+/// Options for generating PDF.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GenerateOptions {
+    /// TTF and OTF fonts or font collections to typeset with. When empty the
+    /// fonts of the `fonts` folder are used, which needs a file system and so
+    /// fonts must be given on wasm32.
+    pub fonts: Vec<Bytes>,
+}
+
+impl Transformer {
+    pub fn generate_with_options(
+        document: &Document,
+        options: &GenerateOptions,
+    ) -> anyhow::Result<Bytes> {
         let (text, img_map) = crate::typst::generate_document(document)?;
 
-        let world = crate::typst::ShivaWorld::new(text, img_map);
+        let world = if options.fonts.is_empty() {
+            default_world(text, img_map)?
+        } else {
+            ShivaWorld::with_fonts(text, img_map, &options.fonts)?
+        };
         let mut tracer = Tracer::default();
 
-        let document = typst::compile(&world, &mut tracer).unwrap();
+        let document = typst::compile(&world, &mut tracer).map_err(|errors| {
+            let messages: Vec<_> = errors.iter().map(|error| error.message.as_str()).collect();
+            anyhow::anyhow!("Typesetting failed: {}", messages.join("; "))
+        })?;
         let warnings = tracer.warnings();
 
         if !warnings.is_empty() {
@@ -47,6 +73,24 @@ impl TransformerTrait for Transformer {
     }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn default_world(
+    text: String,
+    img_map: HashMap<String, typst::foundations::Bytes>,
+) -> anyhow::Result<ShivaWorld> {
+    ShivaWorld::with_fonts(text, img_map, &crate::typst::folder_fonts("fonts")?)
+}
+
+#[cfg(target_arch = "wasm32")]
+fn default_world(
+    _text: String,
+    _img_map: HashMap<String, typst::foundations::Bytes>,
+) -> anyhow::Result<ShivaWorld> {
+    Err(anyhow::anyhow!(
+        "PDF generation on wasm32 needs fonts, pass them in GenerateOptions::fonts"
+    ))
+}
+
 fn parse_object(
     page_id: ObjectId,
     pdf_document: &PdfDocument,
@@ -91,6 +135,7 @@ fn parse_object(
                                             };
                                             let new_list_item_element = ListItem {
                                                 element: text_element,
+                                                checked: None,
                                             };
                                             list_item_elements.push(new_list_item_element);
                                             let new_list = List {
@@ -143,10 +188,8 @@ fn parse_object(
                     let _ = collect_text(text, encoding, arr, elements);
                     text.push(' ');
                 }
-                Object::Integer(i) => {
-                    if i < -100 {
-                        text.push(' ');
-                    }
+                Object::Integer(i) if i < -100 => {
+                    text.push(' ');
                 }
                 _ => {}
             }
@@ -212,11 +255,7 @@ fn parse_object(
             "Tj" | "TJ" => {
                 _ = collect_text(&mut text, current_encoding, &operation.operands, elements);
             }
-            "ET" => {
-                if !text.ends_with('\n') {
-                    text.push('\n')
-                }
-            }
+            "ET" if !text.ends_with('\n') => text.push('\n'),
             _ => {}
         }
     }
@@ -260,6 +299,7 @@ fn parse_object(
                             let mut list_item_elements = list_elements.clone();
                             let new_list_item_element = ListItem {
                                 element: text_element,
+                                checked: None,
                             };
                             list_item_elements.push(new_list_item_element);
                             let new_list = List {
@@ -302,6 +342,24 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_generate_with_fonts() -> anyhow::Result<()> {
+        let document = markdown::Transformer::parse(&Bytes::from("# Title\n\nText"))?;
+
+        let options = pdf::GenerateOptions {
+            fonts: vec![Bytes::from(std::fs::read("fonts/LinLibertine_R.ttf")?)],
+        };
+        let generated = Transformer::generate_with_options(&document, &options)?;
+        assert!(generated.starts_with(b"%PDF-"));
+
+        // Fonts that cannot be read are an error, not a panic
+        let options = pdf::GenerateOptions {
+            fonts: vec![Bytes::from("not a font")],
+        };
+        assert!(Transformer::generate_with_options(&document, &options).is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_md() -> anyhow::Result<()> {
         let document = std::fs::read("test/data/document.md")?;
@@ -351,6 +409,15 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_math_and_definition_list() -> anyhow::Result<()> {
+        let markdown = "- [x] done\n- [ ] todo\n\nTerm\n: Meaning\n\nInline $\\sqrt[3]{x_1^2} + \\left( \\frac{a}{b} \\right]$ math.\n\n$$\\sum_{i=1}^{n} \\alpha_i \\text{ for } i \\leq n, \\{ \\# \\}$$\n";
+        let parsed_document = markdown::Transformer::parse(&Bytes::from(markdown))?;
+        let generated_result = Transformer::generate(&parsed_document)?;
+        assert!(generated_result.starts_with(b"%PDF"));
+        Ok(())
+    }
+
     #[test]
     fn test_hyperlink_generation() -> anyhow::Result<()> {
         use Element::*;
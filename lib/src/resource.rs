@@ -0,0 +1,449 @@
+//! Resolvers for images and other assets referenced from a document.
+//!
+//! Parsers that follow references (`<img src>`, `![](..)`) ask a
+//! [`ResourceResolver`] for the bytes. Plain closures `Fn(&str) -> Result<Bytes>`
+//! are resolvers too, so existing loaders keep working.
+
+use bytes::Bytes;
+use log::info;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+pub trait ResourceResolver {
+    /// Returns the bytes of the resource referenced by `uri`.
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes>;
+
+    /// Like [`ResourceResolver::resolve`], but fails when the resource is
+    /// larger than `max_size` bytes. Files and archive entries are read no
+    /// further than that.
+    fn resolve_limited(&self, uri: &str, max_size: usize) -> anyhow::Result<Bytes> {
+        let bytes = self.resolve(uri)?;
+        check_size(uri, bytes.len(), max_size)?;
+        Ok(bytes)
+    }
+}
+
+impl<F> ResourceResolver for F
+where
+    F: Fn(&str) -> anyhow::Result<Bytes>,
+{
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        self(uri)
+    }
+}
+
+fn check_size(uri: &str, size: usize, max_size: usize) -> anyhow::Result<()> {
+    if size > max_size {
+        return Err(anyhow::anyhow!(
+            "Resource {uri} is larger than the limit of {max_size} bytes"
+        ));
+    }
+    Ok(())
+}
+
+/// Reads `reader` up to one byte past `max_size`, without trusting any size
+/// the source declares.
+fn read_limited(uri: &str, reader: impl Read, max_size: usize) -> anyhow::Result<Bytes> {
+    let mut bytes = Vec::new();
+    reader
+        .take((max_size as u64).saturating_add(1))
+        .read_to_end(&mut bytes)?;
+    check_size(uri, bytes.len(), max_size)?;
+    Ok(Bytes::from(bytes))
+}
+
+/// Splits a relative reference into path segments, rejecting absolute paths
+/// and `..` so that the reference cannot leave its root.
+fn relative_segments(uri: &str) -> anyhow::Result<Vec<&str>> {
+    if uri.contains("://") {
+        return Err(anyhow::anyhow!("Unsupported resource scheme: {uri}"));
+    }
+    let mut segments = Vec::new();
+    for component in Path::new(uri).components() {
+        match component {
+            Component::Normal(segment) => segments.push(
+                segment
+                    .to_str()
+                    .ok_or_else(|| anyhow::anyhow!("Invalid resource path: {uri}"))?,
+            ),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err(anyhow::anyhow!("Resource path escapes the root: {uri}"))
+            }
+        }
+    }
+    if segments.is_empty() {
+        return Err(anyhow::anyhow!("Empty resource path"));
+    }
+    Ok(segments)
+}
+
+/// Reads resources from a directory. References are relative to `root` and
+/// may not point outside of it, neither with `..` nor through symlinks.
+#[derive(Debug, Clone)]
+pub struct FileResolver {
+    root: PathBuf,
+    confined: bool,
+}
+
+impl FileResolver {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            confined: true,
+        }
+    }
+
+    /// Reads resources relative to `base` without confining them to it, so
+    /// that `../images/a.png` and absolute paths work as in any local tool.
+    pub fn unconfined(base: impl Into<PathBuf>) -> Self {
+        Self {
+            root: base.into(),
+            confined: false,
+        }
+    }
+
+    fn path(&self, uri: &str) -> anyhow::Result<PathBuf> {
+        let uri = uri.strip_prefix("file:").unwrap_or(uri);
+        if !self.confined {
+            let path = self.root.join(uri);
+            info!("Loading resource: {}", path.display());
+            return Ok(path);
+        }
+        let path = relative_segments(uri)?
+            .into_iter()
+            .fold(self.root.clone(), |path, segment| path.join(segment));
+
+        let root = self.root.canonicalize()?;
+        let canonical = path.canonicalize()?;
+        if !canonical.starts_with(&root) {
+            return Err(anyhow::anyhow!("Resource path escapes the root: {uri}"));
+        }
+        info!("Loading resource: {}", canonical.display());
+        Ok(canonical)
+    }
+}
+
+impl ResourceResolver for FileResolver {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        Ok(Bytes::from(std::fs::read(self.path(uri)?)?))
+    }
+
+    fn resolve_limited(&self, uri: &str, max_size: usize) -> anyhow::Result<Bytes> {
+        read_limited(uri, std::fs::File::open(self.path(uri)?)?, max_size)
+    }
+}
+
+/// Serves resources from memory, keyed by their relative path.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    resources: HashMap<String, Bytes>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, path: &str, bytes: Bytes) {
+        self.resources.insert(path.to_string(), bytes);
+    }
+}
+
+impl From<HashMap<String, Bytes>> for MemoryResolver {
+    fn from(resources: HashMap<String, Bytes>) -> Self {
+        Self { resources }
+    }
+}
+
+impl ResourceResolver for MemoryResolver {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        if let Some(bytes) = self.resources.get(uri) {
+            return Ok(bytes.clone());
+        }
+        let path = relative_segments(uri)?.join("/");
+        self.resources
+            .get(&path)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Resource not found: {uri}"))
+    }
+}
+
+/// Serves resources from the entries of a ZIP archive.
+#[cfg(feature = "zip")]
+pub struct ZipResolver {
+    archive: Mutex<zip::ZipArchive<std::io::Cursor<Bytes>>>,
+}
+
+#[cfg(feature = "zip")]
+impl ZipResolver {
+    pub fn new(archive: Bytes) -> anyhow::Result<Self> {
+        let archive = zip::ZipArchive::new(std::io::Cursor::new(archive))?;
+        Ok(Self {
+            archive: Mutex::new(archive),
+        })
+    }
+}
+
+#[cfg(feature = "zip")]
+impl ResourceResolver for ZipResolver {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        self.resolve_limited(uri, usize::MAX)
+    }
+
+    // The size an entry declares is not trusted, the entry is read until the limit
+    fn resolve_limited(&self, uri: &str, max_size: usize) -> anyhow::Result<Bytes> {
+        let path = relative_segments(uri)?.join("/");
+        let mut archive = self
+            .archive
+            .lock()
+            .map_err(|_| anyhow::anyhow!("ZIP archive lock poisoned"))?;
+        let file = archive
+            .by_name(&path)
+            .map_err(|_| anyhow::anyhow!("Resource not found: {uri}"))?;
+        read_limited(uri, file, max_size)
+    }
+}
+
+/// Decodes `data:` URIs, both base64 and percent-encoded.
+#[cfg(feature = "base64")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DataUriResolver;
+
+#[cfg(feature = "base64")]
+impl ResourceResolver for DataUriResolver {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        use base64::Engine;
+
+        let data = uri
+            .strip_prefix("data:")
+            .ok_or_else(|| anyhow::anyhow!("Not a data URI: {uri}"))?;
+        let (header, payload) = data
+            .split_once(',')
+            .ok_or_else(|| anyhow::anyhow!("Malformed data URI"))?;
+        if header.ends_with(";base64") {
+            let payload: String = payload.split_whitespace().collect();
+            Ok(Bytes::from(
+                base64::engine::general_purpose::STANDARD.decode(payload)?,
+            ))
+        } else {
+            Ok(Bytes::from(percent_decode(payload)))
+        }
+    }
+}
+
+#[cfg(feature = "base64")]
+fn percent_decode(text: &str) -> Vec<u8> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    decoded
+}
+
+/// Tries each resolver in turn and returns the first success.
+#[derive(Default)]
+pub struct ChainResolver {
+    resolvers: Vec<Box<dyn ResourceResolver>>,
+}
+
+impl ChainResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, resolver: impl ResourceResolver + 'static) -> Self {
+        self.resolvers.push(Box::new(resolver));
+        self
+    }
+}
+
+impl ResourceResolver for ChainResolver {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        self.resolve_limited(uri, usize::MAX)
+    }
+
+    fn resolve_limited(&self, uri: &str, max_size: usize) -> anyhow::Result<Bytes> {
+        let mut last_error = None;
+        for resolver in &self.resolvers {
+            match resolver.resolve_limited(uri, max_size) {
+                Ok(bytes) => return Ok(bytes),
+                Err(error) => last_error = Some(error),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No resolver for: {uri}")))
+    }
+}
+
+/// Remembers resolved resources so that repeated references are loaded once.
+pub struct CachedResolver<R> {
+    inner: R,
+    cache: Mutex<HashMap<String, Bytes>>,
+}
+
+impl<R: ResourceResolver> CachedResolver<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<R: ResourceResolver> ResourceResolver for CachedResolver<R> {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        self.resolve_limited(uri, usize::MAX)
+    }
+
+    fn resolve_limited(&self, uri: &str, max_size: usize) -> anyhow::Result<Bytes> {
+        if let Some(bytes) = self
+            .cache
+            .lock()
+            .ok()
+            .and_then(|cache| cache.get(uri).cloned())
+        {
+            check_size(uri, bytes.len(), max_size)?;
+            return Ok(bytes);
+        }
+        let bytes = self.inner.resolve_limited(uri, max_size)?;
+        if let Ok(mut cache) = self.cache.lock() {
+            cache.insert(uri.to_string(), bytes.clone());
+        }
+        Ok(bytes)
+    }
+}
+
+/// Rejects resources larger than `max_size` bytes and, optionally, stops
+/// resolving once `max_total` bytes have been loaded in total.
+pub struct LimitedResolver<R> {
+    inner: R,
+    max_size: usize,
+    max_total: Option<usize>,
+    total: Mutex<usize>,
+}
+
+impl<R: ResourceResolver> LimitedResolver<R> {
+    pub fn new(inner: R, max_size: usize) -> Self {
+        Self {
+            inner,
+            max_size,
+            max_total: None,
+            total: Mutex::new(0),
+        }
+    }
+
+    pub fn with_total_limit(mut self, max_total: usize) -> Self {
+        self.max_total = Some(max_total);
+        self
+    }
+}
+
+impl<R: ResourceResolver> ResourceResolver for LimitedResolver<R> {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        self.resolve_limited(uri, self.max_size)
+    }
+
+    // The inner resolver stops reading at the limit, a large resource is never loaded whole
+    fn resolve_limited(&self, uri: &str, max_size: usize) -> anyhow::Result<Bytes> {
+        let bytes = self
+            .inner
+            .resolve_limited(uri, max_size.min(self.max_size))?;
+        if let Some(max_total) = self.max_total {
+            let mut total = self
+                .total
+                .lock()
+                .map_err(|_| anyhow::anyhow!("Resource limit lock poisoned"))?;
+            if *total + bytes.len() > max_total {
+                return Err(anyhow::anyhow!(
+                    "Resources exceed the total limit of {max_total} bytes"
+                ));
+            }
+            *total += bytes.len();
+        }
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_resolver() -> anyhow::Result<()> {
+        let resolver = FileResolver::new("test/data");
+        let bytes = resolver.resolve("./small.png")?;
+        assert_eq!(bytes, std::fs::read("test/data/small.png")?);
+        assert!(resolver.resolve("../Cargo.toml").is_err());
+        assert!(resolver.resolve("output/../../Cargo.toml").is_err());
+        assert!(resolver.resolve("/etc/passwd").is_err());
+        assert!(resolver.resolve("https://example.com/small.png").is_err());
+
+        let resolver = FileResolver::unconfined("src");
+        assert_eq!(resolver.resolve("../test/data/small.png")?, bytes);
+        Ok(())
+    }
+
+    #[cfg(feature = "base64")]
+    #[test]
+    fn test_memory_chain_and_limits() -> anyhow::Result<()> {
+        let mut memory = MemoryResolver::new();
+        memory.insert("images/logo.png", Bytes::from("logo"));
+
+        let resolver = LimitedResolver::new(
+            CachedResolver::new(ChainResolver::new().with(DataUriResolver).with(memory)),
+            16,
+        )
+        .with_total_limit(10);
+        assert_eq!(resolver.resolve("./images/logo.png")?, "logo");
+        assert_eq!(resolver.resolve("data:text/plain;base64,aGk=")?, "hi");
+        assert_eq!(resolver.resolve("data:,a%20b")?, "a b");
+        assert!(resolver.resolve("images/../logo.png").is_err());
+        assert!(resolver.resolve("data:,0123456789abcdefg").is_err());
+        // 4 + 2 + 3 bytes were loaded, a second logo goes over the total
+        assert!(resolver.resolve("images/logo.png").is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "zip")]
+    #[test]
+    fn test_zip_resolver() -> anyhow::Result<()> {
+        let docx = Bytes::from(std::fs::read("test/data/document.docx")?);
+        let resolver = ZipResolver::new(docx)?;
+        assert!(!resolver.resolve("word/document.xml")?.is_empty());
+        assert!(resolver.resolve("word/missing.xml").is_err());
+        assert!(resolver.resolve_limited("word/document.xml", 10).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_limited() -> anyhow::Result<()> {
+        let size = std::fs::metadata("test/data/small.png")?.len() as usize;
+        let resolver =
+            LimitedResolver::new(CachedResolver::new(FileResolver::new("test/data")), size);
+        assert_eq!(resolver.resolve("small.png")?.len(), size);
+        assert!(resolver.resolve_limited("small.png", size - 1).is_err());
+        assert!(
+            LimitedResolver::new(FileResolver::new("test/data"), size - 1)
+                .resolve("small.png")
+                .is_err()
+        );
+        Ok(())
+    }
+}
@@ -1,59 +1,43 @@
-use crate::core::{Document, Element, TableHeader, TableRow, TransformerTrait};
-use bytes::Bytes;
-use image::GenericImageView;
-use image::ImageReader;
+use crate::core::{
+    Document, Element, ImageData, ImageType, TableHeader, TableRow, TransformerTrait, IMAGE_AREA,
+};
 use log::warn;
-use std::io::Cursor;
 
 use rtf_parser::lexer::Lexer;
 use rtf_parser::parser::Parser;
 
 pub struct Transformer;
 
-struct ImageSize {
-    output_width: u32,
-    output_height: u32,
-}
-
-fn re_size_picture(image_bytes: &Bytes) -> ImageSize {
-    //setting the maximum image size
-    let max_width = 9700; // 16.5 cm
-    let max_height = 18000; // 29.7 cm
-
-    let size_img = ImageReader::new(Cursor::new(image_bytes))
-        .with_guessed_format()
-        .expect("the image format is not defined")
-        .decode()
-        .expect("fail decode image");
-    let (width, height) = size_img.dimensions();
-
-    //reassigning the dimensions taking into account the coefficients
-    let width = width * 15;
-    let height = height * 15;
-
-    //scale the image if it exceeds the page size
-    let mut new_width = width;
-    let mut new_height = height;
-
-    //scale the image if it exceeds the page size
-    if width > max_width {
-        let ratio = max_width as f32 / width as f32;
-        new_width = (width as f32 * ratio) as u32;
-        new_height = (height as f32 * ratio) as u32;
-    }
-    if new_height > max_height {
-        let ratio = max_height as f32 / new_height as f32;
-        new_width = (new_width as f32 * ratio) as u32;
-        new_height = (new_height as f32 * ratio) as u32;
-    }
-
-    let output_width = new_width;
-    let output_height = new_height;
-
-    ImageSize {
-        output_width,
-        output_height,
-    }
+/// Builds a `\pict` group. RTF readers only reliably embed PNG and JPEG, so
+/// other formats are converted to PNG first.
+fn picture(image: &ImageData) -> String {
+    let (image, blip) = match image.image_type() {
+        ImageType::Png => (image.clone(), "pngblip"),
+        ImageType::Jpeg => (image.clone(), "jpegblip"),
+        _ => match image.convert(ImageType::Png) {
+            Ok(converted) => (converted, "pngblip"),
+            Err(error) => {
+                warn!("Skipping image {}: {error}", image.title());
+                return String::new();
+            }
+        },
+    };
+    let hex = image
+        .bytes()
+        .iter()
+        .map(|b| format!("{b:02X}"))
+        .collect::<String>();
+
+    // The goal size is in twips, 20 per point
+    let goal = match image.display_size(IMAGE_AREA) {
+        Some((width, height)) => format!(
+            "\\picwgoal{}\\pichgoal{}",
+            (width * 20.0) as u32,
+            (height * 20.0) as u32
+        ),
+        None => String::new(),
+    };
+    format!("{{{{\\pict\\{blip}{goal} {hex} }}}}")
 }
 
 fn detect_element_in_list(
@@ -117,13 +101,6 @@ fn detect_element_in_list(
         }
 
         Element::Image(image) => {
-            let image_bytes = image.bytes();
-            let image_size = re_size_picture(image_bytes);
-            let image = image_bytes
-                .iter()
-                .map(|b| format!("{b:02X}"))
-                .collect::<String>();
-
             let indent = " ".repeat(depth * 4);
             let modified_image_caption = if numbered {
                 let numbering = parent_indices
@@ -138,10 +115,7 @@ fn detect_element_in_list(
 
             rtf_content.push_str(&format!("{{\\fs24 {modified_image_caption}}}\\par "));
 
-            rtf_content.push_str(&format!(
-                "{{{{\\pict\\jpegblip\\picwgoal{}\\pichgoal{} {image} }}}}",
-                image_size.output_width, image_size.output_height
-            ));
+            rtf_content.push_str(&picture(image));
             rtf_content.push_str("\\par ");
         }
 
@@ -206,6 +180,7 @@ impl TransformerTrait for Transformer {
     fn generate(document: &Document) -> anyhow::Result<bytes::Bytes> {
         let mut rtf_content = String::new();
         let mut parent_indices = Vec::new();
+        let mut figures = 0;
 
         rtf_content.push_str("{\\rtf1\\ansi\\deff0"); //the standard title of an RTF document, which indicates that it is an RTF document using ANSI characters and the default font
         for element in &document.get_all_elements() {
@@ -245,9 +220,16 @@ impl TransformerTrait for Transformer {
                                 *parent_indices.last_mut().unwrap() -= 1;
                             }
                         }
+                        let element = match (&list_item.element, list_item.checked) {
+                            (Element::Text { text, size }, Some(checked)) => Element::Text {
+                                text: format!("[{}] {text}", if checked { 'x' } else { ' ' }),
+                                size: *size,
+                            },
+                            (element, _) => element.clone(),
+                        };
                         detect_element_in_list(
                             &mut rtf_content,
-                            &list_item.element,
+                            &element,
                             *numbered,
                             &mut parent_indices,
                             0,
@@ -271,22 +253,13 @@ impl TransformerTrait for Transformer {
                 }
 
                 Element::Image(image) => {
-                    let image_bytes = image.bytes();
-
-                    let image_size = re_size_picture(image_bytes);
-
-                    let image = image_bytes
-                        .iter()
-                        .map(|b| format!("{b:02X}"))
-                        .collect::<String>();
-
-                    let image_width = image_size.output_width;
-                    let image_height = image_size.output_height;
-
-                    rtf_content.push_str(&format!(
-                        "{{{{\\pict\\jpegblip\\picwgoal{image_width}\\pichgoal{image_height} {image} }}}}"
-                    ));
+                    rtf_content.push_str(&picture(image));
                     rtf_content.push_str("\\par ");
+                    if !image.caption().is_empty() {
+                        figures += 1;
+                        rtf_content
+                            .push_str(&format!("{{\\i {}}}\\par ", image.figure_label(figures)));
+                    }
                 }
 
                 Element::Table { headers, rows } => {
@@ -325,6 +298,36 @@ impl TransformerTrait for Transformer {
                         rtf_content.push_str("\\row");
                     }
                 }
+
+                Element::DefinitionList { items } => {
+                    for item in items {
+                        rtf_content.push_str(&format!("{{\\b {}}}\\par ", item.term));
+                        for description in &item.descriptions {
+                            let text = match description {
+                                Element::Text { text, .. } => text.clone(),
+                                Element::Paragraph { elements } => elements
+                                    .iter()
+                                    .filter_map(|element| match element {
+                                        Element::Text { text, .. } => Some(text.as_str()),
+                                        _ => None,
+                                    })
+                                    .collect::<Vec<&str>>()
+                                    .join(" "),
+                                _ => continue,
+                            };
+                            rtf_content.push_str(&format!("{{\\li720 {text}}}\\par "));
+                        }
+                    }
+                }
+
+                Element::Math { latex, .. } => {
+                    // RTF has no equation markup, the LaTeX source is kept as text
+                    let latex = latex
+                        .replace('\\', "\\\\")
+                        .replace('{', "\\{")
+                        .replace('}', "\\}");
+                    rtf_content.push_str(&format!("{{\\f0 {latex}}}\\par "));
+                }
             }
         }
 
@@ -368,7 +371,6 @@ fn calculate_column_widths(headers: &[TableHeader], rows: &[TableRow]) -> Vec<i3
 }
 
 #[cfg(test)]
-
 mod tests {
     use crate::core::{disk_image_loader, TransformerWithImageLoaderSaverTrait};
     use crate::markdown;
@@ -1,227 +1,854 @@
 use crate::core::Element::{Image, Paragraph, Table};
 use crate::core::*;
 use bytes::Bytes;
-use log::debug;
-use std::collections::HashMap;
 
 pub struct Transformer;
+
+/// How the plain-text parser interprets its input.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ParseMode {
+    /// Groups lines into paragraphs and recognizes headings, lists, tables and URLs.
+    #[default]
+    Structured,
+    /// Keeps every line as a `Text` element followed by a line break, all inside one `Paragraph`.
+    Literal,
+}
+
+/// Options for parsing plain text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+}
+
+/// Horizontal alignment of wrapped paragraphs.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Alignment {
+    #[default]
+    Left,
+    /// Stretches every wrapped line except the last one of a paragraph to the wrap width.
+    /// Has no effect when wrapping is disabled.
+    Justify,
+}
+
+/// Characters used to draw table grids.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TableStyle {
+    /// `+`, `-`, `=` and `|`.
+    #[default]
+    Ascii,
+    /// Box-drawing characters such as `┌`, `─` and `│`.
+    Unicode,
+}
+
+/// How headers are set apart from the surrounding text.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HeaderStyle {
+    /// Underlined with `=` for level 1, `-` for level 2 and `~` below, as understood by the parser.
+    #[default]
+    Underline,
+    /// Prefixed with one `#` per level.
+    Hash,
+    /// Written as a plain line.
+    Plain,
+}
+
+/// How hyperlinks with a title different from their URL are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LinkStyle {
+    /// `title (url)`.
+    #[default]
+    Inline,
+    /// `title[1]`, with a numbered list of URLs at the end of the document.
+    References,
+}
+
+/// Options for generating plain text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerateOptions {
+    /// Maximum line length in characters. Words longer than the width are not broken.
+    /// `None`, the default, disables wrapping.
+    pub wrap_width: Option<usize>,
+    pub alignment: Alignment,
+    pub table_style: TableStyle,
+    /// Number of spaces added for every level of list nesting.
+    pub list_indent: usize,
+    pub header_style: HeaderStyle,
+    pub link_style: LinkStyle,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        GenerateOptions {
+            wrap_width: None,
+            alignment: Alignment::default(),
+            table_style: TableStyle::default(),
+            list_indent: 2,
+            header_style: HeaderStyle::default(),
+            link_style: LinkStyle::default(),
+        }
+    }
+}
+
 impl TransformerTrait for Transformer {
     fn parse(document: &Bytes) -> anyhow::Result<Document>
     where
         Self: Sized,
     {
-        let mut elements: Vec<Element> = vec![];
-        let document: &str = std::str::from_utf8(document.as_ref())?;
-        let lines = document.lines();
-        let lines_vec: Vec<&str> = lines.collect();
-        let mut i = 0;
-        while i < lines_vec.len() {
-            let line = lines_vec[i].to_string();
-            elements.push(Element::Text {
-                text: line,
-                size: 8,
-            });
-            elements.push(Element::Text {
-                text: "\n".to_string(),
-                size: 8,
-            });
-            i += 1;
-        }
-        let new_paragraph = Paragraph { elements };
-        Ok(Document::new(vec![new_paragraph]))
+        Transformer::parse_with_options(document, &ParseOptions::default())
     }
 
     fn generate(document: &Document) -> anyhow::Result<Bytes>
     where
         Self: Sized,
     {
-        let mut images: HashMap<String, Bytes> = HashMap::new();
-        let mut image_num: i32 = 0;
-
-        let mut markdown = String::new();
-        fn generate_element(
-            element: &Element,
-            markdown: &mut String,
-            list_depth: usize,
-            list_counters: &mut Vec<usize>,
-            list_types: &mut Vec<bool>,
-            images: &mut HashMap<String, Bytes>,
-            image_num: &mut i32,
-        ) -> anyhow::Result<()> {
-            fn generate_list_item(
-                element: &ListItem,
-                markdown: &mut String,
-                list_depth: usize,
-                list_counters: &mut Vec<usize>,
-                list_types: &mut Vec<bool>,
-                images: &mut HashMap<String, Bytes>,
-                image_num: &mut i32,
-            ) -> anyhow::Result<()> {
-                let prefix = if *list_types.last().unwrap() {
-                    let counter = list_counters.last_mut().unwrap();
-                    if let Element::Text { .. } = element.element {
-                        *counter += 1;
+        Transformer::generate_with_options(document, &GenerateOptions::default())
+    }
+}
+
+impl Transformer {
+    /// Parses plain text into a `Document`.
+    ///
+    /// In `ParseMode::Structured` blank lines separate paragraphs, a line underlined with
+    /// `=`, `-` or `~` becomes a header of level 1, 2 or 3, lines starting with `-`, `*`, `•`,
+    /// `1.` or `a)` become (nested by indentation) lists, ASCII and box-drawing tables become
+    /// `Element::Table` with the first row as headers, and URLs become hyperlinks.
+    pub fn parse_with_options(
+        document: &Bytes,
+        options: &ParseOptions,
+    ) -> anyhow::Result<Document> {
+        let document: &str = std::str::from_utf8(document.as_ref())?;
+        let elements = match options.mode {
+            ParseMode::Literal => parse_literal(document),
+            ParseMode::Structured => parse_structured(document),
+        };
+        Ok(Document::new(elements))
+    }
+
+    /// Writes the document as plain text laid out according to `options`.
+    /// Top-level elements are separated by a blank line.
+    pub fn generate_with_options(
+        document: &Document,
+        options: &GenerateOptions,
+    ) -> anyhow::Result<Bytes> {
+        let mut writer = TextWriter {
+            options,
+            links: vec![],
+        };
+        let mut blocks: Vec<Vec<String>> = vec![];
+        for band in &document.bands {
+            for element in &document.get_elements_by_band(band) {
+                let lines = writer.block(element, 0);
+                if !lines.is_empty() {
+                    blocks.push(lines);
+                }
+            }
+        }
+        if !writer.links.is_empty() {
+            blocks.push(
+                writer
+                    .links
+                    .iter()
+                    .enumerate()
+                    .map(|(i, url)| format!("[{}] {url}", i + 1))
+                    .collect(),
+            );
+        }
+
+        let mut text = blocks
+            .iter()
+            .map(|lines| lines.join("\n"))
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        if !text.is_empty() {
+            text.push('\n');
+        }
+        Ok(Bytes::from(text))
+    }
+}
+
+fn parse_literal(document: &str) -> Vec<Element> {
+    let mut elements: Vec<Element> = vec![];
+    for line in document.lines() {
+        elements.push(Element::Text {
+            text: line.to_string(),
+            size: 8,
+        });
+        elements.push(Element::Text {
+            text: "\n".to_string(),
+            size: 8,
+        });
+    }
+    vec![Paragraph { elements }]
+}
+
+fn parse_structured(document: &str) -> Vec<Element> {
+    let lines: Vec<&str> = document.lines().map(str::trim_end).collect();
+    let mut elements: Vec<Element> = vec![];
+    let mut paragraph: Vec<&str> = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if line.trim().is_empty() {
+            flush_paragraph(&mut paragraph, &mut elements);
+            i += 1;
+        } else if is_table_line(line) {
+            flush_paragraph(&mut paragraph, &mut elements);
+            let start = i;
+            while i < lines.len() && is_table_line(lines[i]) {
+                i += 1;
+            }
+            if let Some(table) = parse_table(&lines[start..i]) {
+                elements.push(table);
+            }
+        } else if underline_level(line).is_some() {
+            // a rule that does not underline anything only separates blocks
+            flush_paragraph(&mut paragraph, &mut elements);
+            i += 1;
+        } else if let Some(level) = lines.get(i + 1).and_then(|next| underline_level(next)) {
+            flush_paragraph(&mut paragraph, &mut elements);
+            elements.push(Element::Header {
+                level,
+                text: line.trim().to_string(),
+            });
+            i += 2;
+        } else if let Some(first) = list_line(line) {
+            flush_paragraph(&mut paragraph, &mut elements);
+            let start = i;
+            i += 1;
+            while i < lines.len() && !lines[i].trim().is_empty() && !is_table_line(lines[i]) {
+                match list_line(lines[i]) {
+                    // a different marker at the top level starts a new list
+                    Some(item)
+                        if item.indent <= first.indent && item.numbered != first.numbered =>
+                    {
+                        break
                     }
-                    format!("{counter}. ")
-                } else {
-                    "- ".to_string()
-                };
-                debug!("list depth: {list_depth}");
-                markdown.push_str(&"  ".repeat(list_depth - 1));
-                if let Element::Text { .. } = element.element {
-                    markdown.push_str(&prefix);
+                    Some(_) => i += 1,
+                    // an indented line that is not a list item continues the previous item
+                    None if lines[i].starts_with(char::is_whitespace) => i += 1,
+                    None => break,
                 }
-                generate_element(
-                    &element.element,
-                    markdown,
-                    list_depth,
-                    list_counters,
-                    list_types,
-                    images,
-                    image_num,
-                )?;
-                if let Element::Text { .. } = element.element {
-                    markdown.push('\n');
+            }
+            elements.push(parse_list(&lines[start..i]));
+        } else {
+            paragraph.push(line.trim());
+            i += 1;
+        }
+    }
+    flush_paragraph(&mut paragraph, &mut elements);
+    elements
+}
+
+fn flush_paragraph(paragraph: &mut Vec<&str>, elements: &mut Vec<Element>) {
+    if paragraph.is_empty() {
+        return;
+    }
+    elements.push(Paragraph {
+        elements: inline_elements(&paragraph.join(" ")),
+    });
+    paragraph.clear();
+}
+
+/// Returns the header level for a line made only of `=`, `-` or `~` characters.
+fn underline_level(line: &str) -> Option<u8> {
+    let line = line.trim();
+    if line.chars().count() < 3 {
+        return None;
+    }
+    [('=', 1), ('-', 2), ('~', 3)]
+        .into_iter()
+        .find(|(c, _)| line.chars().all(|ch| ch == *c))
+        .map(|(_, level)| level)
+}
+
+struct ListLine {
+    indent: usize,
+    numbered: bool,
+    /// Set when the marker is followed by a `[ ]` or `[x]` checkbox.
+    checked: Option<bool>,
+    text: String,
+}
+
+fn list_line(line: &str) -> Option<ListLine> {
+    let content = line.trim_start();
+    let indent = line[..line.len() - content.len()]
+        .chars()
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum();
+    let (marker, text) = content.split_once(char::is_whitespace)?;
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let numbered = match marker {
+        "-" | "*" | "•" => false,
+        _ => {
+            let label = marker
+                .strip_suffix('.')
+                .or_else(|| marker.strip_suffix(')'))?;
+            let is_number =
+                !label.is_empty() && label.len() <= 9 && label.chars().all(|c| c.is_ascii_digit());
+            let is_letter = label.len() == 1 && label.chars().all(|c| c.is_ascii_lowercase());
+            if !is_number && !is_letter {
+                return None;
+            }
+            true
+        }
+    };
+    let (checked, text) = match text.split_at_checked(4) {
+        Some(("[ ] ", rest)) => (Some(false), rest.trim_start()),
+        Some(("[x] " | "[X] ", rest)) => (Some(true), rest.trim_start()),
+        _ => (None, text),
+    };
+    Some(ListLine {
+        indent,
+        numbered,
+        checked,
+        text: text.to_string(),
+    })
+}
+
+fn parse_list(lines: &[&str]) -> Element {
+    let mut items: Vec<ListLine> = vec![];
+    for line in lines {
+        match list_line(line) {
+            Some(item) => items.push(item),
+            None => {
+                if let Some(item) = items.last_mut() {
+                    item.text.push(' ');
+                    item.text.push_str(line.trim());
                 }
-                Ok(())
             }
+        }
+    }
+    build_list(&items)
+}
+
+/// Items indented deeper than the first item form a nested list,
+/// stored as a `ListItem` holding an `Element::List`.
+fn build_list(items: &[ListLine]) -> Element {
+    let (indent, numbered) = (items[0].indent, items[0].numbered);
+    let mut elements: Vec<ListItem> = vec![];
+    let mut i = 0;
+    while i < items.len() {
+        if items[i].indent > indent {
+            let start = i;
+            while i < items.len() && items[i].indent > indent {
+                i += 1;
+            }
+            elements.push(ListItem {
+                element: build_list(&items[start..i]),
+                checked: None,
+            });
+        } else {
+            let text = &items[i].text;
+            let element = match find_url(text) {
+                Some((0, end)) if end == text.len() => hyperlink(text),
+                _ => Element::Text {
+                    text: text.clone(),
+                    size: 8,
+                },
+            };
+            elements.push(ListItem {
+                element,
+                checked: items[i].checked,
+            });
+            i += 1;
+        }
+    }
+    Element::List { elements, numbered }
+}
+
+const VERTICAL_BARS: [char; 4] = ['|', '│', '┃', '║'];
+
+fn is_box_drawing(c: char) -> bool {
+    ('\u{2500}'..='\u{257F}').contains(&c)
+}
+
+fn is_table_border(line: &str) -> bool {
+    let line = line.trim();
+    line.chars()
+        .any(|c| matches!(c, '-' | '=' | '─' | '━' | '═'))
+        && line
+            .chars()
+            .all(|c| matches!(c, '+' | '-' | '=' | ':' | '|' | ' ') || is_box_drawing(c))
+}
+
+fn is_table_line(line: &str) -> bool {
+    let line = line.trim();
+    let Some(first) = line.chars().next() else {
+        return false;
+    };
+    let last = line.chars().last().unwrap_or(first);
+    if (first == '+' || first == '|' || is_box_drawing(first)) && is_table_border(line) {
+        return true;
+    }
+    line.chars().count() > 1
+        && VERTICAL_BARS.contains(&first)
+        && VERTICAL_BARS.contains(&last)
+        && line.chars().any(|c| !VERTICAL_BARS.contains(&c))
+}
+
+fn parse_table(lines: &[&str]) -> Option<Element> {
+    let mut rows = lines
+        .iter()
+        .filter(|line| !is_table_border(line))
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_prefix(VERTICAL_BARS).unwrap_or(line);
+            let line = line.strip_suffix(VERTICAL_BARS).unwrap_or(line);
+            line.split(VERTICAL_BARS).collect::<Vec<&str>>()
+        });
+
+    let headers: Vec<TableHeader> = rows
+        .next()?
+        .into_iter()
+        .map(|cell| TableHeader {
+            element: Element::Text {
+                text: cell.trim().to_string(),
+                size: 8,
+            },
+            // the width of the value, without the space on each side of it
+            width: cell.chars().count().saturating_sub(2) as f32,
+        })
+        .collect();
+    let rows = rows
+        .map(|cells| TableRow {
+            // every row has as many cells as there are headers
+            cells: cells
+                .into_iter()
+                .chain(std::iter::repeat(""))
+                .take(headers.len())
+                .map(|cell| TableCell {
+                    element: Element::Text {
+                        text: cell.trim().to_string(),
+                        size: 8,
+                    },
+                })
+                .collect(),
+        })
+        .collect();
+    Some(Table { headers, rows })
+}
 
-            match element {
-                Element::Header { level: _, text } => {
-                    markdown.push_str(text);
-                    markdown.push('\n');
-                    markdown.push('\n');
+/// Finds the first URL starting at a word boundary and returns its byte range.
+/// Trailing punctuation is not considered part of the URL, nor is a closing parenthesis
+/// without an opening one inside the URL.
+fn find_url(text: &str) -> Option<(usize, usize)> {
+    ["https://", "http://", "www."]
+        .into_iter()
+        .flat_map(|prefix| {
+            text.match_indices(prefix).filter_map(move |(start, _)| {
+                let boundary = text[..start]
+                    .chars()
+                    .next_back()
+                    .is_none_or(|c| c.is_whitespace() || matches!(c, '(' | '<' | '"' | '\''));
+                if !boundary {
+                    return None;
                 }
-                Element::Paragraph { elements } => {
-                    for child in elements {
-                        generate_element(
-                            child,
-                            markdown,
-                            list_depth,
-                            list_counters,
-                            list_types,
-                            images,
-                            image_num,
-                        )?;
+                let end = text[start..]
+                    .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"'))
+                    .map_or(text.len(), |end| start + end);
+                let mut url = &text[start..end];
+                while let Some(last) = url.chars().next_back() {
+                    let unmatched_paren =
+                        last == ')' && url.matches('(').count() < url.matches(')').count();
+                    if !unmatched_paren && !matches!(last, '.' | ',' | ';' | ':' | '!' | '?' | '\'')
+                    {
+                        break;
                     }
-                    markdown.push('\n');
-                    markdown.push('\n');
+                    url = &url[..url.len() - last.len_utf8()];
                 }
-                Element::List { elements, numbered } => {
-                    list_counters.push(0);
-                    list_types.push(*numbered);
-                    for item in elements {
-                        generate_list_item(
-                            item,
-                            markdown,
-                            list_depth + 1,
-                            list_counters,
-                            list_types,
-                            images,
-                            image_num,
-                        )?;
-                    }
-                    list_counters.pop();
-                    list_types.pop();
+                (url.len() > prefix.len()).then_some((start, start + url.len()))
+            })
+        })
+        .min()
+}
 
-                    if list_counters.is_empty() {
-                        markdown.push('\n');
-                    }
+fn hyperlink(text: &str) -> Element {
+    let url = if text.starts_with("www.") {
+        format!("http://{text}")
+    } else {
+        text.to_string()
+    };
+    Element::Hyperlink {
+        title: text.to_string(),
+        alt: url.clone(),
+        url,
+        size: 8,
+    }
+}
+
+fn inline_elements(text: &str) -> Vec<Element> {
+    let mut elements = vec![];
+    let mut rest = text;
+    while let Some((start, end)) = find_url(rest) {
+        // angle brackets around the whole URL only delimit it
+        let bracketed = rest[..start].ends_with('<') && rest[end..].starts_with('>');
+        let before = if bracketed { start - 1 } else { start };
+        if before > 0 {
+            elements.push(Element::Text {
+                text: rest[..before].to_string(),
+                size: 8,
+            });
+        }
+        elements.push(hyperlink(&rest[start..end]));
+        rest = &rest[if bracketed { end + 1 } else { end }..];
+    }
+    if !rest.is_empty() {
+        elements.push(Element::Text {
+            text: rest.to_string(),
+            size: 8,
+        });
+    }
+    elements
+}
+
+struct TextWriter<'a> {
+    options: &'a GenerateOptions,
+    /// URLs collected for `LinkStyle::References`, in order of first appearance.
+    links: Vec<String>,
+}
+
+impl TextWriter<'_> {
+    fn width(&self, indent: usize) -> Option<usize> {
+        self.options
+            .wrap_width
+            .map(|width| width.saturating_sub(indent).max(1))
+    }
+
+    fn block(&mut self, element: &Element, indent: usize) -> Vec<String> {
+        match element {
+            Element::Header { level, text } => {
+                let prefix = match self.options.header_style {
+                    HeaderStyle::Hash => format!("{} ", "#".repeat((*level).max(1) as usize)),
+                    _ => String::new(),
+                };
+                let mut lines = wrap(
+                    &format!("{prefix}{text}"),
+                    self.width(indent),
+                    Alignment::Left,
+                );
+                if self.options.header_style == HeaderStyle::Underline {
+                    let underline = match level {
+                        0 | 1 => '=',
+                        2 => '-',
+                        _ => '~',
+                    };
+                    let length = lines
+                        .iter()
+                        .map(|line| line.chars().count())
+                        .max()
+                        .unwrap_or(0)
+                        .max(3);
+                    lines.push(underline.to_string().repeat(length));
                 }
-                Element::Text { text, size: _ } => {
-                    markdown.push_str(text);
-                    if !text.ends_with(' ') {
-                        markdown.push(' ');
+                lines
+            }
+            Element::List { elements, numbered } => self.list(elements, *numbered, indent),
+            Table { headers, rows } => self.table(headers, rows),
+            Element::DefinitionList { items } => {
+                let mut lines = vec![];
+                for item in items {
+                    lines.extend(wrap(&item.term, self.width(indent), Alignment::Left));
+                    for description in &item.descriptions {
+                        let text = self.inline(description);
+                        let description_lines =
+                            wrap(&text, self.width(indent + 4), self.options.alignment);
+                        lines.extend(description_lines.iter().map(|line| format!("    {line}")));
                     }
                 }
-                Element::Hyperlink {
-                    title, url, alt, ..
-                } => {
-                    if url == alt {
-                        markdown.push_str(&url.to_string());
-                    } else {
-                        markdown.push_str(&format!("[{title}]({url} \"{alt}\")"));
+                lines
+            }
+            Element::Math {
+                latex,
+                display: true,
+            } => latex.lines().map(|line| line.trim().to_string()).collect(),
+            _ => {
+                let text = self.inline(element);
+                wrap(&text, self.width(indent), self.options.alignment)
+            }
+        }
+    }
+
+    fn inline(&mut self, element: &Element) -> String {
+        match element {
+            Element::Text { text, .. } => text.clone(),
+            Element::Header { text, .. } => text.clone(),
+            Paragraph { elements } => {
+                let mut text = String::new();
+                for child in elements {
+                    let child = self.inline(child);
+                    if !text.is_empty()
+                        && !text.ends_with(char::is_whitespace)
+                        && !child.starts_with(char::is_whitespace)
+                    {
+                        text.push(' ');
                     }
+                    text.push_str(&child);
                 }
-                Image(image) => {
-                    let image_path = format!("image{image_num}.png");
-                    markdown.push_str(&format!("![{}]", image.alt()));
-                    images.insert(image_path.to_string(), image.bytes().clone());
-                    *image_num += 1;
+                text
+            }
+            Element::Hyperlink { title, url, .. } => {
+                if title.is_empty() || title == url {
+                    return url.clone();
                 }
-                Table { headers, rows } => {
-                    let mut max_lengths: Vec<usize> = Vec::new();
-
-                    for header in headers {
-                        if let Element::Text { text, size: _ } = header.element.clone() {
-                            max_lengths.push(text.len());
-                        }
-                    }
-                    for row in rows {
-                        for (cell_index, cell) in row.cells.iter().enumerate() {
-                            if let Element::Text { text, size: _ } = cell.element.clone() {
-                                if cell_index < max_lengths.len() {
-                                    max_lengths[cell_index] =
-                                        max_lengths[cell_index].max(text.len());
-                                }
+                match self.options.link_style {
+                    LinkStyle::Inline => format!("{title} ({url})"),
+                    LinkStyle::References => {
+                        let number = match self.links.iter().position(|link| link == url) {
+                            Some(index) => index + 1,
+                            None => {
+                                self.links.push(url.clone());
+                                self.links.len()
                             }
-                        }
+                        };
+                        format!("{title}[{number}]")
                     }
+                }
+            }
+            Image(image) if image.alt().is_empty() => "[Image]".to_string(),
+            Image(image) => format!("[Image: {}]", image.alt()),
+            Element::List { elements, .. } => elements
+                .iter()
+                .map(|item| self.inline(&item.element))
+                .collect::<Vec<String>>()
+                .join(" "),
+            Table { .. } => String::new(),
+            Element::DefinitionList { items } => items
+                .iter()
+                .map(|item| {
+                    let descriptions = item
+                        .descriptions
+                        .iter()
+                        .map(|description| self.inline(description))
+                        .collect::<Vec<String>>()
+                        .join(" ");
+                    format!("{}: {descriptions}", item.term)
+                })
+                .collect::<Vec<String>>()
+                .join(" "),
+            Element::Math { latex, .. } => latex.clone(),
+        }
+    }
 
-                    for (index, header) in headers.iter().enumerate() {
-                        if let Element::Text { text, size: _ } = header.element.clone() {
-                            let padding = max_lengths[index] - text.len();
-                            markdown.push_str("| ");
-                            markdown.push_str(text.as_str());
-                            markdown.push_str(&" ".repeat(padding));
-                            markdown.push(' ');
-                        }
-                    }
-                    markdown.push_str("|\n");
+    /// Nested lists are indented by `GenerateOptions::list_indent` and wrapped
+    /// lines of an item are aligned with the text after its marker.
+    fn list(&mut self, elements: &[ListItem], numbered: bool, indent: usize) -> Vec<String> {
+        let mut lines = vec![];
+        let mut counter = 0;
+        for item in elements {
+            if let Element::List { elements, numbered } = &item.element {
+                let nested = self.list(elements, *numbered, indent + self.options.list_indent);
+                lines.extend(nested);
+                continue;
+            }
+            let mut marker = if numbered {
+                counter += 1;
+                format!("{counter}. ")
+            } else {
+                "- ".to_string()
+            };
+            match item.checked {
+                Some(true) => marker.push_str("[x] "),
+                Some(false) => marker.push_str("[ ] "),
+                None => {}
+            }
+            let marker_width = marker.chars().count();
+            let text = self.inline(&item.element);
+            let item_lines = wrap(
+                &text,
+                self.width(indent + marker_width),
+                self.options.alignment,
+            );
+            for (i, line) in item_lines.iter().enumerate() {
+                let prefix = if i == 0 {
+                    marker.clone()
+                } else {
+                    " ".repeat(marker_width)
+                };
+                lines.push(format!("{}{prefix}{line}", " ".repeat(indent)));
+            }
+        }
+        lines
+    }
 
-                    for max_length in &max_lengths {
-                        markdown.push('|');
-                        markdown.push_str(&"-".repeat(*max_length + 2));
-                    }
-                    markdown.push_str("|\n");
-
-                    for row in rows {
-                        for (cell_index, cell) in row.cells.iter().enumerate() {
-                            if let Element::Text { text, size: _ } = cell.element.clone() {
-                                let padding = max_lengths[cell_index] - text.len();
-                                markdown.push_str("| ");
-                                markdown.push_str(text.as_str());
-                                markdown.push_str(&" ".repeat(padding));
-                                markdown.push(' ');
-                            }
-                        }
-                        markdown.push_str("|\n");
-                    }
-                    markdown.push('\n');
-                }
+    /// Columns are as wide as their longest value and at least `TableHeader.width`.
+    /// When the grid does not fit in the wrap width, the widest columns are narrowed
+    /// and their values wrapped, but never below that width or their longest word.
+    fn table(&mut self, headers: &[TableHeader], rows: &[TableRow]) -> Vec<String> {
+        let header_texts: Vec<String> = headers.iter().map(|h| self.inline(&h.element)).collect();
+        let rows: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| row.cells.iter().map(|c| self.inline(&c.element)).collect())
+            .collect();
+        let columns = rows
+            .iter()
+            .map(Vec::len)
+            .chain(std::iter::once(header_texts.len()))
+            .max()
+            .unwrap_or(0);
+        if columns == 0 {
+            return vec![];
+        }
+
+        let mut min_widths: Vec<usize> = (0..columns)
+            .map(|i| {
+                headers
+                    .get(i)
+                    .map_or(1, |h| (h.width.max(0.0).round() as usize).max(1))
+            })
+            .collect();
+        let mut widths = min_widths.clone();
+        for line in std::iter::once(&header_texts).chain(rows.iter()) {
+            for (i, value) in line.iter().enumerate() {
+                widths[i] = widths[i].max(value.chars().count());
+                let longest_word = value
+                    .split_whitespace()
+                    .map(|word| word.chars().count())
+                    .max()
+                    .unwrap_or(0);
+                min_widths[i] = min_widths[i].max(longest_word);
+            }
+        }
+        if let Some(wrap_width) = self.options.wrap_width {
+            // every column takes 3 extra characters for its padding and left border
+            let available = wrap_width.saturating_sub(3 * columns + 1);
+            while widths.iter().sum::<usize>() > available {
+                let Some(widest) = (0..columns)
+                    .filter(|&i| widths[i] > min_widths[i])
+                    .max_by_key(|&i| widths[i])
+                else {
+                    break;
+                };
+                widths[widest] -= 1;
             }
-            Ok(())
         }
 
-        let mut list_counters: Vec<usize> = Vec::new();
-        let mut list_types: Vec<bool> = Vec::new();
+        let (vertical, top, separator, bottom) = match self.options.table_style {
+            TableStyle::Ascii => (
+                '|',
+                ['+', '-', '+', '+'],
+                ['+', '=', '+', '+'],
+                ['+', '-', '+', '+'],
+            ),
+            TableStyle::Unicode => (
+                '│',
+                ['┌', '─', '┬', '┐'],
+                ['╞', '═', '╪', '╡'],
+                ['└', '─', '┴', '┘'],
+            ),
+        };
 
-        for band in &document.bands {
-            for element in &document.get_elements_by_band(band) {
-                generate_element(
-                    element,
-                    &mut markdown,
-                    0,
-                    &mut list_counters,
-                    &mut list_types,
-                    &mut images,
-                    &mut image_num,
-                )?;
+        let mut lines = vec![rule(&widths, top)];
+        lines.extend(table_row(&header_texts, &widths, vertical));
+        lines.push(rule(&widths, separator));
+        for row in &rows {
+            lines.extend(table_row(row, &widths, vertical));
+        }
+        lines.push(rule(&widths, bottom));
+        lines
+    }
+}
+
+/// Draws a horizontal grid line from its left, fill, junction and right characters.
+fn rule(widths: &[usize], [left, fill, junction, right]: [char; 4]) -> String {
+    let mut line = String::from(left);
+    for (i, width) in widths.iter().enumerate() {
+        if i > 0 {
+            line.push(junction);
+        }
+        line.push_str(&fill.to_string().repeat(width + 2));
+    }
+    line.push(right);
+    line
+}
+
+fn table_row(values: &[String], widths: &[usize], vertical: char) -> Vec<String> {
+    let cells: Vec<Vec<String>> = widths
+        .iter()
+        .enumerate()
+        .map(|(i, width)| {
+            let value = values.get(i).map(String::as_str).unwrap_or_default();
+            wrap(value, Some(*width), Alignment::Left)
+        })
+        .collect();
+    let height = cells.iter().map(Vec::len).max().unwrap_or(0).max(1);
+    (0..height)
+        .map(|line_index| {
+            let mut line = String::from(vertical);
+            for (cell, width) in cells.iter().zip(widths) {
+                let value = cell.get(line_index).map(String::as_str).unwrap_or_default();
+                line.push(' ');
+                line.push_str(value);
+                line.push_str(&" ".repeat(width.saturating_sub(value.chars().count()) + 1));
+                line.push(vertical);
+            }
+            line
+        })
+        .collect()
+}
+
+/// Splits text into lines of at most `width` characters on word boundaries.
+/// Line breaks in the text are kept, other whitespace is collapsed.
+fn wrap(text: &str, width: Option<usize>, alignment: Alignment) -> Vec<String> {
+    let mut lines = vec![];
+    for hard_line in text.split('\n') {
+        let words: Vec<&str> = hard_line.split_whitespace().collect();
+        if words.is_empty() {
+            continue;
+        }
+        let Some(width) = width else {
+            lines.push(words.join(" "));
+            continue;
+        };
+
+        let mut wrapped: Vec<Vec<&str>> = vec![];
+        let mut current: Vec<&str> = vec![];
+        let mut length = 0;
+        for word in words {
+            let word_length = word.chars().count();
+            if !current.is_empty() && length + 1 + word_length > width {
+                wrapped.push(std::mem::take(&mut current));
+                length = 0;
+            }
+            length += if current.is_empty() {
+                word_length
+            } else {
+                word_length + 1
+            };
+            current.push(word);
+        }
+        wrapped.push(current);
+
+        let last = wrapped.len() - 1;
+        for (i, words) in wrapped.iter().enumerate() {
+            if alignment == Alignment::Justify && i < last {
+                lines.push(justify(words, width));
+            } else {
+                lines.push(words.join(" "));
             }
         }
+    }
+    lines
+}
 
-        Ok(Bytes::from(markdown))
+fn justify(words: &[&str], width: usize) -> String {
+    if words.len() < 2 {
+        return words.join(" ");
     }
+    let gaps = words.len() - 1;
+    let letters: usize = words.iter().map(|word| word.chars().count()).sum();
+    let spaces = width.saturating_sub(letters).max(gaps);
+    let mut line = String::new();
+    for (i, word) in words.iter().enumerate() {
+        line.push_str(word);
+        if i < gaps {
+            let count = spaces / gaps + usize::from(i < spaces % gaps);
+            line.push_str(&" ".repeat(count));
+        }
+    }
+    line
 }
 
 #[cfg(test)]
@@ -284,4 +911,291 @@ Second header
         info!("{}", generated_text);
         Ok(())
     }
+
+    #[test]
+    fn test_parse_structured() -> anyhow::Result<()> {
+        init_logger();
+        let document = r#"Title
+=====
+
+First line of a paragraph
+that continues here, see https://example.com.
+
+- apple
+- banana
+  * yellow
+  * green
+a) first
+b) second
+
+┌──────┬───────┐
+│ Name │ Value │
+├──────┼───────┤
+│ a    │ 1     │
+└──────┴───────┘
+"#;
+        let parsed = Transformer::parse(&Bytes::from(document))?;
+        let elements = parsed.get_all_elements();
+        debug!("{:?}", elements);
+        assert_eq!(
+            *elements[0],
+            Header {
+                level: 1,
+                text: "Title".to_string()
+            }
+        );
+        assert_eq!(
+            *elements[1],
+            Element::Paragraph {
+                elements: vec![
+                    Element::Text {
+                        text: "First line of a paragraph that continues here, see ".to_string(),
+                        size: 8,
+                    },
+                    Element::Hyperlink {
+                        title: "https://example.com".to_string(),
+                        url: "https://example.com".to_string(),
+                        alt: "https://example.com".to_string(),
+                        size: 8,
+                    },
+                    Element::Text {
+                        text: ".".to_string(),
+                        size: 8,
+                    },
+                ]
+            }
+        );
+
+        let Element::List {
+            elements: items,
+            numbered,
+        } = elements[2]
+        else {
+            panic!("Expected a list");
+        };
+        assert!(!numbered);
+        assert_eq!(items.len(), 3);
+        assert!(matches!(
+            &items[2].element,
+            Element::List { elements, numbered: false } if elements.len() == 2
+        ));
+        assert!(matches!(
+            elements[3],
+            Element::List { elements, numbered: true } if elements.len() == 2
+        ));
+
+        let Element::Table { headers, rows } = elements[4] else {
+            panic!("Expected a table");
+        };
+        assert_eq!(headers.len(), 2);
+        assert_eq!(
+            rows[0].cells[1].element,
+            Element::Text {
+                text: "1".to_string(),
+                size: 8
+            }
+        );
+
+        // short rows are padded to the headers, a line of bars alone is not a table
+        let parsed = Transformer::parse(&Bytes::from("| a | b |\n| 1 |\n\n||\n"))?;
+        let elements = parsed.get_all_elements();
+        let Element::Table { rows, .. } = elements[0] else {
+            panic!("Expected a table");
+        };
+        assert_eq!(rows[0].cells.len(), 2);
+        assert!(matches!(elements[1], Element::Paragraph { .. }));
+
+        // parentheses inside a URL are kept, angle brackets around it are dropped
+        let parsed = Transformer::parse(&Bytes::from(
+            "See https://en.wikipedia.org/wiki/Rust_(programming_language) (or https://x.y/(a)), \
+             <http://q>.",
+        ))?;
+        let Element::Paragraph { elements } = parsed.get_all_elements()[0] else {
+            panic!("Expected a paragraph");
+        };
+        let texts: Vec<&str> = elements
+            .iter()
+            .map(|element| match element {
+                Element::Text { text, .. } => text.as_str(),
+                Element::Hyperlink { url, .. } => url.as_str(),
+                _ => panic!("Unexpected {element:?}"),
+            })
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "See ",
+                "https://en.wikipedia.org/wiki/Rust_(programming_language)",
+                " (or ",
+                "https://x.y/(a)",
+                "), ",
+                "http://q",
+                "."
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_literal() -> anyhow::Result<()> {
+        init_logger();
+        let options = ParseOptions {
+            mode: ParseMode::Literal,
+        };
+        let parsed =
+            Transformer::parse_with_options(&Bytes::from("Title\n=====\n- item"), &options)?;
+        let Element::Paragraph { elements } = parsed.get_all_elements()[0] else {
+            panic!("Expected a paragraph");
+        };
+        assert_eq!(elements.len(), 6);
+        assert_eq!(
+            elements[2],
+            Element::Text {
+                text: "=====".to_string(),
+                size: 8
+            }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_layout() -> anyhow::Result<()> {
+        init_logger();
+        let document = r#"Title
+=====
+
+Read the manual at https://example.com before you start.
+
+- apple
+  * yellow
+- banana
+
++------+-------+
+| Name | Value |
++------+-------+
+| a    | 1     |
++------+-------+
+"#;
+        let parsed = Transformer::parse(&Bytes::from(document))?;
+        let generated = Transformer::generate(&parsed)?;
+        let generated_text = std::str::from_utf8(&generated)?;
+        info!("{}", generated_text);
+        assert_eq!(
+            generated_text,
+            r#"Title
+=====
+
+Read the manual at https://example.com before you start.
+
+- apple
+  - yellow
+- banana
+
++------+-------+
+| Name | Value |
++======+=======+
+| a    | 1     |
++------+-------+
+"#
+        );
+        assert_eq!(Transformer::parse(&generated)?, parsed);
+
+        // The header width is the minimum width of the column
+        let table = Element::Table {
+            headers: vec![TableHeader {
+                element: Element::Text {
+                    text: "Id".to_string(),
+                    size: 8,
+                },
+                width: 6.0,
+            }],
+            rows: vec![TableRow {
+                cells: vec![TableCell {
+                    element: Element::Text {
+                        text: "7".to_string(),
+                        size: 8,
+                    },
+                }],
+            }],
+        };
+        let generated = Transformer::generate(&Document::new(vec![table]))?;
+        assert_eq!(
+            std::str::from_utf8(&generated)?,
+            "+--------+\n| Id     |\n+========+\n| 7      |\n+--------+\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_generate_with_options() -> anyhow::Result<()> {
+        init_logger();
+        let document = Document::new(vec![
+            Header {
+                level: 2,
+                text: "Notes".to_string(),
+            },
+            Element::Paragraph {
+                elements: vec![
+                    Element::Text {
+                        text: "The quick brown fox jumps over the lazy dog, see".to_string(),
+                        size: 8,
+                    },
+                    Element::Hyperlink {
+                        title: "the source".to_string(),
+                        url: "https://example.com".to_string(),
+                        alt: "".to_string(),
+                        size: 8,
+                    },
+                ],
+            },
+            Element::Table {
+                headers: vec![TableHeader {
+                    element: Element::Text {
+                        text: "Description".to_string(),
+                        size: 8,
+                    },
+                    width: 10.0,
+                }],
+                rows: vec![TableRow {
+                    cells: vec![TableCell {
+                        element: Element::Text {
+                            text: "a rather long value".to_string(),
+                            size: 8,
+                        },
+                    }],
+                }],
+            },
+        ]);
+        let options = GenerateOptions {
+            wrap_width: Some(20),
+            alignment: Alignment::Justify,
+            table_style: TableStyle::Unicode,
+            header_style: HeaderStyle::Hash,
+            link_style: LinkStyle::References,
+            ..Default::default()
+        };
+        let generated = Transformer::generate_with_options(&document, &options)?;
+        let generated_text = std::str::from_utf8(&generated)?;
+        info!("{}", generated_text);
+        assert_eq!(
+            generated_text,
+            r#"## Notes
+
+The  quick brown fox
+jumps  over the lazy
+dog,     see     the
+source[1]
+
+┌──────────────────┐
+│ Description      │
+╞══════════════════╡
+│ a rather long    │
+│ value            │
+└──────────────────┘
+
+[1] https://example.com
+"#
+        );
+        Ok(())
+    }
 }
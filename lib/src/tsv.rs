@@ -0,0 +1,74 @@
+use crate::core::{Document, TransformerTrait};
+use crate::csv::{generate_delimited, parse_delimited};
+use bytes::Bytes;
+
+pub struct Transformer;
+
+impl TransformerTrait for Transformer {
+    /// Parses tab-separated data into a `Document` holding a single `Element::Table`.
+    /// The first line is used as the table headers. Quotes have no special meaning in TSV.
+    fn parse(document: &Bytes) -> anyhow::Result<Document> {
+        parse_delimited(document, b'\t')
+    }
+
+    /// Writes every `Element::Table` of the document as tab-separated lines.
+    /// Tabs and line breaks inside cells are replaced with spaces.
+    fn generate(document: &Document) -> anyhow::Result<Bytes> {
+        generate_delimited(document, b'\t')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::tests::init_logger;
+    use crate::core::*;
+    use crate::tsv::*;
+
+    #[test]
+    fn test() -> anyhow::Result<()> {
+        init_logger();
+        let document = "StudentID\tName\tMath\n1\tJohn \"JD\" Doe\t88\n2\tJane, Smith\t\n";
+        let parsed = Transformer::parse(&Bytes::from(document))?;
+
+        let Element::Table { headers, rows } = &parsed.get_all_elements()[0] else {
+            panic!("Expected a table");
+        };
+        assert_eq!(headers.len(), 3);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0].cells[1].element,
+            Element::Text {
+                text: "John \"JD\" Doe".to_string(),
+                size: 8,
+            }
+        );
+
+        let generated = Transformer::generate(&parsed)?;
+        assert_eq!(std::str::from_utf8(&generated)?, document);
+
+        // A dump that leaves off trailing empty fields
+        let parsed = Transformer::parse(&Bytes::from("a\tb\tc\n1\t2\n"))?;
+        let Element::Table { headers, rows } = &parsed.get_all_elements()[0] else {
+            panic!("Expected a table");
+        };
+        assert_eq!(rows[0].cells.len(), 3);
+        let generated = Transformer::generate(&parsed)?;
+        assert_eq!(std::str::from_utf8(&generated)?, "a\tb\tc\n1\t2\t\n");
+
+        // A table whose row has fewer cells than headers
+        let uneven = Document::new(vec![Element::Table {
+            headers: headers.clone(),
+            rows: vec![TableRow {
+                cells: vec![TableCell {
+                    element: Element::Text {
+                        text: "1".to_string(),
+                        size: 8,
+                    },
+                }],
+            }],
+        }]);
+        let generated = Transformer::generate(&uneven)?;
+        assert_eq!(std::str::from_utf8(&generated)?, "a\tb\tc\n1\t\t\n");
+        Ok(())
+    }
+}
@@ -1,12 +1,13 @@
 use crate::core::Element::{Header, Hyperlink, Image, List, Paragraph, Table, Text};
 
-use crate::core::{Document, Element, ListItem, TableHeader, TableRow, TransformerTrait};
+use crate::core::{
+    Document, Element, ImageData, ListItem, TableHeader, TableRow, TransformerTrait, IMAGE_AREA,
+};
 use anyhow;
 use bytes::Bytes;
 use comemo::Prehashed;
 use log::warn;
-use std::path::Path;
-use std::{collections::HashMap, io::Cursor};
+use std::collections::HashMap;
 use time::{OffsetDateTime, UtcOffset};
 
 use typst::{
@@ -28,99 +29,176 @@ pub struct ShivaWorld {
 }
 
 impl ShivaWorld {
+    /// A world that typesets with the fonts of the `fonts` folder, which are
+    /// downloaded into it on first use.
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new(source: String, img_map: HashMap<String, typst::foundations::Bytes>) -> Self {
+        folder_fonts("fonts")
+            .and_then(|fonts| Self::with_fonts(source, img_map, &fonts))
+            .expect("failed to load the fonts")
+    }
+
+    /// A world that typesets with `fonts`, the bytes of TTF and OTF files or
+    /// font collections. Nothing is read from disk or downloaded, so this also
+    /// works on wasm32.
+    pub fn with_fonts(
+        source: String,
+        img_map: HashMap<String, typst::foundations::Bytes>,
+        fonts: &[Bytes],
+    ) -> anyhow::Result<Self> {
         let source = Source::detached(source);
 
-        let folder = "fonts";
-
-        // Check if the "fonts" folder exists
-        if !std::path::Path::new(folder).exists() {
-            // Create the "fonts" folder
-            std::fs::create_dir_all(folder).expect("Failed to create folder");
-
-            // Download fonts
-            let font_info = vec![
-                ("DejaVuSansMono-Bold.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/DejaVuSansMono-Bold.ttf"),
-                ("DejaVuSansMono.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/DejaVuSansMono.ttf"),
-                ("FiraMath-Regular.otf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/FiraMath-Regular.otf"),
-                ("IBMPlexSerif-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/IBMPlexSerif-Regular.ttf"),
-                ("InriaSerif-BoldItalic.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/InriaSerif-BoldItalic.ttf"),
-                ("InriaSerif-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/InriaSerif-Regular.ttf"),
-                ("LinLibertine_R.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_R.ttf"),
-                ("LinLibertine_RB.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_RB.ttf"),
-                ("LinLibertine_RBI.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_RBI.ttf"),
-                ("LinLibertine_RI.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_RI.ttf"),
-                ("Nerd.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/Nerd.ttf"),
-                ("NewCM10-Bold.otf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCM10-Bold.otf"),
-                ("NewCM10-Regular.otf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCM10-Regular.otf"),
-                ("NewCMMath-Book.otf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCMMath-Book.otf"),
-                ("NewCMMath-Regular.otf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCMMath-Regular.otf"),
-                ("NotoColorEmoji.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoColorEmoji.ttf"),
-                ("NotoSansArabic-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSansArabic-Regular.ttf"),
-                ("NotoSansSymbols2-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSansSymbols2-Regular.ttf"),
-                ("NotoSerifCJKsc-Regular.otf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSerifCJKsc-Regular.otf"),
-                ("NotoSerifHebrew-Bold.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSerifHebrew-Bold.ttf"),
-                ("NotoSerifHebrew-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSerifHebrew-Regular.ttf"),
-                ("PTSans-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/PTSans-Regular.ttf"),
-                ("Roboto-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/Roboto-Regular.ttf"),
-                ("TwitterColorEmoji.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/TwitterColorEmoji.ttf"),
-                ("Ubuntu-Regular.ttf", "https://github.com/igumnoff/shiva/raw/main/lib/fonts/Ubuntu-Regular.ttf"),
-            ];
-
-            for (filename, url) in font_info {
-                download_font(url, folder, filename);
+        let mut loaded = Vec::new();
+        for (index, bytes) in fonts.iter().enumerate() {
+            let buffer = typst::foundations::Bytes::from(bytes.to_vec());
+            let face_count = ttf_parser::fonts_in_collection(&buffer).unwrap_or(1);
+            for face in 0..face_count {
+                loaded.push(Font::new(buffer.clone(), face).ok_or_else(|| {
+                    anyhow::anyhow!("Failed to load font {index} (face index {face})")
+                })?);
             }
         }
+        if loaded.is_empty() {
+            return Err(anyhow::anyhow!("No fonts to typeset with"));
+        }
 
-        let fonts = std::fs::read_dir(folder)
-            .unwrap()
-            .map(Result::unwrap)
-            .flat_map(|entry| {
-                let path = entry.path();
-                let bytes = std::fs::read(&path).unwrap();
-                let buffer = typst::foundations::Bytes::from(bytes);
-                let face_count = ttf_parser::fonts_in_collection(&buffer).unwrap_or(1);
-                (0..face_count).map(move |face| {
-                    Font::new(buffer.clone(), face).unwrap_or_else(|| {
-                        panic!("failed to load font from {path:?} (face index {face})");
-                    })
-                })
-            })
-            .collect::<Vec<Font>>();
-
-        Self {
-            book: Prehashed::new(FontBook::from_fonts(&fonts)),
-            fonts,
+        Ok(Self {
+            book: Prehashed::new(FontBook::from_fonts(&loaded)),
+            fonts: loaded,
             library: Prehashed::new(Library::default()),
             source,
             img_map,
-        }
+        })
     }
 }
 
-#[cfg(target_arch = "wasm32")]
-fn download_font(url: &str, folder: &str, filename: &str) {
-    use log::info;
-
-    let font_path = Path::new(folder).join(filename);
-
-    info!("Downloading font file {}...", font_path.display());
-
-    let request = ehttp::Request::get(url);
-    ehttp::fetch(request, move |result: ehttp::Result<ehttp::Response>| {
-        let mut reader = Cursor::new(result.unwrap().bytes);
-        let f = std::fs::File::create(&font_path).unwrap();
-        let mut writer = std::io::BufWriter::new(f);
-
-        let _bytes_io_count = std::io::copy(&mut reader, &mut writer).unwrap();
+/// Reads the fonts of `folder`, downloading them when the folder is missing.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn folder_fonts(folder: &str) -> anyhow::Result<Vec<Bytes>> {
+    // Check if the "fonts" folder exists
+    if !std::path::Path::new(folder).exists() {
+        // Create the "fonts" folder
+        std::fs::create_dir_all(folder)?;
+
+        // Download fonts
+        let font_info = vec![
+            (
+                "DejaVuSansMono-Bold.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/DejaVuSansMono-Bold.ttf",
+            ),
+            (
+                "DejaVuSansMono.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/DejaVuSansMono.ttf",
+            ),
+            (
+                "FiraMath-Regular.otf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/FiraMath-Regular.otf",
+            ),
+            (
+                "IBMPlexSerif-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/IBMPlexSerif-Regular.ttf",
+            ),
+            (
+                "InriaSerif-BoldItalic.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/InriaSerif-BoldItalic.ttf",
+            ),
+            (
+                "InriaSerif-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/InriaSerif-Regular.ttf",
+            ),
+            (
+                "LinLibertine_R.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_R.ttf",
+            ),
+            (
+                "LinLibertine_RB.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_RB.ttf",
+            ),
+            (
+                "LinLibertine_RBI.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_RBI.ttf",
+            ),
+            (
+                "LinLibertine_RI.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/LinLibertine_RI.ttf",
+            ),
+            (
+                "Nerd.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/Nerd.ttf",
+            ),
+            (
+                "NewCM10-Bold.otf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCM10-Bold.otf",
+            ),
+            (
+                "NewCM10-Regular.otf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCM10-Regular.otf",
+            ),
+            (
+                "NewCMMath-Book.otf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCMMath-Book.otf",
+            ),
+            (
+                "NewCMMath-Regular.otf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NewCMMath-Regular.otf",
+            ),
+            (
+                "NotoColorEmoji.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoColorEmoji.ttf",
+            ),
+            (
+                "NotoSansArabic-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSansArabic-Regular.ttf",
+            ),
+            (
+                "NotoSansSymbols2-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSansSymbols2-Regular.ttf",
+            ),
+            (
+                "NotoSerifCJKsc-Regular.otf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSerifCJKsc-Regular.otf",
+            ),
+            (
+                "NotoSerifHebrew-Bold.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSerifHebrew-Bold.ttf",
+            ),
+            (
+                "NotoSerifHebrew-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/NotoSerifHebrew-Regular.ttf",
+            ),
+            (
+                "PTSans-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/PTSans-Regular.ttf",
+            ),
+            (
+                "Roboto-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/Roboto-Regular.ttf",
+            ),
+            (
+                "TwitterColorEmoji.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/TwitterColorEmoji.ttf",
+            ),
+            (
+                "Ubuntu-Regular.ttf",
+                "https://github.com/igumnoff/shiva/raw/main/lib/fonts/Ubuntu-Regular.ttf",
+            ),
+        ];
+
+        for (filename, url) in font_info {
+            download_font(url, folder, filename)?;
+        }
+    }
 
-        info!("Font file {} downloaded successfully!", font_path.display());
-    });
+    std::fs::read_dir(folder)?
+        .map(|entry| Ok(Bytes::from(std::fs::read(entry?.path())?)))
+        .collect()
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn download_font(url: &str, folder: &str, filename: &str) {
+fn download_font(url: &str, folder: &str, filename: &str) -> anyhow::Result<()> {
     use log::info;
+    use std::io::Cursor;
+    use std::path::Path;
 
     let font_path = Path::new(folder).join(filename);
 
@@ -128,13 +206,14 @@ fn download_font(url: &str, folder: &str, filename: &str) {
 
     let request = ehttp::Request::get(url);
     let response = ehttp::fetch_blocking(&request);
-    let mut reader = Cursor::new(response.unwrap().bytes);
-    let f = std::fs::File::create(&font_path).unwrap();
+    let mut reader = Cursor::new(response.map_err(anyhow::Error::msg)?.bytes);
+    let f = std::fs::File::create(&font_path)?;
     let mut writer = std::io::BufWriter::new(f);
 
-    let _bytes_io_count = std::io::copy(&mut reader, &mut writer).unwrap();
+    let _bytes_io_count = std::io::copy(&mut reader, &mut writer)?;
 
     info!("Font file {} downloaded successfully!", font_path.display());
+    Ok(())
 }
 
 impl World for ShivaWorld {
@@ -309,6 +388,11 @@ pub fn generate_document(
                 } else {
                     source.push_str("- ")
                 };
+                match el.checked {
+                    Some(true) => source.push_str("☑ "),
+                    Some(false) => source.push_str("☐ "),
+                    None => {}
+                }
 
                 process_element(source, img_map, &el.element)?;
             }
@@ -317,20 +401,25 @@ pub fn generate_document(
         Ok(())
     }
 
-    fn process_image(
-        source: &mut TypstString,
-        bytes: &Bytes,
-        title: &str,
-        alt: &str,
-        image_type: &str,
-    ) -> anyhow::Result<()> {
-        if !bytes.is_empty() {
-            let image_text = format!(
-                "
-            #image(\"{title}{image_type}\", alt: \"{alt}\")
-            "
+    fn process_image(source: &mut TypstString, image: &ImageData) -> anyhow::Result<()> {
+        if !image.bytes().is_empty() {
+            let mut arguments = format!(
+                "\"{}{}\", alt: \"{}\"",
+                image.title(),
+                image.image_type(),
+                image.alt()
             );
-            source.push_str(&image_text);
+            if let Some((width, _)) = image.display_size(IMAGE_AREA) {
+                arguments.push_str(&format!(", width: {width:.1}pt"));
+            }
+            // Figures are numbered by typst itself
+            let image_text = if image.caption().is_empty() {
+                format!("#image({arguments})")
+            } else {
+                let caption = image.caption().replace('\\', "\\\\").replace('"', "\\\"");
+                format!("#figure(image({arguments}), caption: \"{caption}\")")
+            };
+            source.push_str(&format!("\n{image_text}\n"));
         }
         // need to think how to implement using raw bytes
         Ok(())
@@ -378,15 +467,28 @@ pub fn generate_document(
             Image(image) => {
                 let key = format!("{}{}", image.title(), image.image_type());
                 img_map.insert(key, typst::foundations::Bytes::from(image.bytes().to_vec()));
-                process_image(
-                    source,
-                    image.bytes(),
-                    image.title(),
-                    image.alt(),
-                    &image.image_type().to_string(),
-                )?;
+                process_image(source, image)?;
                 source.push('\n');
                 Ok(())
+            }
+            Element::DefinitionList { items } => {
+                for item in items {
+                    source.push_str(&format!("/ {}: ", item.term));
+                    for description in &item.descriptions {
+                        process_element(source, img_map, description)?;
+                    }
+                    source.push('\n');
+                }
+                Ok(())
+            }
+            Element::Math { latex, display } => {
+                let math = crate::math::to_typst(&crate::math::parse(latex));
+                if *display {
+                    source.push_str(&format!("$ {math} $\n"));
+                } else {
+                    source.push_str(&format!("${math}$\n"));
+                }
+                Ok(())
             } // _ => {
               //     warn!("Should implement element - {:?}", element);
               //     Ok(())
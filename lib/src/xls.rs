@@ -70,7 +70,7 @@ impl TransformerTrait for Transformer {
     where
         Self: Sized,
     {
-        todo!()
+        Err(anyhow::anyhow!("Generating XLS documents is not supported"))
     }
 }
 
@@ -8,8 +8,8 @@ use quick_xml::{
 use std::str::from_utf8;
 
 use crate::core::{
-    Document, Element, ImageAlignment, ImageData, ImageDimension, ImageType, ListItem,
-    PageDimensions, PageFormat, TableCell, TableHeader, TableRow, TransformerTrait,
+    DefinitionItem, Document, Element, ImageAlignment, ImageData, ImageDimension, ImageType,
+    ListItem, PageDimensions, PageFormat, TableCell, TableHeader, TableRow, TransformerTrait,
 };
 
 use serde::{Deserialize, Serialize};
@@ -187,6 +187,7 @@ impl TransformerTrait for Transformer {
                         let mut align = ImageAlignment::default().to_string();
                         let mut width = None;
                         let mut height = None;
+                        let mut caption = "";
                         for child in element.children.iter() {
                             match child.name.as_str() {
                                 "image_type" => {
@@ -238,17 +239,24 @@ impl TransformerTrait for Transformer {
                                         error!("Error: No value");
                                     }
                                 }
+                                "caption" => {
+                                    if let Some(value) = &child.text {
+                                        caption = value;
+                                    }
+                                }
                                 _ => {}
                             }
                         }
-                        elements.push(Element::Image(ImageData::new(
+                        let mut image = ImageData::new(
                             image_bytes,
                             title.to_string(),
                             alt.to_string(),
                             image_type,
                             align,
                             ImageDimension { width, height },
-                        )));
+                        );
+                        image.set_image_caption(caption);
+                        elements.push(Element::Image(image));
                     }
                     "Hyperlink" => {
                         let mut url = "_";
@@ -493,6 +501,37 @@ impl TransformerTrait for Transformer {
                         }
                         elements.push(Element::Table { headers, rows });
                     }
+                    "DefinitionList" => {
+                        let mut items: Vec<DefinitionItem> = vec![];
+                        for item in element.children.iter().flat_map(|c| c.children.iter()) {
+                            if item.name.as_str() != "DefinitionItem" {
+                                continue;
+                            }
+                            let mut term = String::new();
+                            let mut descriptions = vec![];
+                            for child in item.children.iter() {
+                                match child.name.as_str() {
+                                    "term" => term = child.text.clone().unwrap_or_default(),
+                                    "descriptions" => descriptions = parse_element(child)?,
+                                    _ => {}
+                                }
+                            }
+                            items.push(DefinitionItem { term, descriptions });
+                        }
+                        elements.push(Element::DefinitionList { items });
+                    }
+                    "Math" => {
+                        let mut latex = String::new();
+                        let mut display = false;
+                        for child in element.children.iter() {
+                            match child.name.as_str() {
+                                "latex" => latex = child.text.clone().unwrap_or_default(),
+                                "display" => display = child.text.as_deref() == Some("true"),
+                                _ => {}
+                            }
+                        }
+                        elements.push(Element::Math { latex, display });
+                    }
                     "element" => {
                         elements = parse_element(element)?;
                     }
@@ -506,8 +545,11 @@ impl TransformerTrait for Transformer {
             let mut elements: Vec<ListItem> = vec![];
             for element in element_data.children.iter() {
                 if element.name.as_str() == "ListItem" {
+                    let first_item = elements.len();
+                    let mut checked = None;
                     for child in element.children.iter() {
                         match child.name.as_str() {
+                            "checked" => checked = child.text.as_deref().map(|v| v == "true"),
                             "elements" => {
                                 for sub_child in child.children.iter() {
                                     if sub_child.name.as_str() == "Text" {
@@ -538,6 +580,7 @@ impl TransformerTrait for Transformer {
                                         };
                                         elements.push(ListItem {
                                             element: sub_element,
+                                            checked: None,
                                         });
                                     }
                                 }
@@ -565,11 +608,15 @@ impl TransformerTrait for Transformer {
                                         elements: sub_elements,
                                         numbered,
                                     },
+                                    checked: None,
                                 });
                             }
                             _ => {}
                         }
                     }
+                    for item in &mut elements[first_item..] {
+                        item.checked = checked;
+                    }
                 }
             }
             Ok(elements)
@@ -754,6 +801,11 @@ impl TransformerTrait for Transformer {
                         &image.image_type().to_string(),
                     )))?;
                     writer.write_event(Event::End(BytesEnd::new("image_type")))?;
+                    if !image.caption().is_empty() {
+                        writer.write_event(Event::Start(BytesStart::new("caption")))?;
+                        writer.write_event(Event::Text(BytesText::new(image.caption())))?;
+                        writer.write_event(Event::End(BytesEnd::new("caption")))?;
+                    }
                     writer.write_event(Event::End(BytesEnd::new("Image")))?;
                 }
                 Element::Hyperlink {
@@ -838,6 +890,34 @@ impl TransformerTrait for Transformer {
                     writer.write_event(Event::End(BytesEnd::new("rows")))?;
                     writer.write_event(Event::End(BytesEnd::new("Table")))?;
                 }
+                Element::DefinitionList { items } => {
+                    writer.write_event(Event::Start(BytesStart::new("DefinitionList")))?;
+                    writer.write_event(Event::Start(BytesStart::new("items")))?;
+                    for item in items {
+                        writer.write_event(Event::Start(BytesStart::new("DefinitionItem")))?;
+                        writer.write_event(Event::Start(BytesStart::new("term")))?;
+                        writer.write_event(Event::Text(BytesText::new(&item.term)))?;
+                        writer.write_event(Event::End(BytesEnd::new("term")))?;
+                        writer.write_event(Event::Start(BytesStart::new("descriptions")))?;
+                        for description in &item.descriptions {
+                            serialize_element(description, writer)?;
+                        }
+                        writer.write_event(Event::End(BytesEnd::new("descriptions")))?;
+                        writer.write_event(Event::End(BytesEnd::new("DefinitionItem")))?;
+                    }
+                    writer.write_event(Event::End(BytesEnd::new("items")))?;
+                    writer.write_event(Event::End(BytesEnd::new("DefinitionList")))?;
+                }
+                Element::Math { latex, display } => {
+                    writer.write_event(Event::Start(BytesStart::new("Math")))?;
+                    writer.write_event(Event::Start(BytesStart::new("latex")))?;
+                    writer.write_event(Event::Text(BytesText::new(latex)))?;
+                    writer.write_event(Event::End(BytesEnd::new("latex")))?;
+                    writer.write_event(Event::Start(BytesStart::new("display")))?;
+                    writer.write_event(Event::Text(BytesText::new(&display.to_string())))?;
+                    writer.write_event(Event::End(BytesEnd::new("display")))?;
+                    writer.write_event(Event::End(BytesEnd::new("Math")))?;
+                }
             }
             Ok(())
         }
@@ -846,11 +926,16 @@ impl TransformerTrait for Transformer {
             element: &ListItem,
             writer: &mut Writer<&mut Vec<u8>>,
         ) -> Result<()> {
-            let ListItem { element } = element;
+            let ListItem { element, checked } = element;
             writer.write_event(Event::Start(BytesStart::new("ListItem")))?;
             writer.write_event(Event::Start(BytesStart::new("element")))?;
             serialize_element(element, writer)?;
             writer.write_event(Event::End(BytesEnd::new("element")))?;
+            if let Some(checked) = checked {
+                writer.write_event(Event::Start(BytesStart::new("checked")))?;
+                writer.write_event(Event::Text(BytesText::new(&checked.to_string())))?;
+                writer.write_event(Event::End(BytesEnd::new("checked")))?;
+            }
             writer.write_event(Event::End(BytesEnd::new("ListItem")))?;
             Ok(())
         }
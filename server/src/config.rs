@@ -0,0 +1,266 @@
+use serde::Deserialize;
+use shiva::core::DocumentType;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+//the server configuration, read from a TOML file and then from `SHIVA_*` environment variables:
+//
+//  host = "127.0.0.1"            # SHIVA_HOST
+//  port = 8080                   # SHIVA_PORT
+//  body_limit = 104857600        # SHIVA_BODY_LIMIT, bytes
+//  formats = ["md", "html"]      # SHIVA_FORMATS=md,html, all enabled formats when missing
+//  workers = 4                   # SHIVA_WORKERS, async threads, one per CPU when missing
+//  max_jobs = 2                  # SHIVA_MAX_JOBS, jobs converted at the same time
+//  max_pending_jobs = 100        # SHIVA_MAX_PENDING_JOBS, jobs queued or running, more are refused
+//  job_ttl = 3600                # SHIVA_JOB_TTL, seconds
+//  request_timeout = 300         # SHIVA_REQUEST_TIMEOUT, seconds
+//  shutdown_timeout = 30         # SHIVA_SHUTDOWN_TIMEOUT, seconds
+//  conversion_timeout = 60       # SHIVA_CONVERSION_TIMEOUT, seconds for each document
+//  max_conversions = 4           # SHIVA_MAX_CONVERSIONS, documents converted at the same time,
+//                                #   timed out ones included, one per CPU when missing
+//  max_depth = 64                # SHIVA_MAX_DEPTH, nesting of elements and of markup tags
+//  max_elements = 100000         # SHIVA_MAX_ELEMENTS, elements of a document
+//  max_unpacked_size = 524288000 # SHIVA_MAX_UNPACKED_SIZE, bytes, of ZIP, DOCX, XLSX and ODS
+//  max_image_pixels = 100000000  # SHIVA_MAX_IMAGE_PIXELS, width times height of an image
+//  auth_config = "auth.toml"     # SHIVA_AUTH_CONFIG
+//  swagger_ui = false            # SHIVA_SWAGGER_UI, serves the API documentation at `/docs`
+//
+//  [tls]
+//  cert = "cert.pem"             # SHIVA_TLS_CERT
+//  key = "key.pem"               # SHIVA_TLS_KEY
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub host: String,
+    pub port: u16,
+    pub body_limit: usize,
+    pub formats: Option<Vec<String>>,
+    pub workers: Option<usize>,
+    pub max_jobs: usize,
+    pub max_pending_jobs: usize,
+    pub job_ttl: u64,
+    pub request_timeout: u64,
+    pub shutdown_timeout: u64,
+    pub conversion_timeout: u64,
+    pub max_conversions: Option<usize>,
+    pub max_depth: usize,
+    pub max_elements: usize,
+    pub max_unpacked_size: u64,
+    pub max_image_pixels: u64,
+    pub auth_config: Option<String>,
+    pub swagger_ui: bool,
+    pub tls: Option<TlsConfig>,
+    #[serde(skip)]
+    enabled_formats: Option<Vec<DocumentType>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            body_limit: 100 * 1024 * 1024, //file size limit - 100 Mb
+            formats: None,
+            workers: None,
+            max_jobs: 2,
+            max_pending_jobs: 100,
+            job_ttl: 3600,
+            request_timeout: 300,
+            shutdown_timeout: 30,
+            conversion_timeout: 60,
+            max_conversions: None,
+            max_depth: 64,
+            max_elements: 100_000,
+            max_unpacked_size: 500 * 1024 * 1024,
+            max_image_pixels: shiva::core::MAX_RASTER_PIXELS,
+            auth_config: None,
+            swagger_ui: false,
+            tls: None,
+            enabled_formats: None,
+        }
+    }
+}
+
+static INSTANCE: OnceLock<Config> = OnceLock::new();
+
+//the configuration of the running server, the defaults until `init` is called
+pub fn config() -> &'static Config {
+    INSTANCE.get_or_init(Config::default)
+}
+
+pub fn init(config: Config) -> anyhow::Result<()> {
+    INSTANCE
+        .set(config)
+        .map_err(|_| anyhow::anyhow!("The configuration is already set"))
+}
+
+impl Config {
+    pub fn load(path: Option<&str>) -> anyhow::Result<Config> {
+        let mut config = match path {
+            Some(path) => Config::from_toml(&std::fs::read_to_string(path)?)?,
+            None => Config::default(),
+        };
+        config.apply_env(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_toml(text: &str) -> anyhow::Result<Config> {
+        let mut config: Config = toml::from_str(text)?;
+        config.check()?;
+        Ok(config)
+    }
+
+    //overrides the settings with the variables that `vars` returns
+    pub fn apply_env(&mut self, vars: impl Fn(&str) -> Option<String>) -> anyhow::Result<()> {
+        fn parse<T: FromStr>(name: &str, value: String) -> anyhow::Result<T> {
+            value
+                .trim()
+                .parse()
+                .map_err(|_| anyhow::anyhow!("{name} has an invalid value: {value}"))
+        }
+
+        if let Some(value) = vars("SHIVA_HOST") {
+            self.host = value;
+        }
+        if let Some(value) = vars("SHIVA_PORT") {
+            self.port = parse("SHIVA_PORT", value)?;
+        }
+        if let Some(value) = vars("SHIVA_BODY_LIMIT") {
+            self.body_limit = parse("SHIVA_BODY_LIMIT", value)?;
+        }
+        if let Some(value) = vars("SHIVA_FORMATS") {
+            self.formats = Some(value.split(',').map(|f| f.trim().to_string()).collect());
+        }
+        if let Some(value) = vars("SHIVA_WORKERS") {
+            self.workers = Some(parse("SHIVA_WORKERS", value)?);
+        }
+        if let Some(value) = vars("SHIVA_MAX_JOBS") {
+            self.max_jobs = parse("SHIVA_MAX_JOBS", value)?;
+        }
+        if let Some(value) = vars("SHIVA_MAX_PENDING_JOBS") {
+            self.max_pending_jobs = parse("SHIVA_MAX_PENDING_JOBS", value)?;
+        }
+        if let Some(value) = vars("SHIVA_JOB_TTL") {
+            self.job_ttl = parse("SHIVA_JOB_TTL", value)?;
+        }
+        if let Some(value) = vars("SHIVA_REQUEST_TIMEOUT") {
+            self.request_timeout = parse("SHIVA_REQUEST_TIMEOUT", value)?;
+        }
+        if let Some(value) = vars("SHIVA_SHUTDOWN_TIMEOUT") {
+            self.shutdown_timeout = parse("SHIVA_SHUTDOWN_TIMEOUT", value)?;
+        }
+        if let Some(value) = vars("SHIVA_CONVERSION_TIMEOUT") {
+            self.conversion_timeout = parse("SHIVA_CONVERSION_TIMEOUT", value)?;
+        }
+        if let Some(value) = vars("SHIVA_MAX_CONVERSIONS") {
+            self.max_conversions = Some(parse("SHIVA_MAX_CONVERSIONS", value)?);
+        }
+        if let Some(value) = vars("SHIVA_MAX_DEPTH") {
+            self.max_depth = parse("SHIVA_MAX_DEPTH", value)?;
+        }
+        if let Some(value) = vars("SHIVA_MAX_ELEMENTS") {
+            self.max_elements = parse("SHIVA_MAX_ELEMENTS", value)?;
+        }
+        if let Some(value) = vars("SHIVA_MAX_UNPACKED_SIZE") {
+            self.max_unpacked_size = parse("SHIVA_MAX_UNPACKED_SIZE", value)?;
+        }
+        if let Some(value) = vars("SHIVA_MAX_IMAGE_PIXELS") {
+            self.max_image_pixels = parse("SHIVA_MAX_IMAGE_PIXELS", value)?;
+        }
+        if let Some(value) = vars("SHIVA_AUTH_CONFIG") {
+            self.auth_config = Some(value);
+        }
+        if let Some(value) = vars("SHIVA_SWAGGER_UI") {
+            self.swagger_ui = parse("SHIVA_SWAGGER_UI", value)?;
+        }
+        match (vars("SHIVA_TLS_CERT"), vars("SHIVA_TLS_KEY")) {
+            (Some(cert), Some(key)) => {
+                self.tls = Some(TlsConfig {
+                    cert: cert.into(),
+                    key: key.into(),
+                })
+            }
+            (None, None) => {}
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "SHIVA_TLS_CERT and SHIVA_TLS_KEY must be set together"
+                ))
+            }
+        }
+
+        self.check()
+    }
+
+    //validates the settings and resolves the format names
+    fn check(&mut self) -> anyhow::Result<()> {
+        if self.max_jobs == 0 || self.max_pending_jobs == 0 {
+            return Err(anyhow::anyhow!(
+                "max_jobs and max_pending_jobs must be at least 1"
+            ));
+        }
+        if self.workers == Some(0) || self.max_conversions == Some(0) {
+            return Err(anyhow::anyhow!(
+                "workers and max_conversions must be at least 1"
+            ));
+        }
+        if self.conversion_timeout == 0 || self.max_depth == 0 || self.max_elements == 0 {
+            return Err(anyhow::anyhow!(
+                "conversion_timeout, max_depth and max_elements must be at least 1"
+            ));
+        }
+        self.enabled_formats = match &self.formats {
+            Some(formats) => Some(
+                formats
+                    .iter()
+                    .map(|format| {
+                        DocumentType::from_extension(&format.to_lowercase())
+                            .filter(DocumentType::is_enabled)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown format: {format}"))
+                    })
+                    .collect::<anyhow::Result<_>>()?,
+            ),
+            None => None,
+        };
+        Ok(())
+    }
+
+    //the format is enabled in this build and allowed by the configuration
+    pub fn format_enabled(&self, format: DocumentType) -> bool {
+        format.is_enabled()
+            && self
+                .enabled_formats
+                .as_ref()
+                .is_none_or(|formats| formats.contains(&format))
+    }
+
+    pub fn job_ttl(&self) -> Duration {
+        Duration::from_secs(self.job_ttl)
+    }
+
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout)
+    }
+
+    pub fn conversion_timeout(&self) -> Duration {
+        Duration::from_secs(self.conversion_timeout)
+    }
+
+    pub fn max_conversions(&self) -> usize {
+        self.max_conversions.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+        })
+    }
+}
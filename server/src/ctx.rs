@@ -0,0 +1,15 @@
+//the caller of a request, known once the request has been authenticated
+#[derive(Debug, Clone)]
+pub struct Ctx {
+    key_id: String,
+}
+
+impl Ctx {
+    pub fn new(key_id: String) -> Self {
+        Self { key_id }
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+}
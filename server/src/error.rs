@@ -1,5 +1,6 @@
-use axum::http::StatusCode;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
+use axum::Json;
 use log::info;
 use serde::Serialize;
 use strum_macros::AsRefStr;
@@ -9,20 +10,146 @@ pub type Result<T> = core::result::Result<T, Error>;
 #[derive(Debug, Clone, AsRefStr, Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum Error {
-    ExtensionMissing,      //the file extension is missing
-    FailBytes,             //binary conversion error
-    UnsupportedFormat,     //unsupported format
-    FailParseDocument,     //document parsing error
-    FailConvertFile,       //file conversion error
-    FailHeader,            //error creating the header of the converted file
-    NoFilesToConvertInZip, //there are no files to convert in the zip archive
+    ExtensionMissing,          //the file extension is missing
+    FileMissing,               //the request has no `file` field
+    InputFormatMissing,        //the raw body has neither a `from` format nor a Content-Type
+    NotAcceptable,             //no type of the Accept header is a format that can be generated
+    FailBytes,                 //binary conversion error
+    FileTooLarge,              //the upload exceeds the size limit
+    RequestTimeout,            //the request took longer than the configured timeout
+    UnsupportedFormat(String), //unsupported format
+    InvalidZip,                //the zip archive cannot be read
+    FailParseDocument(String), //document parsing error
+    FailConvertFile(String),   //file conversion error
+    FailHeader,                //error creating the header of the converted file
+    NoFilesToConvertInZip,     //there are no files to convert in the zip archive
+    ConversionTimeout(u64),    //the conversion of a document took longer than the limit
+    TooDeep(usize),            //the elements or the tags are nested deeper than the limit
+    TooManyElements(usize),    //the document has more elements than the limit
+    UnpackedTooLarge(u64),     //the ZIP container unpacks to more bytes than the limit
+    ImageTooLarge(u64),        //an image has more pixels than the limit
+    ServerBusy,                //every converter is taken, timed out conversions included
+    JobNotFound,               //there is no job with the id, or its result has expired
+    TooManyJobs(usize),        //as many jobs as the limit are queued or running
+    JobNotFinished,            //the job result was requested before the job finished
+    AuthMissing,               //the request has neither an API key nor a bearer token
+    AuthInvalid,               //the API key or the bearer token is not valid
+    LoginFail,                 //the API key given to the login is not valid
+    TokensDisabled,            //no JWT secret is configured, so no tokens are issued
+    RateLimited,               //the key made more requests this minute than it may
+    QuotaExceeded,             //the key used up its requests for the day
+}
+
+impl Error {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Error::ExtensionMissing
+            | Error::FileMissing
+            | Error::InputFormatMissing
+            | Error::FailBytes => StatusCode::BAD_REQUEST,
+            Error::NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            Error::FileTooLarge | Error::UnpackedTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::RequestTimeout => StatusCode::REQUEST_TIMEOUT,
+            Error::UnsupportedFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            Error::InvalidZip
+            | Error::FailParseDocument(_)
+            | Error::NoFilesToConvertInZip
+            | Error::ConversionTimeout(_)
+            | Error::TooDeep(_)
+            | Error::TooManyElements(_)
+            | Error::ImageTooLarge(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            Error::AuthMissing | Error::AuthInvalid | Error::LoginFail => StatusCode::UNAUTHORIZED,
+            Error::RateLimited | Error::QuotaExceeded => StatusCode::TOO_MANY_REQUESTS,
+            Error::JobNotFound | Error::TokensDisabled => StatusCode::NOT_FOUND,
+            Error::JobNotFinished => StatusCode::CONFLICT,
+            Error::FailConvertFile(_) | Error::FailHeader => StatusCode::INTERNAL_SERVER_ERROR,
+            Error::ServerBusy | Error::TooManyJobs(_) => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    pub fn detail(&self) -> String {
+        match self {
+            Error::ExtensionMissing => "The uploaded file name has no extension".to_string(),
+            Error::FileMissing => "The request has no `file` field".to_string(),
+            Error::InputFormatMissing => {
+                "Set the input format with `from` or a Content-Type".to_string()
+            }
+            Error::NotAcceptable => {
+                "Set the output format with `to` or an Accept header of a supported type"
+                    .to_string()
+            }
+            Error::FailBytes => "The upload could not be read".to_string(),
+            Error::FileTooLarge => "The upload exceeds the size limit".to_string(),
+            Error::RequestTimeout => "The request took longer than the time limit".to_string(),
+            Error::UnsupportedFormat(format) => format!("The format '{format}' is not supported"),
+            Error::InvalidZip => "The ZIP archive could not be read".to_string(),
+            Error::FailParseDocument(reason) => {
+                format!("The document could not be parsed: {reason}")
+            }
+            Error::FailConvertFile(reason) => {
+                format!("The document could not be converted: {reason}")
+            }
+            Error::FailHeader => "The response headers could not be created".to_string(),
+            Error::NoFilesToConvertInZip => {
+                "The ZIP archive contains no document in a supported format".to_string()
+            }
+            Error::ConversionTimeout(seconds) => {
+                format!("The conversion took longer than {seconds} seconds")
+            }
+            Error::TooDeep(depth) => format!("The document is nested deeper than {depth} levels"),
+            Error::TooManyElements(count) => format!("The document has more than {count} elements"),
+            Error::UnpackedTooLarge(size) => {
+                format!("The document unpacks to more than {size} bytes")
+            }
+            Error::ImageTooLarge(pixels) => format!("An image has more than {pixels} pixels"),
+            Error::ServerBusy => {
+                "The server is converting too many documents, retry later".to_string()
+            }
+            Error::JobNotFound => "There is no job with this id, or it has expired".to_string(),
+            Error::TooManyJobs(count) => {
+                format!("The server has {count} jobs pending, retry later")
+            }
+            Error::JobNotFinished => "The job has not finished yet".to_string(),
+            Error::AuthMissing => {
+                "The request needs an X-API-Key header or a bearer token".to_string()
+            }
+            Error::AuthInvalid => "The API key or the token is not valid".to_string(),
+            Error::LoginFail => "The API key is not valid".to_string(),
+            Error::TokensDisabled => "This server does not issue tokens".to_string(),
+            Error::RateLimited => "Too many requests for this key, retry in a minute".to_string(),
+            Error::QuotaExceeded => "The daily quota of this key is used up".to_string(),
+        }
+    }
+}
+
+//a problem details body, RFC 9457
+#[derive(Debug, Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: String,
+    status: u16,
+    detail: String,
 }
 
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         info!("-->> {:<12} - {self:?}", "INTO_RES");
 
-        let mut response = StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        let status = self.status();
+        let problem = Problem {
+            problem_type: format!("urn:shiva:error:{}", self.as_ref()),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: self.detail(),
+        };
+
+        let mut response = (
+            status,
+            [(header::CONTENT_TYPE, "application/problem+json")],
+            Json(problem),
+        )
+            .into_response();
 
         response.extensions_mut().insert(self);
 
@@ -0,0 +1,210 @@
+use crate::config::Config;
+use crate::error::{Error, Result};
+use log::error;
+use shiva::core::{Document, DocumentType, Element, ImageData};
+use std::io::{Cursor, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::Duration;
+
+//HTML elements that have no end tag or whose end tag may be left out, they are not counted
+//in the nesting so that valid documents such as long lists of `<li>` are not refused
+const HTML_UNCOUNTED: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr", "p", "li", "dt", "dd", "tr", "td", "th", "thead", "tbody", "tfoot", "option",
+    "optgroup", "rt", "rp", "colgroup", "caption", "html", "head", "body",
+];
+
+//the converter threads that are running; a thread cannot be stopped, so one whose conversion
+//timed out keeps its slot until it finishes and slow documents cannot pile up threads
+static CONVERTERS: AtomicUsize = AtomicUsize::new(0);
+
+struct ConverterSlot;
+
+impl ConverterSlot {
+    fn acquire(max_conversions: usize) -> Option<ConverterSlot> {
+        CONVERTERS
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |running| {
+                (running < max_conversions).then_some(running + 1)
+            })
+            .ok()
+            .map(|_| ConverterSlot)
+    }
+}
+
+impl Drop for ConverterSlot {
+    fn drop(&mut self) {
+        CONVERTERS.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+//runs `convert` on its own thread and gives up after `timeout`; at most `max_conversions`
+//threads run at once, timed out ones included, and the server is busy when all are taken
+pub fn with_timeout<T: Send + 'static>(
+    timeout: Duration,
+    max_conversions: usize,
+    convert: impl FnOnce() -> Result<T> + Send + 'static,
+) -> Result<T> {
+    let slot = ConverterSlot::acquire(max_conversions).ok_or(Error::ServerBusy)?;
+    let (sender, receiver) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("shiva-convert".to_string())
+        .spawn(move || {
+            let _slot = slot;
+            let _ = sender.send(convert());
+        })
+        .map_err(|e| {
+            error!("Failed to start the converter thread: {e}");
+            Error::FailConvertFile("the conversion could not be started".to_string())
+        })?;
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => Err(Error::ConversionTimeout(timeout.as_secs())),
+        //the converter panicked
+        Err(mpsc::RecvTimeoutError::Disconnected) => Err(Error::FailParseDocument(
+            "the document could not be processed".to_string(),
+        )),
+    }
+}
+
+//refuses an input before it is parsed: the formats in a ZIP container by their unpacked size,
+//markup by the nesting of its tags; JSON is limited by the recursion limit of its parser
+pub fn check_input(format: DocumentType, data: &[u8], config: &Config) -> Result<()> {
+    match format {
+        DocumentType::DOCX | DocumentType::XLSX | DocumentType::ODS => {
+            check_unpacked_size(data, config.max_unpacked_size)
+        }
+        DocumentType::HTML => check_markup_depth(data, true, config.max_depth),
+        DocumentType::XML => check_markup_depth(data, false, config.max_depth),
+        _ => Ok(()),
+    }
+}
+
+//the bytes the ZIP entries actually inflate to, the sizes an archive declares may be false;
+//an archive or an entry that cannot be read is left to the parser
+fn check_unpacked_size(data: &[u8], max_size: u64) -> Result<()> {
+    let Ok(mut archive) = zip::ZipArchive::new(Cursor::new(data)) else {
+        return Ok(());
+    };
+
+    let mut size = 0u64;
+    for i in 0..archive.len() {
+        let Ok(file) = archive.by_index(i) else {
+            continue;
+        };
+        let remaining = max_size - size;
+        let mut entry = file.take(remaining + 1);
+        //an entry that fails halfway still counts the bytes it inflated
+        let _ = std::io::copy(&mut entry, &mut std::io::sink());
+        let unpacked = remaining + 1 - entry.limit();
+        if unpacked > remaining {
+            return Err(Error::UnpackedTooLarge(max_size));
+        }
+        size += unpacked;
+    }
+    Ok(())
+}
+
+//the nesting of the tags, counted on the text so that a deep document is refused
+//before the parser recurses into it
+fn check_markup_depth(data: &[u8], html: bool, max_depth: usize) -> Result<()> {
+    let mut depth = 0usize;
+    let mut rest = data;
+
+    while let Some(start) = rest.iter().position(|b| *b == b'<') {
+        rest = &rest[start + 1..];
+        let end = rest.iter().position(|b| *b == b'>').unwrap_or(rest.len());
+        let (tag, closing) = match rest.first() {
+            Some(b'/') => (&rest[1..end], true),
+            Some(b) if b.is_ascii_alphabetic() => (&rest[..end], false),
+            //comments, declarations and processing instructions
+            _ => continue,
+        };
+        let name: String = tag
+            .iter()
+            .take_while(|b| b.is_ascii_alphanumeric() || b"-_:.".contains(b))
+            .map(|b| b.to_ascii_lowercase() as char)
+            .collect();
+        rest = &rest[end..];
+
+        if html && HTML_UNCOUNTED.contains(&name.as_str()) {
+            continue;
+        }
+        if closing {
+            depth = depth.saturating_sub(1);
+        } else if !tag.ends_with(b"/") {
+            //the text of scripts and styles is not markup
+            if html && (name == "script" || name == "style") {
+                let end_tag = format!("</{name}");
+                rest = rest
+                    .windows(end_tag.len())
+                    .position(|window| window.eq_ignore_ascii_case(end_tag.as_bytes()))
+                    .map_or(&[], |at| &rest[at..]);
+                continue;
+            }
+            depth += 1;
+            if depth > max_depth {
+                return Err(Error::TooDeep(max_depth));
+            }
+        }
+    }
+    Ok(())
+}
+
+//the parsed document by the number of its elements, their nesting and the pixels of the images
+pub fn check_document(document: &Document, config: &Config) -> Result<()> {
+    let mut stack: Vec<(&Element, usize)> = document
+        .get_all_elements()
+        .into_iter()
+        .map(|element| (element, 1))
+        .collect();
+    let mut count = 0usize;
+
+    while let Some((element, depth)) = stack.pop() {
+        count += 1;
+        if count > config.max_elements {
+            return Err(Error::TooManyElements(config.max_elements));
+        }
+        if depth > config.max_depth {
+            return Err(Error::TooDeep(config.max_depth));
+        }
+
+        let depth = depth + 1;
+        match element {
+            Element::Paragraph { elements } => {
+                stack.extend(elements.iter().map(|element| (element, depth)))
+            }
+            Element::List { elements, .. } => {
+                stack.extend(elements.iter().map(|item| (&item.element, depth)))
+            }
+            Element::Table { headers, rows } => {
+                stack.extend(headers.iter().map(|header| (&header.element, depth)));
+                stack.extend(
+                    rows.iter()
+                        .flat_map(|row| &row.cells)
+                        .map(|cell| (&cell.element, depth)),
+                );
+            }
+            Element::DefinitionList { items } => stack.extend(
+                items
+                    .iter()
+                    .flat_map(|item| &item.descriptions)
+                    .map(|element| (element, depth)),
+            ),
+            Element::Image(image) => check_image(image, config.max_image_pixels)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+//the size read from the image header, so that a huge image is never decoded
+fn check_image(image: &ImageData, max_pixels: u64) -> Result<()> {
+    match image.intrinsic_size() {
+        Some((width, height)) if u64::from(width) * u64::from(height) > max_pixels => {
+            Err(Error::ImageTooLarge(max_pixels))
+        }
+        _ => Ok(()),
+    }
+}
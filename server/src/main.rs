@@ -1,35 +1,58 @@
-pub use self::error::Result;
+pub use self::error::{Error, Result};
+use crate::config::{config, Config};
+use crate::ctx::Ctx;
+use crate::web::mw_auth::{mw_require_auth, Auth};
 use crate::web::routes_files::handler_convert_file;
+use crate::web::routes_jobs::JobStore;
 use axum::extract::DefaultBodyLimit;
+use axum::http::{Method, StatusCode, Uri};
 use axum::response::{Html, IntoResponse, Response};
 use axum::routing::{get, post};
 use axum::{middleware, Router};
-use clap::{Arg, Command};
+use axum_server::tls_rustls::RustlsConfig;
+use clap::{value_parser, Arg, ArgMatches, Command};
 use env_logger::Env;
-use log::info;
+use log::{info, warn};
+use std::future::IntoFuture;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tokio::net::TcpListener;
+use tokio::sync::watch;
 use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::timeout::TimeoutLayer;
+
+mod config;
+
+mod ctx;
 
 mod error;
 
+mod limits;
+
+mod metrics;
+
 mod web;
 
-// multy thread
-#[tokio::main]
-async fn main() -> Result<()> {
+fn main() -> anyhow::Result<()> {
     env_logger::Builder::from_env(Env::default().default_filter_or("info"))
         .format_timestamp(None)
         .init();
 
     // Defining command line arguments
     let matches = Command::new("Server")
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .help("Sets the TOML configuration file")
+                .require_equals(true),
+        )
         .arg(
             Arg::new("host")
                 .long("host")
                 .value_name("HOST")
                 .help("Sets the host address")
-                .require_equals(true)
-                .default_value("127.0.0.1"),
+                .require_equals(true),
         )
         .arg(
             Arg::new("port")
@@ -37,49 +60,222 @@ async fn main() -> Result<()> {
                 .value_name("PORT")
                 .help("Sets the port number")
                 .require_equals(true)
-                .default_value("8080"),
+                .value_parser(value_parser!(u16)),
+        )
+        .arg(
+            Arg::new("max-jobs")
+                .long("max-jobs")
+                .value_name("COUNT")
+                .help("Sets how many jobs are converted at the same time")
+                .require_equals(true)
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("job-ttl")
+                .long("job-ttl")
+                .value_name("SECONDS")
+                .help("Sets how long the result of a finished job is kept")
+                .require_equals(true)
+                .value_parser(value_parser!(u64)),
+        )
+        .arg(
+            Arg::new("auth-config")
+                .long("auth-config")
+                .value_name("FILE")
+                .help("Sets the TOML file with the API keys and their limits")
+                .require_equals(true),
         )
         .get_matches();
 
-    // Extracting argument values
-    let host = matches.get_one::<String>("host").unwrap();
-    let port = matches.get_one::<String>("port").unwrap();
+    // The configuration file, then the environment, then the command line
+    let mut server_config = Config::load(matches.get_one::<String>("config").map(String::as_str))?;
+    apply_args(&mut server_config, &matches);
+    config::init(server_config)?;
+
+    // multy thread
+    let mut runtime = tokio::runtime::Builder::new_multi_thread();
+    if let Some(workers) = config().workers {
+        runtime.worker_threads(workers);
+    }
+    runtime.enable_all().build()?.block_on(serve())
+}
+
+fn apply_args(config: &mut Config, matches: &ArgMatches) {
+    if let Some(host) = matches.get_one::<String>("host") {
+        config.host = host.clone();
+    }
+    if let Some(port) = matches.get_one::<u16>("port") {
+        config.port = *port;
+    }
+    if let Some(max_jobs) = matches.get_one::<usize>("max-jobs") {
+        config.max_jobs = *max_jobs;
+    }
+    if let Some(job_ttl) = matches.get_one::<u64>("job-ttl") {
+        config.job_ttl = *job_ttl;
+    }
+    if let Some(auth_config) = matches.get_one::<String>("auth-config") {
+        config.auth_config = Some(auth_config.clone());
+    }
+}
+
+async fn serve() -> anyhow::Result<()> {
+    let config = config();
+    let auth = config.auth_config.as_deref().map(Auth::load).transpose()?;
+    let ready = Arc::new(AtomicBool::new(false));
 
     let route_test = Router::new().route("/test_server", get(handler_answer_server));
 
     let route_input_file = Router::new()
         .route("/transform/:output_format", post(handler_convert_file))
         .layer(DefaultBodyLimit::disable())
-        .layer(RequestBodyLimitLayer::new(100 * 1024 * 1024)); //file size limit - 100 Mb
+        .layer(RequestBodyLimitLayer::new(config.body_limit));
 
-    let routes_all = Router::new()
-        .merge(route_test)
+    let job_store = JobStore::new(config.max_jobs, config.max_pending_jobs, config.job_ttl());
+    job_store.spawn_cleanup();
+
+    let route_jobs = web::routes_jobs::routes(job_store)
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(config.body_limit));
+
+    let route_convert = web::routes_convert::routes()
+        .layer(DefaultBodyLimit::disable())
+        .layer(RequestBodyLimitLayer::new(config.body_limit));
+
+    let mut routes_protected = Router::new()
         .merge(route_input_file)
+        .merge(route_convert)
+        .merge(route_jobs);
+    let mut routes_all = Router::new()
+        .merge(route_test)
+        .merge(web::routes_health::routes(ready.clone()))
+        .merge(web::routes_formats::routes())
+        .merge(web::routes_docs::routes(auth.is_some(), config.swagger_ui));
+    if let Some(auth) = auth {
+        routes_protected = routes_protected.route_layer(middleware::from_fn_with_state(
+            auth.clone(),
+            mw_require_auth,
+        ));
+        routes_all = routes_all.merge(web::routes_login::routes(auth));
+    }
+
+    let routes_all = routes_all
+        .merge(routes_protected)
+        .layer(TimeoutLayer::new(config.request_timeout()))
         .layer(middleware::map_response(main_response_mapper));
 
     // region:    ---Start Server
 
-    let listener = TcpListener::bind(format!("{}:{}", host, port))
-        .await
-        .unwrap();
-
-    info!("-->>LISTENING on {:?}", listener.local_addr().unwrap());
-
-    axum::serve(listener, routes_all).await.unwrap();
+    let address = tokio::net::lookup_host((config.host.as_str(), config.port))
+        .await?
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("The host {} has no address", config.host))?;
+
+    // The server stops taking requests on a signal and finishes the open ones
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let shutdown_ready = ready.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        info!("-->>SHUTTING DOWN");
+        shutdown_ready.store(false, Ordering::Relaxed);
+        let _ = shutdown_tx.send(true);
+    });
+
+    match &config.tls {
+        Some(tls) => {
+            let tls_config = RustlsConfig::from_pem_file(&tls.cert, &tls.key).await?;
+            let handle = axum_server::Handle::new();
+            let shutdown_handle = handle.clone();
+            let mut shutdown = shutdown_rx;
+            tokio::spawn(async move {
+                let _ = shutdown.changed().await;
+                shutdown_handle.graceful_shutdown(Some(config.shutdown_timeout()));
+            });
+
+            info!("-->>LISTENING on {address} with TLS");
+            ready.store(true, Ordering::Relaxed);
+            axum_server::bind_rustls(address, tls_config)
+                .handle(handle)
+                .serve(routes_all.into_make_service())
+                .await?;
+        }
+        None => {
+            let listener = TcpListener::bind(address).await?;
+            info!("-->>LISTENING on {:?}", listener.local_addr()?);
+            ready.store(true, Ordering::Relaxed);
+
+            let mut graceful = shutdown_rx.clone();
+            let server = axum::serve(listener, routes_all).with_graceful_shutdown(async move {
+                let _ = graceful.changed().await;
+            });
+            let mut deadline = shutdown_rx;
+            tokio::select! {
+                result = server.into_future() => result?,
+                _ = async {
+                    let _ = deadline.changed().await;
+                    tokio::time::sleep(config.shutdown_timeout()).await;
+                } => warn!("-->>SHUTDOWN timed out, closing the open connections"),
+            }
+        }
+    }
     // endregion: ---Start Server
 
     Ok(())
 }
 
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending::<()>().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
 async fn handler_answer_server() -> impl IntoResponse {
     info!("-->> {:<12} - answer_server", "HANDLER");
 
     Html("TEST DONE")
 }
 
-async fn main_response_mapper(res: Response) -> Response {
+async fn main_response_mapper(uri: Uri, req_method: Method, res: Response) -> Response {
     info!("-->> {:<12} - main_response_mapper", "RES_MAPPER");
 
+    //the request log, with the key id of authenticated requests
+    let key_id = res
+        .extensions()
+        .get::<Ctx>()
+        .map_or("-", |ctx| ctx.key_id());
+    info!(
+        "-->> {:<12} - {req_method} {uri} - key {key_id} - {}",
+        "REQUEST",
+        res.status()
+    );
+
+    //the body limit and timeout layers answer without a body, give them problem details too
+    if res.extensions().get::<Error>().is_none() {
+        match res.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => return Error::FileTooLarge.into_response(),
+            StatusCode::REQUEST_TIMEOUT => return Error::RequestTimeout.into_response(),
+            _ => {}
+        }
+    }
+
     res
 }
 
@@ -87,7 +283,11 @@ async fn main_response_mapper(res: Response) -> Response {
 
 #[cfg(test)]
 mod tests {
+    use crate::config::Config;
+    use crate::ctx::Ctx;
+    use crate::web::mw_auth::{mw_require_auth, Auth};
     use crate::web::routes_files::handler_convert_file;
+    use crate::web::routes_jobs::JobStore;
     use anyhow::anyhow;
     use anyhow::Error;
     use anyhow::Result;
@@ -98,9 +298,13 @@ mod tests {
     use log::info;
     use reqwest::multipart;
     use reqwest::Body;
+    use shiva::core::DocumentType;
     use std::fs;
     use std::io::Cursor;
+    use std::io::Read;
     use std::io::Write;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
     use tokio::fs::File;
     use tokio::net::TcpListener;
     use tower_http::limit::RequestBodyLimitLayer;
@@ -149,7 +353,7 @@ mod tests {
 
         //We form all combinations of incoming and outgoing file formats
         let input_formats = vec!["md", "html", "txt"];
-        let output_formats = vec!["md", "html", "txt", "pdf", "json", "rtf", "docx"];
+        let output_formats = vec!["md", "html", "txt", "pdf", "json", "rtf", "docx", "xml"];
 
         // We go through all the combinations
         for input_format in &input_formats {
@@ -201,6 +405,20 @@ mod tests {
                 // Checking the server response
                 assert_eq!(response.status(), reqwest::StatusCode::OK);
 
+                // The download is named after the upload with the new extension
+                let content_disposition = response
+                    .headers()
+                    .get(reqwest::header::CONTENT_DISPOSITION)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default();
+                assert!(content_disposition.starts_with(&format!(
+                    "attachment; filename=\"test_file.{}\"",
+                    output_format
+                )));
+                assert!(response
+                    .headers()
+                    .contains_key(reqwest::header::CONTENT_TYPE));
+
                 // Verify we got some content back
                 let content = response.bytes().await?;
                 assert!(!content.is_empty(), "Response should not be empty");
@@ -462,4 +680,624 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_error_responses() -> Result<(), Box<dyn std::error::Error>> {
+        let route_input_file = Router::new()
+            .route("/transform/:output_format", post(handler_convert_file))
+            .layer(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(1024));
+        let routes_all = Router::new()
+            .merge(route_input_file)
+            .layer(middleware::map_response(crate::main_response_mapper));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server_handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, routes_all).await;
+        });
+
+        let cases = [
+            ("test_file", "md", "# Title", 400),
+            ("test_file.xyz", "md", "# Title", 415),
+            ("test_file.md", "xyz", "# Title", 415),
+            ("test_file.json", "md", "{ not json", 422),
+            ("test_file.md", "html", &"a".repeat(4096), 413),
+        ];
+        let client = reqwest::Client::new();
+        for (file_name, output_format, file_data, status) in cases {
+            let part = multipart::Part::bytes(file_data.as_bytes().to_vec())
+                .file_name(file_name.to_string());
+            let form = multipart::Form::new().part("file", part);
+
+            let response = client
+                .post(format!("http://{address}/transform/{output_format}"))
+                .multipart(form)
+                .send()
+                .await?;
+
+            assert_eq!(
+                response.status().as_u16(),
+                status,
+                "{file_name} to {output_format}"
+            );
+            assert_eq!(
+                response
+                    .headers()
+                    .get(reqwest::header::CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok()),
+                Some("application/problem+json")
+            );
+            let problem = response.text().await?;
+            assert!(
+                problem.contains(&format!("\"status\":{status}")),
+                "{problem}"
+            );
+        }
+
+        server_handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_batch_to_zip() -> Result<(), Box<dyn std::error::Error>> {
+        let route_input_file = Router::new()
+            .route("/transform/:output_format", post(handler_convert_file))
+            .layer(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(100 * 1024 * 1024));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server_handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, route_input_file).await;
+        });
+
+        // Two documents with the same name and one that cannot be parsed
+        let files = [
+            ("report.md", "# Report"),
+            ("report.txt", "Report"),
+            ("broken.json", "{ not json"),
+        ];
+        let mut form = multipart::Form::new();
+        for (file_name, file_data) in files {
+            let part = multipart::Part::bytes(file_data.as_bytes().to_vec())
+                .file_name(file_name.to_string());
+            form = form.part("file", part);
+        }
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{address}/transform/html"))
+            .multipart(form)
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok()),
+            Some("application/zip")
+        );
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(response.bytes().await?))?;
+        assert!(archive.by_name("report/report.html").is_ok());
+        assert!(archive.by_name("report-2/report.html").is_ok());
+
+        let mut manifest = String::new();
+        archive
+            .by_name("manifest.json")?
+            .read_to_string(&mut manifest)?;
+        let manifest: serde_json::Value = serde_json::from_str(&manifest)?;
+        assert_eq!(manifest[0]["status"], "ok");
+        assert_eq!(manifest[1]["output"], "report-2/report.html");
+        assert_eq!(manifest[2]["source"], "broken.json");
+        assert_eq!(manifest[2]["status"], "error");
+
+        server_handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_convert_raw_body() -> Result<(), Box<dyn std::error::Error>> {
+        let routes = crate::web::routes_convert::routes()
+            .layer(DefaultBodyLimit::disable())
+            .layer(RequestBodyLimitLayer::new(100 * 1024 * 1024))
+            .layer(middleware::map_response(crate::main_response_mapper));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server_handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, routes).await;
+        });
+        let client = reqwest::Client::new();
+        let content_type = |response: &reqwest::Response| {
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        };
+
+        // The query names the formats
+        let response = client
+            .post(format!("http://{address}/convert?from=md&to=html"))
+            .body("# Title")
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.text().await?.contains("<h1>Title</h1>"));
+
+        // The headers name the formats, the Accept header by its q values
+        let response = client
+            .post(format!("http://{address}/convert?name=notes"))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                "text/markdown; charset=utf-8",
+            )
+            .header(
+                reqwest::header::ACCEPT,
+                "text/html;q=0.5, text/plain, */*;q=0.1",
+            )
+            .body("# Title")
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            content_type(&response).as_deref(),
+            Some(DocumentType::Text.mime_type())
+        );
+        assert!(response
+            .headers()
+            .get(reqwest::header::CONTENT_DISPOSITION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.contains("notes.txt")));
+
+        // A CDM document in JSON is rendered
+        let document = shiva::core::Document::parse(
+            &axum::body::Bytes::from("# Title\n\nText"),
+            DocumentType::Markdown,
+        )?;
+        let response = client
+            .post(format!("http://{address}/convert?to=md"))
+            .header(
+                reqwest::header::CONTENT_TYPE,
+                crate::web::routes_convert::CDM_MEDIA_TYPE,
+            )
+            .body(serde_json::to_vec(&document)?)
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.text().await?.contains("# Title"));
+
+        // Unknown or missing formats
+        for (query, headers, status) in [
+            ("to=html", vec![], reqwest::StatusCode::BAD_REQUEST),
+            (
+                "from=md",
+                vec![(reqwest::header::ACCEPT, "image/png")],
+                reqwest::StatusCode::NOT_ACCEPTABLE,
+            ),
+            (
+                "to=html",
+                vec![(reqwest::header::CONTENT_TYPE, "image/png")],
+                reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                "from=cdm&to=html",
+                vec![],
+                reqwest::StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+        ] {
+            let mut request = client.post(format!("http://{address}/convert?{query}"));
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            let response = request.body("# Title").send().await?;
+            assert_eq!(response.status(), status, "{query}");
+            assert_eq!(
+                content_type(&response).as_deref(),
+                Some("application/problem+json")
+            );
+        }
+
+        server_handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_auth() -> Result<(), Box<dyn std::error::Error>> {
+        let auth = Auth::from_toml(
+            r#"
+            jwt_secret = "test secret"
+
+            [[keys]]
+            id = "tests"
+            key = "test-key"
+            requests_per_minute = 2
+            "#,
+        )?;
+        let route_input_file = Router::new()
+            .route("/transform/:output_format", post(handler_convert_file))
+            .route_layer(middleware::from_fn_with_state(
+                auth.clone(),
+                mw_require_auth,
+            ));
+        let routes_all = Router::new()
+            .merge(crate::web::routes_login::routes(auth))
+            .merge(route_input_file)
+            .layer(middleware::map_response(crate::main_response_mapper));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server_handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, routes_all).await;
+        });
+
+        let client = reqwest::Client::new();
+        let convert = |auth: Option<(&str, String)>| {
+            let part = multipart::Part::bytes(b"# Title".to_vec()).file_name("test_file.md");
+            let mut request = client
+                .post(format!("http://{address}/transform/html"))
+                .multipart(multipart::Form::new().part("file", part));
+            if let Some((name, value)) = auth {
+                request = request.header(name, value);
+            }
+            request.send()
+        };
+
+        assert_eq!(convert(None).await?.status().as_u16(), 401);
+        let wrong_key = Some(("x-api-key", "wrong-key".to_string()));
+        assert_eq!(convert(wrong_key).await?.status().as_u16(), 401);
+        let key = Some(("x-api-key", "test-key".to_string()));
+        assert_eq!(convert(key.clone()).await?.status().as_u16(), 200);
+
+        let response = client
+            .post(format!("http://{address}/api/login"))
+            .json(&serde_json::json!({ "api_key": "test-key" }))
+            .send()
+            .await?;
+        assert_eq!(response.status().as_u16(), 200);
+        let login: serde_json::Value = response.json().await?;
+        let token = login["token"].as_str().ok_or("no token")?;
+
+        let bearer = Some(("authorization", format!("Bearer {token}")));
+        assert_eq!(convert(bearer).await?.status().as_u16(), 200);
+        let bad_token = Some(("authorization", format!("Bearer {token}x")));
+        assert_eq!(convert(bad_token).await?.status().as_u16(), 401);
+
+        // Two requests a minute are allowed for the key
+        assert_eq!(convert(key).await?.status().as_u16(), 429);
+
+        server_handle.abort();
+        Ok(())
+    }
+
+    #[test]
+    fn test_config() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config = Config::from_toml(
+            r#"
+            port = 9000
+            formats = ["md", "HTML"]
+
+            [tls]
+            cert = "cert.pem"
+            key = "key.pem"
+            "#,
+        )?;
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.body_limit, 100 * 1024 * 1024);
+        assert!(config.format_enabled(DocumentType::HTML));
+        assert!(!config.format_enabled(DocumentType::PDF));
+
+        // The environment overrides the file
+        let env = [("SHIVA_PORT", "9001"), ("SHIVA_FORMATS", "pdf")];
+        config.apply_env(|name| {
+            env.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        })?;
+        assert_eq!(config.port, 9001);
+        assert!(config.format_enabled(DocumentType::PDF));
+        assert!(!config.format_enabled(DocumentType::HTML));
+
+        assert!(Config::from_toml("formats = [\"doc\"]").is_err());
+        assert!(Config::from_toml("max_jobs = 0").is_err());
+        assert!(Config::from_toml("max_pending_jobs = 0").is_err());
+        assert!(Config::from_toml("unknown = 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits() -> Result<(), Box<dyn std::error::Error>> {
+        use crate::error::Error as ShivaError;
+        use crate::limits::{check_document, check_input, with_timeout};
+        use std::time::Duration;
+
+        let config = Config::from_toml(
+            r#"
+            max_depth = 4
+            max_elements = 10
+            max_unpacked_size = 1000
+            max_image_pixels = 100
+            "#,
+        )?;
+
+        // Markup is refused by the nesting of its tags before it is parsed
+        let deep = "<div>".repeat(5);
+        assert!(matches!(
+            check_input(DocumentType::HTML, deep.as_bytes(), &config),
+            Err(ShivaError::TooDeep(4))
+        ));
+        let html = format!(
+            "<html><body><ul>{}</ul><p>a<br>b<p>c<script>if (a<b) {{}}</script></body></html>",
+            "<li>item".repeat(20)
+        );
+        assert!(check_input(DocumentType::HTML, html.as_bytes(), &config).is_ok());
+        assert!(check_input(DocumentType::XML, deep.as_bytes(), &config).is_err());
+
+        // A ZIP container by the size it unpacks to
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        archive.start_file("word/document.xml", zip::write::FileOptions::default())?;
+        archive.write_all(&[b' '; 2000])?;
+        let docx = archive.finish()?.into_inner();
+        assert!(matches!(
+            check_input(DocumentType::DOCX, &docx, &config),
+            Err(ShivaError::UnpackedTooLarge(1000))
+        ));
+        // The sizes the headers declare are not trusted
+        let mut bomb = docx.clone();
+        let central = bomb.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        bomb[22..26].copy_from_slice(&10u32.to_le_bytes());
+        bomb[central + 24..central + 28].copy_from_slice(&10u32.to_le_bytes());
+        assert!(matches!(
+            check_input(DocumentType::DOCX, &bomb, &config),
+            Err(ShivaError::UnpackedTooLarge(1000))
+        ));
+
+        // The parsed document by its elements, their nesting and its images
+        let parse = |text: &str| {
+            shiva::core::Document::parse(
+                &axum::body::Bytes::from(text.to_string()),
+                DocumentType::Markdown,
+            )
+        };
+        assert!(check_document(&parse("# Title\n\nText")?, &config).is_ok());
+        assert!(matches!(
+            check_document(&parse(&"Text\n\n".repeat(11))?, &config),
+            Err(ShivaError::TooManyElements(10))
+        ));
+        assert!(matches!(
+            check_document(&parse("- a\n  - b\n    - c\n      - d")?, &config),
+            Err(ShivaError::TooDeep(4))
+        ));
+        let image = shiva::core::Element::Image(shiva::core::ImageData::new(
+            axum::body::Bytes::from(fs::read("../lib/test/data/small.png")?),
+            "title".to_string(),
+            "alt".to_string(),
+            "small.png".to_string(),
+            "center".to_string(),
+            shiva::core::ImageDimension::default(),
+        ));
+        assert!(matches!(
+            check_document(&shiva::core::Document::new(vec![image]), &config),
+            Err(ShivaError::ImageTooLarge(100))
+        ));
+
+        // A conversion that takes too long keeps its converter until it finishes
+        let result = with_timeout(Duration::from_millis(10), 1000, || {
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(())
+        });
+        assert!(matches!(result, Err(ShivaError::ConversionTimeout(_))));
+        assert!(matches!(
+            with_timeout(Duration::from_secs(1), 1, || Ok(1)),
+            Err(ShivaError::ServerBusy)
+        ));
+        assert!(with_timeout(Duration::from_secs(1), 1000, || Ok(1)).is_ok());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_health_and_metrics() -> Result<(), Box<dyn std::error::Error>> {
+        let ready = Arc::new(AtomicBool::new(false));
+        let routes_all = Router::new()
+            .merge(crate::web::routes_health::routes(ready.clone()))
+            .route("/transform/:output_format", post(handler_convert_file));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server_handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, routes_all).await;
+        });
+
+        let client = reqwest::Client::new();
+        let status = |path: &'static str| {
+            let request = client.get(format!("http://{address}{path}")).send();
+            async move { Ok::<_, reqwest::Error>(request.await?.status().as_u16()) }
+        };
+        assert_eq!(status("/healthz").await?, 200);
+        assert_eq!(status("/readyz").await?, 503);
+        ready.store(true, Ordering::Relaxed);
+        assert_eq!(status("/readyz").await?, 200);
+
+        let part = multipart::Part::bytes(b"# Title".to_vec()).file_name("test_file.md");
+        client
+            .post(format!("http://{address}/transform/txt"))
+            .multipart(multipart::Form::new().part("file", part))
+            .send()
+            .await?;
+
+        let metrics = client
+            .get(format!("http://{address}/metrics"))
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(metrics.contains("shiva_conversions_total{from=\"md\",to=\"txt\"}"));
+        assert!(metrics.contains(
+            "shiva_conversion_duration_seconds_bucket{from=\"md\",to=\"txt\",le=\"+Inf\"}"
+        ));
+        assert!(metrics.contains("# TYPE shiva_conversion_input_bytes histogram"));
+
+        server_handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_formats_and_openapi() -> Result<(), Box<dyn std::error::Error>> {
+        let routes_all = Router::new()
+            .merge(crate::web::routes_formats::routes())
+            .merge(crate::web::routes_docs::routes(true, true));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server_handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, routes_all).await;
+        });
+
+        let client = reqwest::Client::new();
+        let formats: serde_json::Value = client
+            .get(format!("http://{address}/formats"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        let formats = formats.as_array().ok_or("not an array")?;
+        let xls = formats
+            .iter()
+            .find(|format| format["name"] == "xls")
+            .ok_or("no xls")?;
+        assert_eq!(xls["parse"], true);
+        assert_eq!(xls["generate"], false);
+        assert_eq!(xls["parse_elements"], serde_json::json!(["table"]));
+        let text = formats
+            .iter()
+            .find(|format| format["name"] == "text")
+            .ok_or("no text")?;
+        assert_eq!(
+            text["parse_elements"],
+            serde_json::json!(["header", "paragraph", "list", "table", "hyperlink"])
+        );
+        let markdown = formats
+            .iter()
+            .find(|format| format["name"] == "markdown")
+            .ok_or("no markdown")?;
+        assert_eq!(
+            markdown["extensions"],
+            serde_json::json!(["markdown", "md"])
+        );
+
+        let openapi: serde_json::Value = client
+            .get(format!("http://{address}/openapi.json"))
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(openapi["openapi"], "3.0.3");
+        let transform = &openapi["paths"]["/transform/{output_format}"]["post"];
+        let output_formats = transform["parameters"][0]["schema"]["enum"]
+            .as_array()
+            .ok_or("no output formats")?;
+        assert!(output_formats.contains(&serde_json::json!("docx")));
+        assert!(!output_formats.contains(&serde_json::json!("xls")));
+        assert!(openapi["paths"]["/api/login"].is_object());
+
+        let docs = client.get(format!("http://{address}/docs")).send().await?;
+        assert_eq!(docs.status().as_u16(), 200);
+
+        server_handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_jobs() -> Result<(), Box<dyn std::error::Error>> {
+        let job_store = JobStore::new(1, 10, std::time::Duration::from_secs(60));
+        let routes_all = crate::web::routes_jobs::routes(job_store.clone())
+            .layer(axum::Extension(Ctx::new("owner".to_string())))
+            .layer(middleware::map_response(crate::main_response_mapper));
+        let routes_other = crate::web::routes_jobs::routes(job_store)
+            .layer(axum::Extension(Ctx::new("other".to_string())));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?;
+        let server_handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, routes_all).await;
+        });
+        let other_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let other_address = other_listener.local_addr()?;
+        let other_handle = tokio::spawn(async move {
+            let _ = axum::serve(other_listener, routes_other).await;
+        });
+
+        let client = reqwest::Client::new();
+        let part = multipart::Part::bytes(b"# Test Document".to_vec()).file_name("test_file.md");
+        let form = multipart::Form::new().part("file", part);
+        let response = client
+            .post(format!("http://{address}/jobs?output_format=html"))
+            .multipart(form)
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or("no Location header")?
+            .to_string();
+
+        // Polling until the conversion is done
+        let mut status = String::new();
+        for _ in 0..50 {
+            status = client
+                .get(format!("http://{address}{location}"))
+                .send()
+                .await?
+                .text()
+                .await?;
+            if status.contains("\"status\":\"done\"") {
+                break;
+            }
+            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        }
+        assert!(status.contains("\"progress\":100"), "{status}");
+
+        let response = client
+            .get(format!("http://{address}{location}/result"))
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert!(response.text().await?.contains("Test Document"));
+
+        // The jobs of another key are not found
+        for path in [location.clone(), format!("{location}/result")] {
+            let response = client
+                .get(format!("http://{other_address}{path}"))
+                .send()
+                .await?;
+            assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+        }
+
+        let response = client
+            .get(format!("http://{address}/jobs/unknown"))
+            .send()
+            .await?;
+        assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+        server_handle.abort();
+        other_handle.abort();
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_job_queue() {
+        use crate::error::Error as ShivaError;
+
+        // The job is still queued, the runtime has not run it yet
+        let job_store = JobStore::new(1, 1, std::time::Duration::from_secs(60));
+        assert!(job_store.submit(vec![], DocumentType::HTML, None).is_ok());
+        assert!(matches!(
+            job_store.submit(vec![], DocumentType::HTML, None),
+            Err(ShivaError::TooManyJobs(1))
+        ));
+    }
 }
@@ -0,0 +1,136 @@
+use crate::error::Error;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+const DURATION_BUCKETS: &[f64] = &[0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0];
+const SIZE_BUCKETS: &[f64] = &[
+    1024.0,
+    10.0 * 1024.0,
+    100.0 * 1024.0,
+    1024.0 * 1024.0,
+    10.0 * 1024.0 * 1024.0,
+    100.0 * 1024.0 * 1024.0,
+];
+
+#[derive(Debug)]
+struct Histogram {
+    buckets: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(buckets: &'static [f64]) -> Self {
+        Histogram {
+            buckets,
+            counts: vec![0; buckets.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.buckets.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        for (bound, count) in self.buckets.iter().zip(&self.counts) {
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {count}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {}", self.count);
+    }
+}
+
+//conversion metrics by input and output format, ordered for a stable output
+#[derive(Debug, Default)]
+struct Metrics {
+    conversions: BTreeMap<(String, String), u64>,
+    failures: BTreeMap<(String, String, String), u64>,
+    durations: BTreeMap<(String, String), Histogram>,
+    input_sizes: BTreeMap<String, Histogram>,
+}
+
+static METRICS: LazyLock<Mutex<Metrics>> = LazyLock::new(Default::default);
+
+//records one conversion, `error` is set when it failed
+pub fn record_conversion(
+    from: &str,
+    to: &str,
+    input_size: usize,
+    elapsed: Duration,
+    error: Option<&Error>,
+) {
+    let Ok(mut metrics) = METRICS.lock() else {
+        return;
+    };
+    let formats = (from.to_string(), to.to_string());
+
+    *metrics.conversions.entry(formats.clone()).or_default() += 1;
+    if let Some(error) = error {
+        let key = (from.to_string(), to.to_string(), error.as_ref().to_string());
+        *metrics.failures.entry(key).or_default() += 1;
+    }
+    metrics
+        .durations
+        .entry(formats)
+        .or_insert_with(|| Histogram::new(DURATION_BUCKETS))
+        .observe(elapsed.as_secs_f64());
+    metrics
+        .input_sizes
+        .entry(from.to_string())
+        .or_insert_with(|| Histogram::new(SIZE_BUCKETS))
+        .observe(input_size as f64);
+}
+
+//the metrics in the Prometheus text format
+pub fn render() -> String {
+    let mut out = String::new();
+    let Ok(metrics) = METRICS.lock() else {
+        return out;
+    };
+
+    out.push_str("# HELP shiva_conversions_total Documents converted, successfully or not.\n");
+    out.push_str("# TYPE shiva_conversions_total counter\n");
+    for ((from, to), count) in &metrics.conversions {
+        let _ = writeln!(
+            out,
+            "shiva_conversions_total{{from=\"{from}\",to=\"{to}\"}} {count}"
+        );
+    }
+
+    out.push_str("# HELP shiva_conversion_failures_total Conversions that failed, by error.\n");
+    out.push_str("# TYPE shiva_conversion_failures_total counter\n");
+    for ((from, to, error), count) in &metrics.failures {
+        let labels = format!("from=\"{from}\",to=\"{to}\",error=\"{error}\"");
+        let _ = writeln!(out, "shiva_conversion_failures_total{{{labels}}} {count}");
+    }
+
+    out.push_str(
+        "# HELP shiva_conversion_duration_seconds Time to parse and generate a document.\n",
+    );
+    out.push_str("# TYPE shiva_conversion_duration_seconds histogram\n");
+    for ((from, to), histogram) in &metrics.durations {
+        let labels = format!("from=\"{from}\",to=\"{to}\"");
+        histogram.render(&mut out, "shiva_conversion_duration_seconds", &labels);
+    }
+
+    out.push_str("# HELP shiva_conversion_input_bytes Size of the converted documents.\n");
+    out.push_str("# TYPE shiva_conversion_input_bytes histogram\n");
+    for (from, histogram) in &metrics.input_sizes {
+        let labels = format!("from=\"{from}\"");
+        histogram.render(&mut out, "shiva_conversion_input_bytes", &labels);
+    }
+
+    out
+}
@@ -1,3 +1,15 @@
+pub mod mw_auth;
+
 pub mod routes_login;
 
+pub mod routes_convert;
+
+pub mod routes_docs;
+
 pub mod routes_files;
+
+pub mod routes_formats;
+
+pub mod routes_health;
+
+pub mod routes_jobs;
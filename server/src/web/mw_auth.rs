@@ -0,0 +1,213 @@
+use crate::ctx::Ctx;
+use crate::error::{Error, Result};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap};
+use axum::middleware::Next;
+use axum::response::Response;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+const MINUTE: Duration = Duration::from_secs(60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+//the auth configuration file, in TOML:
+//
+//  jwt_secret = "..."        # optional, enables bearer tokens and `/api/login`
+//  token_ttl = 3600          # seconds a token is valid
+//
+//  [[keys]]
+//  id = "reports"            # logged with each request, never the key itself
+//  key = "..."
+//  requests_per_minute = 60  # optional
+//  daily_quota = 10000       # optional, requests per day
+#[derive(Debug, Deserialize)]
+struct AuthConfig {
+    jwt_secret: Option<String>,
+    #[serde(default = "default_token_ttl")]
+    token_ttl: u64,
+    #[serde(default)]
+    keys: Vec<ApiKey>,
+}
+
+fn default_token_ttl() -> u64 {
+    3600
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiKey {
+    id: String,
+    key: String,
+    requests_per_minute: Option<u32>,
+    daily_quota: Option<u32>,
+}
+
+//the claims of the tokens issued by `/api/login`, `sub` is the key id
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    exp: u64,
+}
+
+//requests counted in the current minute and day of a key
+#[derive(Debug, Default)]
+struct Usage {
+    minute: Option<(Instant, u32)>,
+    day: Option<(Instant, u32)>,
+}
+
+pub struct Auth {
+    keys: HashMap<String, ApiKey>,
+    jwt: Option<(EncodingKey, DecodingKey)>,
+    token_ttl: u64,
+    usage: Mutex<HashMap<String, Usage>>,
+}
+
+impl Auth {
+    pub fn load(path: &str) -> anyhow::Result<Arc<Auth>> {
+        Auth::from_toml(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_toml(config: &str) -> anyhow::Result<Arc<Auth>> {
+        let config: AuthConfig = toml::from_str(config)?;
+
+        let mut keys = HashMap::new();
+        for api_key in config.keys {
+            if api_key.key.is_empty() {
+                return Err(anyhow::anyhow!("The key '{}' is empty", api_key.id));
+            }
+            if keys.values().any(|other: &ApiKey| other.id == api_key.id) {
+                return Err(anyhow::anyhow!("The key id '{}' is repeated", api_key.id));
+            }
+            keys.insert(api_key.key.clone(), api_key);
+        }
+
+        let jwt = config.jwt_secret.map(|secret| {
+            (
+                EncodingKey::from_secret(secret.as_bytes()),
+                DecodingKey::from_secret(secret.as_bytes()),
+            )
+        });
+
+        Ok(Arc::new(Auth {
+            keys,
+            jwt,
+            token_ttl: config.token_ttl,
+            usage: Mutex::new(HashMap::new()),
+        }))
+    }
+
+    //an `X-API-Key` header, or else a bearer token issued by `/api/login`
+    fn authenticate(&self, headers: &HeaderMap) -> Result<&ApiKey> {
+        if let Some(key) = headers.get(API_KEY_HEADER) {
+            let key = key.to_str().map_err(|_| Error::AuthInvalid)?;
+            return self.keys.get(key).ok_or(Error::AuthInvalid);
+        }
+
+        let token = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(Error::AuthMissing)?;
+        let (_, decoding_key) = self.jwt.as_ref().ok_or(Error::AuthInvalid)?;
+        let claims = jsonwebtoken::decode::<Claims>(
+            token.trim(),
+            decoding_key,
+            &Validation::new(Algorithm::HS256),
+        )
+        .map_err(|e| {
+            info!("-->> {:<12} - rejected token: {e}", "AUTH");
+            Error::AuthInvalid
+        })?
+        .claims;
+
+        //a token stops working as soon as its key is removed from the configuration
+        self.keys
+            .values()
+            .find(|api_key| api_key.id == claims.sub)
+            .ok_or(Error::AuthInvalid)
+    }
+
+    //counts the request against the limits of the key, requests over a limit are not counted
+    fn check_limits(&self, api_key: &ApiKey) -> Result<()> {
+        let mut usage = self.usage.lock().map_err(|_| {
+            error!("The usage lock is poisoned");
+            Error::RateLimited
+        })?;
+        let usage = usage.entry(api_key.id.clone()).or_default();
+        let now = Instant::now();
+
+        let minute = window(&mut usage.minute, now, MINUTE);
+        if api_key
+            .requests_per_minute
+            .is_some_and(|limit| *minute >= limit)
+        {
+            return Err(Error::RateLimited);
+        }
+        let day = window(&mut usage.day, now, DAY);
+        if api_key.daily_quota.is_some_and(|quota| *day >= quota) {
+            return Err(Error::QuotaExceeded);
+        }
+
+        *minute += 1;
+        *day += 1;
+        Ok(())
+    }
+
+    //a bearer token for the key, valid for `token_ttl` seconds
+    pub fn login(&self, key: &str) -> Result<(String, u64)> {
+        let (encoding_key, _) = self.jwt.as_ref().ok_or(Error::TokensDisabled)?;
+        let api_key = self.keys.get(key).ok_or(Error::LoginFail)?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let claims = Claims {
+            sub: api_key.id.clone(),
+            exp: now + self.token_ttl,
+        };
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, encoding_key)
+            .map_err(|e| {
+                error!("Failed to sign a token: {e}");
+                Error::LoginFail
+            })?;
+
+        info!("-->> {:<12} - token issued for key {}", "AUTH", api_key.id);
+        Ok((token, self.token_ttl))
+    }
+}
+
+//the counter of a fixed window, restarted once `length` has passed
+fn window(counter: &mut Option<(Instant, u32)>, now: Instant, length: Duration) -> &mut u32 {
+    if counter.is_some_and(|(start, _)| now.duration_since(start) >= length) {
+        *counter = None;
+    }
+    &mut counter.get_or_insert((now, 0)).1
+}
+
+pub async fn mw_require_auth(
+    State(auth): State<Arc<Auth>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response> {
+    let api_key = auth.authenticate(req.headers())?;
+    auth.check_limits(api_key)?;
+    info!(
+        "-->> {:<12} - mw_require_auth - key {}",
+        "MIDDLEWARE", api_key.id
+    );
+
+    //the key id goes with the response too, for the request log of the response mapper
+    let ctx = Ctx::new(api_key.id.clone());
+    req.extensions_mut().insert(ctx.clone());
+    let mut res = next.run(req).await;
+    res.extensions_mut().insert(ctx);
+
+    Ok(res)
+}
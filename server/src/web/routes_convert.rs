@@ -0,0 +1,153 @@
+use crate::config::config;
+use crate::error::{Error, Result};
+use crate::web::routes_files::{convert_all, output_document_type, unpacking, Conversion};
+use axum::body::Bytes;
+use axum::extract::Query;
+use axum::http::{header, HeaderMap};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::Router;
+use log::{error, info};
+use serde::Deserialize;
+use shiva::core::{Document, DocumentType};
+
+//the media type of a Common Document Model document in JSON, also selected with `from=cdm`
+pub(crate) const CDM_MEDIA_TYPE: &str = "application/vnd.shiva.cdm+json";
+
+#[derive(Debug, Deserialize)]
+pub struct ConvertParams {
+    from: Option<String>,
+    to: Option<String>,
+    name: Option<String>,
+}
+
+//what the body of a request holds
+#[derive(Debug)]
+enum Input {
+    File(DocumentType),
+    Zip,
+    Cdm,
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/convert", post(handler_convert))
+}
+
+//converts the raw body: the input format is `from` or the Content-Type,
+//the output format is `to` or the preferred type of the Accept header
+pub async fn handler_convert(
+    Query(params): Query<ConvertParams>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse> {
+    let input = input_format(params.from.as_deref(), &headers)?;
+    let output_format = match params.to.as_deref() {
+        Some(to) => output_document_type(to)?,
+        None => accepted_format(&headers)?,
+    };
+    let file_name = params.name.unwrap_or("Shiva_convert".to_string());
+
+    info!(
+        "-->> {:<12} - handler_convert {input:?} - output_extension_{output_format}",
+        "HANDLER"
+    );
+
+    let conversions = match input {
+        Input::File(format) => vec![Conversion::new(file_name, format, body)],
+        Input::Zip => unpacking(body)?,
+        Input::Cdm => {
+            let document: Document = serde_json::from_slice(&body).map_err(|e| {
+                error!("Failed to read the CDM document: {e}");
+                Error::FailParseDocument(e.to_string())
+            })?;
+            vec![Conversion::from_document(file_name, document, body.len())]
+        }
+    };
+
+    //a panic in a converter fails the blocking task instead of the handler
+    tokio::task::spawn_blocking(move || convert_all(conversions, output_format, |_| {}))
+        .await
+        .map_err(|e| {
+            error!("Converter task failed: {e}");
+            Error::FailParseDocument("the document could not be processed".to_string())
+        })?
+}
+
+//the media type without its parameters, such as the charset
+fn essence(media_type: &str) -> String {
+    media_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+//the input format from `from`, or else from the Content-Type
+fn input_format(from: Option<&str>, headers: &HeaderMap) -> Result<Input> {
+    if let Some(from) = from {
+        let from = from.to_lowercase();
+        return match from.as_str() {
+            "zip" => Ok(Input::Zip),
+            "cdm" => Ok(Input::Cdm),
+            _ => DocumentType::from_extension(&from)
+                .filter(|format| config().format_enabled(*format))
+                .map(Input::File)
+                .ok_or_else(|| Error::UnsupportedFormat(from.clone())),
+        };
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(essence)
+        .filter(|content_type| content_type != "application/octet-stream")
+        .ok_or(Error::InputFormatMissing)?;
+
+    match content_type.as_str() {
+        "application/zip" => Ok(Input::Zip),
+        CDM_MEDIA_TYPE => Ok(Input::Cdm),
+        _ => DocumentType::variants()
+            .iter()
+            .find(|format| {
+                config().format_enabled(**format) && essence(format.mime_type()) == content_type
+            })
+            .map(|format| Input::File(*format))
+            .ok_or(Error::UnsupportedFormat(content_type)),
+    }
+}
+
+//the generated format the client prefers, by the q values of the Accept header
+//and then by its order; wildcards do not name a format and are ignored
+fn accepted_format(headers: &HeaderMap) -> Result<DocumentType> {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+
+    let mut ranges: Vec<(f32, String)> = accept
+        .split(',')
+        .map(|range| {
+            let quality = range
+                .split(';')
+                .skip(1)
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .and_then(|q| q.trim().parse().ok())
+                .unwrap_or(1.0);
+            (quality, essence(range))
+        })
+        .filter(|(quality, _)| *quality > 0.0)
+        .collect();
+    ranges.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    ranges
+        .iter()
+        .find_map(|(_, media_type)| {
+            DocumentType::variants().iter().copied().find(|format| {
+                format.can_generate()
+                    && config().format_enabled(*format)
+                    && essence(format.mime_type()) == *media_type
+            })
+        })
+        .ok_or(Error::NotAcceptable)
+}
@@ -0,0 +1,358 @@
+use crate::config::config;
+use crate::web::mw_auth::API_KEY_HEADER;
+use crate::web::routes_convert::CDM_MEDIA_TYPE;
+use crate::web::routes_formats::ELEMENTS;
+use axum::extract::State;
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use shiva::core::DocumentType;
+use std::sync::Arc;
+
+//`/openapi.json`, and the Swagger UI at `/docs` when `swagger_ui` is set
+pub fn routes(auth_enabled: bool, swagger_ui: bool) -> Router {
+    let router = Router::new()
+        .route("/openapi.json", get(handler_openapi))
+        .with_state(Arc::new(openapi(auth_enabled)));
+
+    if swagger_ui {
+        router.route("/docs", get(handler_swagger_ui))
+    } else {
+        router
+    }
+}
+
+async fn handler_openapi(State(document): State<Arc<Value>>) -> Json<Value> {
+    Json(document.as_ref().clone())
+}
+
+async fn handler_swagger_ui() -> Html<&'static str> {
+    Html(SWAGGER_UI)
+}
+
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>Shiva API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+//the OpenAPI 3 document of the server, built from the formats of this build and configuration
+pub(crate) fn openapi(auth_enabled: bool) -> Value {
+    let input_extensions: Vec<&str> = {
+        let mut extensions: Vec<_> = DocumentType::supported_extensions()
+            .into_iter()
+            .filter(|extension| {
+                DocumentType::from_extension(extension)
+                    .is_some_and(|format| config().format_enabled(format))
+            })
+            .chain(["zip"])
+            .collect();
+        extensions.sort_unstable();
+        extensions
+    };
+    let raw_input_formats: Vec<&str> = input_extensions.iter().copied().chain(["cdm"]).collect();
+    let output_formats: Vec<&str> = DocumentType::variants()
+        .iter()
+        .filter(|format| format.can_generate() && config().format_enabled(**format))
+        .map(|format| format.extension())
+        .collect();
+
+    let problem = |description: &str| {
+        json!({
+            "description": description,
+            "content": { "application/problem+json": {
+                "schema": { "$ref": "#/components/schemas/Problem" }
+            } }
+        })
+    };
+    let upload = json!({
+        "required": true,
+        "content": { "multipart/form-data": {
+            "schema": {
+                "type": "object",
+                "required": ["file"],
+                "properties": { "file": {
+                    "type": "array",
+                    "items": { "type": "string", "format": "binary" },
+                    "description": format!(
+                        "One or more documents, or ZIP archives of documents and their images. \
+                         Extensions: {}",
+                        input_extensions.join(", ")
+                    )
+                } }
+            }
+        } }
+    });
+    let converted = json!({
+        "description": "The converted document, or a ZIP of the results with a `manifest.json` \
+                        when several documents were uploaded or the output has images",
+        "headers": { "Content-Disposition": { "schema": { "type": "string" } } },
+        "content": {
+            "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+            "application/zip": { "schema": { "type": "string", "format": "binary" } }
+        }
+    });
+    let output_format = |location: &str| {
+        json!({
+            "name": "output_format",
+            "in": location,
+            "required": true,
+            "schema": { "type": "string", "enum": output_formats }
+        })
+    };
+    let job_id = json!({
+        "name": "id",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string" }
+    });
+    let security = if auth_enabled {
+        json!([{ "apiKey": [] }, { "bearer": [] }])
+    } else {
+        json!([])
+    };
+
+    let mut document = json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Shiva",
+            "description": "Converts documents between formats through the Common Document Model",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": {
+            "/transform/{output_format}": { "post": {
+                "summary": "Convert documents",
+                "security": security,
+                "parameters": [output_format("path")],
+                "requestBody": upload,
+                "responses": {
+                    "200": converted,
+                    "400": problem("The upload is malformed"),
+                    "413": problem("The upload exceeds the size limit"),
+                    "415": problem("A format is not supported"),
+                    "422": problem("The document could not be parsed"),
+                    "503": problem("Every converter is busy")
+                }
+            } },
+            "/convert": { "post": {
+                "summary": "Convert the raw body",
+                "description": "The input format is `from` or the Content-Type, the output \
+                                format is `to` or the preferred type of the Accept header. \
+                                With `from=cdm` or the Content-Type of a CDM document the \
+                                body is a Common Document Model document in JSON.",
+                "security": security,
+                "parameters": [
+                    {
+                        "name": "from",
+                        "in": "query",
+                        "schema": { "type": "string", "enum": raw_input_formats }
+                    },
+                    {
+                        "name": "to",
+                        "in": "query",
+                        "schema": { "type": "string", "enum": output_formats }
+                    },
+                    {
+                        "name": "name",
+                        "in": "query",
+                        "description": "The file name of the result, without extension",
+                        "schema": { "type": "string" }
+                    }
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": {
+                            "schema": { "type": "string", "format": "binary" }
+                        },
+                        CDM_MEDIA_TYPE: { "schema": { "type": "object" } }
+                    }
+                },
+                "responses": {
+                    "200": converted,
+                    "400": problem("Neither `from` nor the Content-Type names the input"),
+                    "406": problem("Neither `to` nor the Accept header names an output format"),
+                    "413": problem("The body exceeds the size limit"),
+                    "415": problem("A format is not supported"),
+                    "422": problem("The document could not be parsed"),
+                    "503": problem("Every converter is busy")
+                }
+            } },
+            "/jobs": { "post": {
+                "summary": "Convert documents in the background",
+                "security": security,
+                "parameters": [output_format("query")],
+                "requestBody": upload,
+                "responses": {
+                    "202": {
+                        "description": "The job was queued, `Location` is its status",
+                        "headers": { "Location": { "schema": { "type": "string" } } },
+                        "content": { "application/json": {
+                            "schema": { "$ref": "#/components/schemas/Job" }
+                        } }
+                    },
+                    "400": problem("The upload is malformed"),
+                    "415": problem("A format is not supported"),
+                    "503": problem("Too many jobs are pending")
+                }
+            } },
+            "/jobs/{id}": { "get": {
+                "summary": "The status of a job",
+                "security": security,
+                "parameters": [job_id],
+                "responses": {
+                    "200": {
+                        "description": "The job",
+                        "content": { "application/json": {
+                            "schema": { "$ref": "#/components/schemas/Job" }
+                        } }
+                    },
+                    "404": problem("There is no such job, or it has expired")
+                }
+            } },
+            "/jobs/{id}/result": { "get": {
+                "summary": "The result of a finished job",
+                "security": security,
+                "parameters": [job_id],
+                "responses": {
+                    "200": converted,
+                    "404": problem("There is no such job, or it has expired"),
+                    "409": problem("The job has not finished yet"),
+                    "422": problem("The document could not be parsed")
+                }
+            } },
+            "/formats": { "get": {
+                "summary": "The formats and elements the server reads and writes",
+                "responses": { "200": {
+                    "description": "The formats",
+                    "content": { "application/json": { "schema": {
+                        "type": "array",
+                        "items": { "$ref": "#/components/schemas/Format" }
+                    } } }
+                } }
+            } },
+            "/healthz": { "get": {
+                "summary": "Liveness",
+                "responses": { "200": { "description": "The server is up" } }
+            } },
+            "/readyz": { "get": {
+                "summary": "Readiness",
+                "responses": {
+                    "200": { "description": "The server takes requests" },
+                    "503": { "description": "The server is starting or shutting down" }
+                }
+            } },
+            "/metrics": { "get": {
+                "summary": "Prometheus metrics",
+                "responses": { "200": {
+                    "description": "The metrics",
+                    "content": { "text/plain": { "schema": { "type": "string" } } }
+                } }
+            } }
+        },
+        "components": {
+            "schemas": {
+                "Problem": {
+                    "type": "object",
+                    "description": "RFC 9457 problem details",
+                    "properties": {
+                        "type": {
+                            "type": "string",
+                            "example": "urn:shiva:error:UnsupportedFormat"
+                        },
+                        "title": { "type": "string" },
+                        "status": { "type": "integer" },
+                        "detail": { "type": "string" }
+                    }
+                },
+                "Job": {
+                    "type": "object",
+                    "properties": {
+                        "id": { "type": "string" },
+                        "status": {
+                            "type": "string",
+                            "enum": ["queued", "running", "done", "failed"]
+                        },
+                        "progress": { "type": "integer", "minimum": 0, "maximum": 100 },
+                        "error": { "type": "string" }
+                    }
+                },
+                "Format": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "extensions": { "type": "array", "items": { "type": "string" } },
+                        "mime_type": { "type": "string" },
+                        "parse": { "type": "boolean" },
+                        "generate": { "type": "boolean" },
+                        "parse_elements": { "$ref": "#/components/schemas/Elements" },
+                        "generate_elements": { "$ref": "#/components/schemas/Elements" }
+                    }
+                },
+                "Elements": {
+                    "type": "array",
+                    "items": { "type": "string", "enum": ELEMENTS }
+                },
+                "ManifestEntry": {
+                    "type": "object",
+                    "description": "An entry of `manifest.json` in a ZIP of results",
+                    "properties": {
+                        "source": { "type": "string" },
+                        "status": { "type": "string", "enum": ["ok", "error"] },
+                        "output": { "type": "string" },
+                        "files": { "type": "array", "items": { "type": "string" } },
+                        "error": { "type": "string" }
+                    }
+                }
+            }
+        }
+    });
+
+    if auth_enabled {
+        document["paths"]["/api/login"] = json!({ "post": {
+            "summary": "Exchange an API key for a bearer token",
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": {
+                    "type": "object",
+                    "required": ["api_key"],
+                    "properties": { "api_key": { "type": "string" } }
+                } } }
+            },
+            "responses": {
+                "200": {
+                    "description": "The token",
+                    "content": { "application/json": { "schema": {
+                        "type": "object",
+                        "properties": {
+                            "token": { "type": "string" },
+                            "token_type": { "type": "string", "enum": ["Bearer"] },
+                            "expires_in": { "type": "integer" }
+                        }
+                    } } }
+                },
+                "401": problem("The API key is not valid")
+            }
+        } });
+        document["components"]["securitySchemes"] = json!({
+            "apiKey": { "type": "apiKey", "in": "header", "name": API_KEY_HEADER },
+            "bearer": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" }
+        });
+    }
+
+    document
+}
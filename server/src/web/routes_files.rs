@@ -1,373 +1,508 @@
+use crate::config::config;
 use crate::error::{Error, Result};
+use crate::{limits, metrics};
 use axum::body::Bytes;
-use axum::extract::multipart::Field;
+use axum::extract::multipart::MultipartError;
 use axum::extract::{Multipart, Path};
+use axum::http::{header, HeaderValue, StatusCode};
 use axum::response::{IntoResponse, Response};
-use futures_util::StreamExt;
 use log::{debug, error, info};
 use serde::Serialize;
-use shiva::core::{Document, TransformerTrait, TransformerWithImageLoaderSaverTrait};
-use std::collections::HashMap;
-use std::io::{Cursor, Read};
-
-#[derive(Debug, Clone, Serialize)]
-struct UploadFileInfo {
-    upload_file_name: String,
-    upload_file_extension: String,
-    upload_file_data: Bytes,
-}
+use shiva::core::{Document, DocumentType, ImageType};
+use shiva::resource::MemoryResolver;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read, Write};
+use std::str::FromStr;
+use std::time::Instant;
 
 #[derive(Debug, Clone)]
-struct DownloadFile {
+pub(crate) struct DownloadFile {
     file_name: String,
+    content_type: &'static str,
     file_data: Bytes,
 }
 
-#[derive(Debug, Clone)]
-struct UploadFileZip {
-    file_name: String,
-    file_data: Bytes,
-    file_extension: String,
-    images: HashMap<String, Bytes>,
-}
-
-enum StructUploadFile {
-    UploadFile(UploadFileInfo),
-    UploadZip(UploadFileZip),
-}
-
 impl IntoResponse for DownloadFile {
     fn into_response(self) -> Response {
-        use axum::http::HeaderValue;
-
         let mut res = self.file_data.into_response();
-        res.headers_mut().insert(
-            "Content-Disposition",
-            HeaderValue::from_bytes(self.file_name.as_bytes()).unwrap(),
+        let headers = res.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(self.content_type),
         );
+        if let Ok(value) = HeaderValue::from_str(&content_disposition(&self.file_name)) {
+            headers.insert(header::CONTENT_DISPOSITION, value);
+        }
 
         res
     }
 }
 
-pub async fn handler_convert_file(
-    Path(output_format): Path<String>,
-    multipart: Multipart,
-) -> Result<impl IntoResponse> {
-    match upload_file(multipart).await {
-        //depending on the returned structure, we execute the code
-        Ok(data_upload_file) => match data_upload_file {
-            StructUploadFile::UploadFile(upload_file_info) => {
-                let input_extension = upload_file_info.upload_file_extension.clone();
-                info!("-->> {:<12} - handler_convert_file input_extension_{input_extension}- output_extension_{output_format}", "HANDLER");
-
-                let build_response_file = convert_file(
-                    upload_file_info.upload_file_name,
-                    upload_file_info.upload_file_extension,
-                    upload_file_info.upload_file_data,
-                    output_format,
-                )
-                .await
-                .map_err(|_| Error::FailConvertFile)?;
-
-                Ok(build_response_file)
+//an ASCII `filename` for old clients and the exact name as RFC 5987 `filename*`
+fn content_disposition(file_name: &str) -> String {
+    let ascii_name: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii_graphic() || c == ' ' => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded_name: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
             }
-            StructUploadFile::UploadZip(upload_file_zip) => {
-                info!("-->> {:<12} - handler_convert_file input ZIP archive - output_extension_{output_format}", "HANDLER");
-
-                let build_response_file = convert_file_zip(
-                    upload_file_zip.file_name,
-                    upload_file_zip.file_data,
-                    upload_file_zip.file_extension,
-                    upload_file_zip.images,
-                    output_format,
-                )
-                .await
-                .map_err(|_| Error::FailConvertFile)?;
-
-                Ok(build_response_file)
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+    format!("attachment; filename=\"{ascii_name}\"; filename*=UTF-8''{encoded_name}")
+}
+
+//an uploaded document ready to be converted
+pub(crate) struct Conversion {
+    file_name: String,
+    source: Source,
+}
+
+enum Source {
+    //a document in a file format, with the images it may reference
+    File {
+        file_extension: String,
+        file_data: Bytes,
+        resolver: MemoryResolver,
+    },
+    //a Common Document Model document sent as JSON, `size` is the length of the JSON
+    Document {
+        document: Box<Document>,
+        size: usize,
+    },
+}
+
+//a converted document and the files it references, such as the images of HTML
+struct Converted {
+    file_name: String,
+    file_data: Bytes,
+    side_files: Vec<(String, Bytes)>,
+}
+
+impl Conversion {
+    //a document without images, which must not read them from the server's disk either
+    pub(crate) fn new(file_name: String, format: DocumentType, file_data: Bytes) -> Self {
+        Conversion {
+            file_name,
+            source: Source::File {
+                file_extension: format.extension().to_string(),
+                file_data,
+                resolver: MemoryResolver::new(),
+            },
+        }
+    }
+
+    pub(crate) fn from_document(file_name: String, document: Document, size: usize) -> Self {
+        Conversion {
+            file_name,
+            source: Source::Document {
+                document: Box::new(document),
+                size,
+            },
+        }
+    }
+
+    fn source_name(&self) -> String {
+        match &self.source {
+            Source::File { file_extension, .. } => format!("{}.{file_extension}", self.file_name),
+            Source::Document { .. } => format!("{}.json", self.file_name),
+        }
+    }
+
+    //the input format and size in the metrics, the format is `cdm` for a document sent as JSON
+    fn input_metrics(&self) -> (&'static str, usize) {
+        match &self.source {
+            Source::File {
+                file_extension,
+                file_data,
+                ..
+            } => (
+                DocumentType::from_extension(file_extension)
+                    .map_or("unknown", |format| format.extension()),
+                file_data.len(),
+            ),
+            Source::Document { size, .. } => ("cdm", *size),
+        }
+    }
+
+    //converts the document and records the conversion in the metrics
+    fn run(self, output_format: DocumentType) -> Result<Converted> {
+        let started = Instant::now();
+        let (input_format, input_size) = self.input_metrics();
+
+        let result = limits::with_timeout(
+            config().conversion_timeout(),
+            config().max_conversions(),
+            move || self.convert(output_format),
+        );
+        metrics::record_conversion(
+            input_format,
+            output_format.extension(),
+            input_size,
+            started.elapsed(),
+            result.as_ref().err(),
+        );
+        result
+    }
+
+    //parses and generates the document, this is CPU bound and must run on a blocking thread
+    fn convert(self, output_format: DocumentType) -> Result<Converted> {
+        debug!("upload file name: {}", self.source_name());
+        debug!("download file format: {}", output_format);
+
+        let document = match self.source {
+            Source::File {
+                file_extension,
+                file_data,
+                resolver,
+            } => {
+                let input_format = DocumentType::from_extension(&file_extension)
+                    .ok_or(Error::UnsupportedFormat(file_extension))?;
+                limits::check_input(input_format, &file_data, config())?;
+
+                Document::parse_with_resolver(&file_data, input_format, resolver).map_err(|e| {
+                    error!("Failed to parse {input_format}: {e}");
+                    Error::FailParseDocument(e.to_string())
+                })?
             }
-        },
-        Err(e) => Err(e),
+            Source::Document { document, .. } => *document,
+        };
+        limits::check_document(&document, config())?;
+
+        //the images are returned with the document, never written to the server's disk
+        let side_files = RefCell::new(Vec::new());
+        let output_bytes = document
+            .generate_with_saver(output_format, |bytes, path| {
+                side_files
+                    .borrow_mut()
+                    .push((path.to_string(), bytes.clone()));
+                Ok(())
+            })
+            .map_err(|e| {
+                error!("Failed to generate {output_format}: {e}");
+                Error::FailConvertFile(e.to_string())
+            })?;
+
+        Ok(Converted {
+            file_name: format!("{}.{}", self.file_name, output_format.extension()),
+            file_data: output_bytes,
+            side_files: side_files.into_inner(),
+        })
     }
 }
 
-async fn convert_file_zip(
-    file_name: String,
-    input_file_data_bytes: Bytes,
-    file_extension: String,
-    images: HashMap<String, Bytes>,
-    output_format: String,
+//converts the uploaded documents, calling `progress` with the percentage done after each one;
+//a single document without side files is returned as is, anything else as a ZIP with a manifest
+pub(crate) fn convert_all(
+    conversions: Vec<Conversion>,
+    output_format: DocumentType,
+    progress: impl Fn(u8),
 ) -> Result<DownloadFile> {
-    /*
-    info!("upload file name: {}", file_name);
-    info!("upload file format: {}", file_extension);
-    info!("download file format: {}", output_format);
-    info!("Image files from the archive");
-    for image_name in images.keys() {
-        info!("- {:#?}", image_name);
-    }
-     */
-
-    let document = match file_extension.as_str() {
-        "md" => Document::from(
-            shiva::markdown::Transformer::parse_with_loader(
-                &input_file_data_bytes,
-                memory_image_loader(images),
-            )
-            .unwrap(),
-        ),
-        "html" | "htm" => Document::from(
-            shiva::html::Transformer::parse_with_loader(
-                &input_file_data_bytes,
-                memory_image_loader(images),
-            )
-            .unwrap(),
-        ),
-        _ => return Err(Error::FailParseDocument),
+    let total = conversions.len();
+    let single = total == 1;
+    let zip_name = match conversions.as_slice() {
+        [conversion] => conversion.file_name.clone(),
+        _ => "Shiva_convert".to_string(),
     };
 
-    let output_bytes = match output_format.as_str() {
-        "md" => shiva::markdown::Transformer::generate(&document).unwrap(),
-        "html" | "htm" => shiva::html::Transformer::generate(&document).unwrap(),
-        "txt" => shiva::text::Transformer::generate(&document).unwrap(),
-        "pdf" => shiva::pdf::Transformer::generate(&document).unwrap(),
-        "json" => shiva::json::Transformer::generate(&document).unwrap(),
-        "rtf" => shiva::rtf::Transformer::generate(&document).unwrap(),
-        "docx" => shiva::docx::Transformer::generate(&document).unwrap(),
-        _ => return Err(Error::FailConvertFile),
-    };
+    let mut results = Vec::with_capacity(total);
+    for (done, conversion) in conversions.into_iter().enumerate() {
+        let source = conversion.source_name();
+        let result = conversion.run(output_format);
+        progress(((done + 1) * 100 / total) as u8);
+
+        //a single document reports its error with the status code of the request
+        match result {
+            Ok(converted) if single && converted.side_files.is_empty() => {
+                return Ok(DownloadFile {
+                    file_name: converted.file_name,
+                    content_type: output_format.mime_type(),
+                    file_data: converted.file_data,
+                })
+            }
+            Err(e) if single => return Err(e),
+            result => results.push((source, result)),
+        }
+    }
 
     Ok(DownloadFile {
-        file_name,
-        file_data: output_bytes,
+        file_name: format!("{zip_name}.zip"),
+        content_type: "application/zip",
+        file_data: zip_results(results, !single)?,
     })
 }
 
-fn memory_image_loader(images: HashMap<String, Bytes>) -> impl Fn(&str) -> anyhow::Result<Bytes> {
-    let image_loader = move |image: &str| -> anyhow::Result<Bytes> {
-        let bytes = images
-            .get(image)
-            .cloned()
-            .ok_or_else(|| anyhow::anyhow!("No image: {}", image))?;
-        Ok(Bytes::from(bytes))
+//one entry of `manifest.json` in a ZIP of results
+#[derive(Debug, Serialize)]
+struct ManifestEntry {
+    source: String,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    files: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+//packs the converted documents with their side files and a manifest of every upload;
+//with `nested` each document gets its own folder so that side files do not collide
+fn zip_results(results: Vec<(String, Result<Converted>)>, nested: bool) -> Result<Bytes> {
+    let zip_error = |e: &dyn std::fmt::Display| {
+        error!("Error writing the ZIP archive: {e}");
+        Error::FailConvertFile("the ZIP archive could not be written".to_string())
     };
-    image_loader
+    let options =
+        zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let mut folders = HashSet::new();
+    let mut manifest = Vec::with_capacity(results.len());
+
+    for (source, result) in results {
+        let converted = match result {
+            Ok(converted) => converted,
+            Err(e) => {
+                manifest.push(ManifestEntry {
+                    source,
+                    status: "error",
+                    output: None,
+                    files: Vec::new(),
+                    error: Some(e.detail()),
+                });
+                continue;
+            }
+        };
+
+        let folder = if nested {
+            let stem = converted
+                .file_name
+                .rsplit_once('.')
+                .map_or(converted.file_name.as_str(), |(stem, _)| stem);
+            let mut folder = stem.to_string();
+            let mut n = 1;
+            while !folders.insert(folder.clone()) {
+                n += 1;
+                folder = format!("{stem}-{n}");
+            }
+            format!("{folder}/")
+        } else {
+            String::new()
+        };
+
+        let mut files = Vec::with_capacity(converted.side_files.len());
+        let output = format!("{folder}{}", converted.file_name);
+        for (path, bytes) in std::iter::once((output.clone(), converted.file_data)).chain(
+            converted
+                .side_files
+                .into_iter()
+                .map(|(path, bytes)| (format!("{folder}{path}"), bytes)),
+        ) {
+            archive
+                .start_file(path.as_str(), options)
+                .map_err(|e| zip_error(&e))?;
+            archive.write_all(&bytes).map_err(|e| zip_error(&e))?;
+            if path != output {
+                files.push(path);
+            }
+        }
+
+        manifest.push(ManifestEntry {
+            source,
+            status: "ok",
+            output: Some(output),
+            files,
+            error: None,
+        });
+    }
+
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(|e| zip_error(&e))?;
+    archive
+        .start_file("manifest.json", options)
+        .map_err(|e| zip_error(&e))?;
+    archive.write_all(&manifest).map_err(|e| zip_error(&e))?;
+
+    let cursor = archive.finish().map_err(|e| zip_error(&e))?;
+    Ok(Bytes::from(cursor.into_inner()))
 }
 
-//checking the supported formats in the archive
-fn supported_extensions_in_archive(file_extension: &str) -> bool {
-    match file_extension {
-        "md" | "html" | "htm" | "png" => true,
+//the output format of a request, which the library must be able to generate
+pub(crate) fn output_document_type(output_format: &str) -> Result<DocumentType> {
+    DocumentType::from_extension(&output_format.to_lowercase())
+        .filter(|format| format.can_generate() && config().format_enabled(*format))
+        .ok_or_else(|| Error::UnsupportedFormat(output_format.to_string()))
+}
 
-        _ => false,
+pub async fn handler_convert_file(
+    Path(output_format): Path<String>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let output_format = output_document_type(&output_format)?;
+
+    let conversions = upload_files(multipart).await?;
+    info!(
+        "-->> {:<12} - handler_convert_file {} file(s) - output_extension_{output_format}",
+        "HANDLER",
+        conversions.len()
+    );
+
+    //a panic in a converter fails the blocking task instead of the handler
+    tokio::task::spawn_blocking(move || convert_all(conversions, output_format, |_| {}))
+        .await
+        .map_err(|e| {
+            error!("Converter task failed: {e}");
+            Error::FailParseDocument("the document could not be processed".to_string())
+        })?
+}
+
+//the lowercase name and extension of an uploaded file, without directories
+fn split_file_name(path: &str) -> (Option<String>, Option<String>) {
+    let non_empty = |part: &str| Some(part.trim().to_lowercase()).filter(|part| !part.is_empty());
+    let file_name = path.rsplit(['/', '\\']).next().unwrap_or_default();
+    match file_name.rsplit_once('.') {
+        Some((name, extension)) => (non_empty(name), non_empty(extension)),
+        None => (non_empty(file_name), None),
     }
 }
 
-//unpacking the archive
-async fn unpacking(mut field: Field<'_>) -> Result<StructUploadFile> {
-    // Creating variables to store archive file data
-    let mut file_name = None;
-    let mut file_data = None;
-    let mut file_extension = None;
-    let mut images = HashMap::new();
-    let mut found_supported_file = false;
-
-    //reading the contents of the archive
-    let mut file_content = Vec::new();
-    while let Some(chunk) = field.next().await {
-        match chunk {
-            Ok(data) => {
-                file_content.extend_from_slice(&data);
-            }
-            Err(e) => {
-                error!("Error reading chunk: {:?}", e);
-                return Err(Error::FailBytes);
-            }
-        }
+//a body over the size limit is reported as such, anything else is a malformed upload
+fn multipart_error(e: MultipartError) -> Error {
+    error!("Error reading the upload: {e}");
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        Error::FileTooLarge
+    } else {
+        Error::FailBytes
     }
+}
+
+//unpacking the archive, every document in it is converted with the images of the archive
+pub(crate) fn unpacking(file_content: Bytes) -> Result<Vec<Conversion>> {
+    let mut documents = Vec::new();
+    let mut images = HashMap::new();
+    let max_unpacked_size = config().max_unpacked_size;
+    let mut unpacked_size = 0u64;
 
     //creating a cursor to read the archive
     let reader = Cursor::new(file_content);
-    let mut archive = zip::ZipArchive::new(reader).unwrap();
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| {
+        error!("Error opening the ZIP archive: {e}");
+        Error::InvalidZip
+    })?;
 
     //check the extension of each archive file
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-
-        //defining the file name
-        let file_name_in_archive = file
-            .name()
-            .split(".")
-            .next()
-            .map(|name_file_in_archive| name_file_in_archive.to_lowercase())
-            .filter(|name_file_in_archive| !name_file_in_archive.trim().is_empty())
-            .map(String::from);
-
-        //we define the extension of each file
-        let file_extension_in_archive = file
-            .name()
-            .split(".")
-            .last()
-            .map(|ext| ext.to_lowercase())
-            .filter(|ext| !ext.trim().is_empty())
-            .map(String::from);
-
-        debug!(
-            "in ZIP {}.{}",
-            file_name_in_archive.clone().unwrap(),
-            file_extension_in_archive.clone().unwrap()
-        );
-
-        //checking the supported format
-        if let Some(ref ext) = file_extension_in_archive {
-            if supported_extensions_in_archive(&ext) {
-                found_supported_file = true;
-                let mut file_data_buf = Vec::new();
-                file.read_to_end(&mut file_data_buf).unwrap();
-                match ext.as_str() {
-                    "html" | "htm" | "md" => {
-                        file_name = file_name_in_archive;
-                        file_data = Some(Bytes::from(file_data_buf));
-                        file_extension = file_extension_in_archive.clone();
-                    }
-                    "png" => {
-                        let image_name = file.name().to_string();
-
-                        images.insert(image_name, Bytes::from(file_data_buf));
-                    }
-                    _ => {
-                        return Err(Error::NoFilesToConvertInZip);
-                    }
-                }
-            }
+        let mut file = archive.by_index(i).map_err(|e| {
+            error!("Error reading the ZIP archive: {e}");
+            Error::InvalidZip
+        })?;
+        if file.is_dir() {
+            continue;
         }
-    }
 
-    // If no supported file was found, return an error
-    if !found_supported_file {
-        return Err(Error::NoFilesToConvertInZip);
-    }
-
-    // building the Upload File Zip structure
-    let upload_file_zip = UploadFileZip {
-        file_name: file_name.unwrap_or("Shiva_convert".to_string()),
-        file_data: file_data.ok_or_else(|| Error::FailBytes)?,
-        file_extension: file_extension.ok_or_else(|| Error::ExtensionMissing)?,
-        images,
-    };
+        let (file_name_in_archive, file_extension_in_archive) = split_file_name(file.name());
+        debug!("in ZIP {}", file.name());
 
-    Ok(StructUploadFile::UploadZip(upload_file_zip))
-}
+        //files in other formats are left out
+        let Some(ext) = file_extension_in_archive else {
+            continue;
+        };
+        let is_image = ImageType::from_str(&ext).is_ok();
+        if !is_image && !supported_format(&ext) {
+            continue;
+        }
 
-async fn upload_file(mut multipart: Multipart) -> Result<StructUploadFile> {
-    //create variables in which we will then write the name, file extension and the file itself in binary form
-    let mut file_name = None;
-    let mut file_extension = None;
-    let mut file_data = Bytes::new();
-
-    //defining the file type and its name
-    while let Some(field) = multipart.next_field().await.unwrap() {
-        let name = field.name().unwrap_or("").to_string();
-        let filename = field.file_name().unwrap_or("").to_string();
-
-        //if the file has the file parameter, then
-        if name == "file" {
-            //defining the file name without the extension
-            file_name = filename
-                .split(".")
-                .next()
-                .map(|upload_name| upload_name.to_lowercase())
-                .filter(|upload_name| !upload_name.trim().is_empty())
-                .map(String::from);
-
-            //defining the file extension
-            file_extension = filename
-                .split(".")
-                .last()
-                .map(|ext| ext.to_lowercase())
-                .filter(|ext| !ext.trim().is_empty())
-                .map(String::from);
-
-            //matching the file extension
-            if let Some(ref ext) = file_extension {
-                match ext.as_str() {
-                    "zip" => {
-                        return unpacking(field).await; //if _zip, start unpacking
-                    }
-
-                    _ => {
-                        //if not zip, check the supported extension
-                        if supported_format(ext).await {
-                            file_data = field.bytes().await.unwrap();
-                        } else {
-                            return Err(Error::UnsupportedFormat);
-                        }
-                    }
-                }
-            } else {
-                return Err(Error::ExtensionMissing);
-            }
+        //the bytes actually unpacked are counted, the sizes an archive declares may be false
+        let remaining = max_unpacked_size - unpacked_size;
+        let mut file_data_buf = Vec::new();
+        let file_name = file.name().to_string();
+        (&mut file)
+            .take(remaining + 1)
+            .read_to_end(&mut file_data_buf)
+            .map_err(|e| {
+                error!("Error unpacking {file_name}: {e}");
+                Error::InvalidZip
+            })?;
+        if file_data_buf.len() as u64 > remaining {
+            return Err(Error::UnpackedTooLarge(max_unpacked_size));
+        }
+        unpacked_size += file_data_buf.len() as u64;
+        if is_image {
+            images.insert(file_name, Bytes::from(file_data_buf));
+        } else {
+            documents.push((
+                file_name_in_archive.unwrap_or("Shiva_convert".to_string()),
+                ext,
+                Bytes::from(file_data_buf),
+            ));
         }
     }
 
-    //writing the received data to variables
-    let file_name = file_name.unwrap_or("Shiva_convert".to_string());
-    let file_extension = file_extension.ok_or("File extension not found").unwrap();
-    let file_data = file_data;
-
-    //creating the uploadFile Info structure
-    Ok(StructUploadFile::UploadFile(UploadFileInfo {
-        upload_file_name: file_name,
-        upload_file_extension: file_extension,
-        upload_file_data: file_data,
-    }))
+    //an archive with only images has nothing to convert
+    if documents.is_empty() {
+        return Err(Error::NoFilesToConvertInZip);
+    }
+
+    Ok(documents
+        .into_iter()
+        .map(|(file_name, file_extension, file_data)| Conversion {
+            file_name,
+            source: Source::File {
+                file_extension,
+                file_data,
+                resolver: MemoryResolver::from(images.clone()),
+            },
+        })
+        .collect())
 }
 
-async fn convert_file(
-    file_name: String,
-    file_extension: String,
-    input_file_data_bytes: Bytes,
-    output_format: String,
-) -> Result<DownloadFile> {
-    debug!("upload file name: {}", file_name);
-    debug!("upload file format: {}", file_extension);
-    debug!("download file format: {}", output_format);
+//every `file` field of the form is converted, ZIP archives are unpacked
+pub(crate) async fn upload_files(mut multipart: Multipart) -> Result<Vec<Conversion>> {
+    let mut conversions = Vec::new();
 
-    let document = match file_extension.as_str() {
-        "md" => {
-            Document::from(shiva::markdown::Transformer::parse(&input_file_data_bytes).unwrap())
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("file") {
+            continue;
         }
-        "html" | "htm" => {
-            Document::from(shiva::html::Transformer::parse(&input_file_data_bytes).unwrap())
+
+        //defining the file name and its extension
+        let (file_name, file_extension) = split_file_name(field.file_name().unwrap_or(""));
+        let file_extension = file_extension.ok_or(Error::ExtensionMissing)?;
+
+        if file_extension == "zip" {
+            let file_data = field.bytes().await.map_err(multipart_error)?;
+            conversions.extend(unpacking(file_data)?);
+        } else if supported_format(&file_extension) {
+            //an uploaded document must not read images from the server, so nothing is resolved
+            conversions.push(Conversion {
+                file_name: file_name.unwrap_or("Shiva_convert".to_string()),
+                source: Source::File {
+                    file_data: field.bytes().await.map_err(multipart_error)?,
+                    file_extension,
+                    resolver: MemoryResolver::new(),
+                },
+            });
+        } else {
+            return Err(Error::UnsupportedFormat(file_extension));
         }
-        "txt" => Document::from(shiva::text::Transformer::parse(&input_file_data_bytes).unwrap()),
-        "pdf" => Document::from(shiva::pdf::Transformer::parse(&input_file_data_bytes).unwrap()),
-        "json" => Document::from(shiva::json::Transformer::parse(&input_file_data_bytes).unwrap()),
-        _ => return Err(Error::FailParseDocument),
-    };
+    }
 
-    let output_bytes = match output_format.as_str() {
-        "md" => shiva::markdown::Transformer::generate(&document).unwrap(),
-        "html" | "htm" => shiva::html::Transformer::generate(&document).unwrap(),
-        "txt" => shiva::text::Transformer::generate(&document).unwrap(),
-        "pdf" => shiva::pdf::Transformer::generate(&document).unwrap(),
-        "json" => shiva::json::Transformer::generate(&document).unwrap(),
-        "rtf" => shiva::rtf::Transformer::generate(&document).unwrap(),
-        "docx" => shiva::docx::Transformer::generate(&document).unwrap(),
-        _ => return Err(Error::FailConvertFile),
-    };
+    if conversions.is_empty() {
+        return Err(Error::FileMissing);
+    }
 
-    Ok(DownloadFile {
-        file_name,
-        file_data: output_bytes,
-    })
+    Ok(conversions)
 }
 
-async fn supported_format(file_extension: &str) -> bool {
-    match file_extension {
-        "md" | "html" | "htm" | "txt" | "pdf" | "json" => true,
-
-        _ => false,
-    }
+//the format is known to the library, enabled in this build and allowed by the configuration
+fn supported_format(file_extension: &str) -> bool {
+    DocumentType::from_extension(file_extension)
+        .is_some_and(|format| config().format_enabled(format))
 }
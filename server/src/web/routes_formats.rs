@@ -0,0 +1,103 @@
+use crate::config::config;
+use axum::routing::get;
+use axum::{Json, Router};
+use log::info;
+use serde::Serialize;
+use shiva::core::DocumentType;
+
+pub(crate) const ELEMENTS: [&str; 8] = [
+    "header",
+    "paragraph",
+    "list",
+    "table",
+    "image",
+    "hyperlink",
+    "page_header",
+    "page_footer",
+];
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FormatInfo {
+    name: String,
+    extensions: Vec<&'static str>,
+    mime_type: &'static str,
+    parse: bool,
+    generate: bool,
+    parse_elements: Vec<&'static str>,
+    generate_elements: Vec<&'static str>,
+}
+
+pub fn routes() -> Router {
+    Router::new().route("/formats", get(handler_formats))
+}
+
+async fn handler_formats() -> Json<Vec<FormatInfo>> {
+    info!("-->> {:<12} - handler_formats", "HANDLER");
+
+    Json(formats())
+}
+
+//every format the server takes or writes, as configured
+pub(crate) fn formats() -> Vec<FormatInfo> {
+    DocumentType::variants()
+        .iter()
+        .filter(|format| config().format_enabled(**format))
+        .map(|format| {
+            let (parse_elements, generate_elements) = element_support(*format);
+            let generate = format.can_generate();
+            let mut extensions: Vec<_> = DocumentType::supported_extensions()
+                .into_iter()
+                .filter(|extension| DocumentType::from_extension(extension) == Some(*format))
+                .collect();
+            extensions.sort_unstable();
+
+            FormatInfo {
+                name: format.to_string(),
+                extensions,
+                mime_type: format.mime_type(),
+                parse: true,
+                generate,
+                parse_elements: supported(parse_elements),
+                generate_elements: if generate {
+                    supported(generate_elements)
+                } else {
+                    Vec::new()
+                },
+            }
+        })
+        .collect()
+}
+
+fn supported(support: [bool; 8]) -> Vec<&'static str> {
+    ELEMENTS
+        .iter()
+        .zip(support)
+        .filter(|(_, supported)| *supported)
+        .map(|(element, _)| *element)
+        .collect()
+}
+
+//the elements each format reads and writes, in the order of ELEMENTS;
+//kept in step with the matrices of the README
+fn element_support(format: DocumentType) -> ([bool; 8], [bool; 8]) {
+    const Y: bool = true;
+    const N: bool = false;
+    const TABLE: [bool; 8] = [N, N, N, Y, N, N, N, N];
+
+    match format {
+        DocumentType::Text => ([Y, Y, Y, Y, N, Y, N, N], [Y, Y, Y, Y, N, Y, Y, Y]),
+        DocumentType::Markdown => ([Y, Y, Y, Y, Y, Y, N, N], [Y; 8]),
+        DocumentType::HTML => ([Y, Y, Y, Y, Y, Y, N, N], [Y, Y, Y, Y, Y, Y, N, N]),
+        DocumentType::PDF => ([N, Y, Y, N, N, N, N, N], [Y; 8]),
+        DocumentType::DOCX => ([Y, Y, Y, Y, N, Y, N, N], [Y, Y, Y, Y, Y, Y, N, N]),
+        DocumentType::RTF => ([Y, Y, Y, Y, N, Y, Y, Y], [Y, Y, Y, Y, Y, Y, N, N]),
+        DocumentType::Json => ([Y, Y, Y, Y, N, Y, Y, Y], [Y, Y, Y, Y, N, Y, Y, Y]),
+        DocumentType::XML => ([Y; 8], [Y; 8]),
+        DocumentType::XLS => (TABLE, [N; 8]),
+        DocumentType::CSV
+        | DocumentType::TSV
+        | DocumentType::FixedWidth
+        | DocumentType::XLSX
+        | DocumentType::ODS => (TABLE, TABLE),
+    }
+}
@@ -0,0 +1,38 @@
+use crate::metrics;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+//`ready` is set once the server listens and cleared when it starts shutting down
+pub fn routes(ready: Arc<AtomicBool>) -> Router {
+    Router::new()
+        .route("/healthz", get(handler_healthz))
+        .route("/readyz", get(handler_readyz))
+        .route("/metrics", get(handler_metrics))
+        .with_state(ready)
+}
+
+//the process is up and answering
+async fn handler_healthz() -> impl IntoResponse {
+    "ok"
+}
+
+//the server takes new requests
+async fn handler_readyz(State(ready): State<Arc<AtomicBool>>) -> impl IntoResponse {
+    if ready.load(Ordering::Relaxed) {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+async fn handler_metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
@@ -0,0 +1,255 @@
+use crate::ctx::Ctx;
+use crate::error::{Error, Result};
+use crate::web::routes_files::{
+    convert_all, output_document_type, upload_files, Conversion, DownloadFile,
+};
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Extension, Json, Router};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use shiva::core::DocumentType;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+struct Job {
+    owner: Option<String>, //the key id of the caller that submitted the job
+    status: JobStatus,
+    progress: u8,
+    result: Option<Result<DownloadFile>>,
+    finished: Option<Instant>,
+}
+
+//conversion jobs, run at most `concurrency` at a time on the blocking thread pool, with at most
+//`max_pending` queued or running; finished jobs are kept for `ttl` so their result can be
+//downloaded
+pub struct JobStore {
+    jobs: Mutex<HashMap<String, Job>>,
+    permits: Semaphore,
+    max_pending: usize,
+    ttl: Duration,
+    next_id: AtomicU64,
+    id_hasher: RandomState,
+}
+
+impl JobStore {
+    pub fn new(concurrency: usize, max_pending: usize, ttl: Duration) -> Arc<Self> {
+        Arc::new(JobStore {
+            jobs: Mutex::new(HashMap::new()),
+            permits: Semaphore::new(concurrency.max(1)),
+            max_pending: max_pending.max(1),
+            ttl,
+            next_id: AtomicU64::new(0),
+            id_hasher: RandomState::new(),
+        })
+    }
+
+    //removes expired results every minute, for as long as the server runs
+    pub fn spawn_cleanup(self: &Arc<Self>) {
+        let store = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(60));
+            loop {
+                interval.tick().await;
+                store.remove_expired();
+            }
+        });
+    }
+
+    fn remove_expired(&self) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            jobs.retain(|_, job| {
+                job.finished
+                    .is_none_or(|finished| finished.elapsed() < self.ttl)
+            });
+        }
+    }
+
+    //ids are not sequential, so that one client cannot guess the jobs of another
+    fn new_id(&self) -> String {
+        let sequence = self.next_id.fetch_add(1, Ordering::Relaxed);
+        format!("{:016x}{sequence:x}", self.id_hasher.hash_one(sequence))
+    }
+
+    fn update(&self, id: &str, update: impl FnOnce(&mut Job)) {
+        if let Ok(mut jobs) = self.jobs.lock() {
+            if let Some(job) = jobs.get_mut(id) {
+                update(job);
+            }
+        }
+    }
+
+    pub fn submit(
+        self: &Arc<Self>,
+        conversions: Vec<Conversion>,
+        output_format: DocumentType,
+        owner: Option<String>,
+    ) -> Result<String> {
+        let id = self.new_id();
+        {
+            let mut jobs = self.jobs.lock().map_err(|_| Error::ServerBusy)?;
+            let pending = jobs.values().filter(|job| job.finished.is_none()).count();
+            if pending >= self.max_pending {
+                return Err(Error::TooManyJobs(self.max_pending));
+            }
+            jobs.insert(
+                id.clone(),
+                Job {
+                    owner,
+                    status: JobStatus::Queued,
+                    progress: 0,
+                    result: None,
+                    finished: None,
+                },
+            );
+        }
+
+        let store = Arc::clone(self);
+        let job_id = id.clone();
+        tokio::spawn(async move {
+            let Ok(_permit) = store.permits.acquire().await else {
+                return;
+            };
+            store.update(&job_id, |job| job.status = JobStatus::Running);
+
+            let progress_store = Arc::clone(&store);
+            let progress_id = job_id.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                convert_all(conversions, output_format, |progress| {
+                    progress_store.update(&progress_id, |job| job.progress = progress)
+                })
+            })
+            .await
+            .unwrap_or_else(|e| {
+                error!("Converter task failed: {e}");
+                Err(Error::FailParseDocument(
+                    "the document could not be processed".to_string(),
+                ))
+            });
+
+            info!("-->> {:<12} - job {job_id} finished", "JOBS");
+            store.update(&job_id, |job| {
+                job.status = match result {
+                    Ok(_) => JobStatus::Done,
+                    Err(_) => JobStatus::Failed,
+                };
+                job.progress = 100;
+                job.result = Some(result);
+                job.finished = Some(Instant::now());
+            });
+        });
+
+        Ok(id)
+    }
+
+    //the jobs of other callers are not found, so that their existence is not revealed either
+    fn get<'a>(jobs: &'a HashMap<String, Job>, id: &str, caller: Option<&str>) -> Result<&'a Job> {
+        jobs.get(id)
+            .filter(|job| job.owner.as_deref() == caller)
+            .ok_or(Error::JobNotFound)
+    }
+
+    fn info(&self, id: &str, caller: Option<&str>) -> Result<JobInfo> {
+        let jobs = self.jobs.lock().map_err(|_| Error::JobNotFound)?;
+        let job = Self::get(&jobs, id, caller)?;
+        Ok(JobInfo {
+            id: id.to_string(),
+            status: job.status,
+            progress: job.progress,
+            error: match &job.result {
+                Some(Err(e)) => Some(e.detail()),
+                _ => None,
+            },
+        })
+    }
+
+    fn result(&self, id: &str, caller: Option<&str>) -> Result<DownloadFile> {
+        let jobs = self.jobs.lock().map_err(|_| Error::JobNotFound)?;
+        match &Self::get(&jobs, id, caller)?.result {
+            Some(result) => result.clone(),
+            None => Err(Error::JobNotFinished),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct JobInfo {
+    id: String,
+    status: JobStatus,
+    progress: u8,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JobParams {
+    output_format: String,
+}
+
+pub fn routes(store: Arc<JobStore>) -> Router {
+    Router::new()
+        .route("/jobs", post(handler_create_job))
+        .route("/jobs/:id", get(handler_job_status))
+        .route("/jobs/:id/result", get(handler_job_result))
+        .with_state(store)
+}
+
+//the caller is known when authentication is enabled, its jobs are visible only to it
+fn caller(ctx: &Option<Extension<Ctx>>) -> Option<&str> {
+    ctx.as_ref().map(|Extension(ctx)| ctx.key_id())
+}
+
+pub async fn handler_create_job(
+    State(store): State<Arc<JobStore>>,
+    ctx: Option<Extension<Ctx>>,
+    Query(params): Query<JobParams>,
+    multipart: Multipart,
+) -> Result<impl IntoResponse> {
+    let output_format = output_document_type(&params.output_format)?;
+    let conversions = upload_files(multipart).await?;
+
+    let owner = caller(&ctx).map(str::to_string);
+    let id = store.submit(conversions, output_format, owner)?;
+    info!(
+        "-->> {:<12} - handler_create_job {id} - output_extension_{output_format}",
+        "HANDLER"
+    );
+
+    Ok((
+        StatusCode::ACCEPTED,
+        [(header::LOCATION, format!("/jobs/{id}"))],
+        Json(store.info(&id, caller(&ctx))?),
+    ))
+}
+
+pub async fn handler_job_status(
+    State(store): State<Arc<JobStore>>,
+    ctx: Option<Extension<Ctx>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    Ok(Json(store.info(&id, caller(&ctx))?))
+}
+
+pub async fn handler_job_result(
+    State(store): State<Arc<JobStore>>,
+    ctx: Option<Extension<Ctx>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse> {
+    store.result(&id, caller(&ctx))
+}
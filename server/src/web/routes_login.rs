@@ -1 +1,42 @@
-//TODO: auth logic
+use crate::error::Result;
+use crate::web::mw_auth::Auth;
+use axum::extract::State;
+use axum::routing::post;
+use axum::{Json, Router};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+struct LoginPayload {
+    api_key: String,
+}
+
+#[derive(Debug, Serialize)]
+struct LoginResponse {
+    token: String,
+    token_type: &'static str,
+    expires_in: u64,
+}
+
+pub fn routes(auth: Arc<Auth>) -> Router {
+    Router::new()
+        .route("/api/login", post(api_login))
+        .with_state(auth)
+}
+
+//exchanges an API key for a bearer token
+async fn api_login(
+    State(auth): State<Arc<Auth>>,
+    Json(payload): Json<LoginPayload>,
+) -> Result<Json<LoginResponse>> {
+    info!("-->> {:<12} - api_login", "HANDLER");
+
+    let (token, expires_in) = auth.login(&payload.api_key)?;
+
+    Ok(Json(LoginResponse {
+        token,
+        token_type: "Bearer",
+        expires_in,
+    }))
+}
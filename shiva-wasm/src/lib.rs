@@ -1,7 +1,13 @@
 mod utils;
 
+use js_sys::{Array, ArrayBuffer, Function, Object, Reflect, Uint8Array};
+use serde::Serialize;
+use std::cell::RefCell;
 use wasm_bindgen::prelude::*;
+use web_sys::{DedicatedWorkerGlobalScope, MessageEvent};
 
+use shiva::core::bytes::Bytes;
+use shiva::resource::{ChainResolver, DataUriResolver, MemoryResolver, ResourceResolver};
 use shiva::{core::Document, core::DocumentType as FileFormat};
 
 use crate::utils::set_panic_hook;
@@ -14,26 +20,511 @@ macro_rules! log {
     }
 }
 
-pub fn convert(
-    file: Vec<u8>,
+#[wasm_bindgen(typescript_custom_section)]
+const TYPESCRIPT: &'static str = r#"
+export type ShivaErrorCode =
+  | "UnsupportedFormat"
+  | "ParseError"
+  | "GenerateError"
+  | "InvalidDocument"
+  | "InvalidOptions"
+  | "FontsMissing";
+
+/** Thrown when a conversion fails. */
+export interface ShivaError extends Error {
+  name: "ShivaError";
+  code: ShivaErrorCode;
+}
+
+export interface ParseOptions {
+  /**
+   * Returns the bytes of an image that the document references, or
+   * `undefined` when there is none. It is called synchronously, so images
+   * that must be fetched have to be fetched beforehand.
+   */
+  resolveImage?: (uri: string) => Uint8Array | ArrayBuffer | null | undefined;
+}
+
+export type ConvertStage = "parse" | "generate" | "done";
+
+export interface ConvertOptions extends ParseOptions {
+  /** Called when a stage starts, at 0, 50 and 100 percent. */
+  onProgress?: (percent: number, stage: ConvertStage) => void;
+}
+
+/** A message posted to a worker that runs `workerMain`. */
+export type WorkerRequest =
+  | {
+      id: number;
+      type: "convert";
+      input: ArrayBuffer;
+      from: DocumentType;
+      to: DocumentType;
+      /** The images the document references, by their URI. */
+      images?: Record<string, Uint8Array | ArrayBuffer>;
+    }
+  | { id: number; type: "setFonts"; fonts: Array<Uint8Array | ArrayBuffer> };
+
+/** A message posted back by `workerMain`, with the `id` of its request. */
+export type WorkerResponse =
+  | { id: number; type: "progress"; percent: number; stage: ConvertStage }
+  | { id: number; type: "result"; output?: ArrayBuffer }
+  | { id: number; type: "error"; code: ShivaErrorCode; message: string };
+
+export interface FormatInfo {
+  format: DocumentType;
+  name: string;
+  extensions: string[];
+  mimeType: string;
+  parse: boolean;
+  generate: boolean;
+}
+
+/** The Common Document Model, see CDM.md. Image data is not included. */
+export interface CdmDocument {
+  bands: CdmBand[];
+  page_format: "A4" | "Letter" | "Legal" | "Tabloid" | { Custom: CdmPageDimensions };
+  orientation: "Portrait" | "Landscape";
+  metadata?: { title: string | null; author: string | null; description: string | null };
+}
+
+export interface CdmPageDimensions {
+  page_width: number;
+  page_height: number;
+  page_margin_top: number;
+  page_margin_bottom: number;
+  page_margin_left: number;
+  page_margin_right: number;
+}
+
+export type CdmBand =
+  | { Title: CdmElement[] }
+  | { PageHeader: CdmElement[] }
+  | { ColumnHeader: CdmElement[] }
+  | { Detail: CdmElement[] }
+  | { ColumnFooter: CdmElement[] }
+  | { PageFooter: CdmElement[] }
+  | { Summary: CdmElement[] }
+  | { Custom: [string, CdmElement[]] };
+
+export type CdmElement =
+  | { Text: { text: string; size: number } }
+  | { Header: { level: number; text: string } }
+  | { Paragraph: { elements: CdmElement[] } }
+  | {
+      Table: {
+        headers: { element: CdmElement; width: number }[];
+        rows: { cells: { element: CdmElement }[] }[];
+      };
+    }
+  | { List: { elements: { element: CdmElement; checked?: boolean | null }[]; numbered: boolean } }
+  | {
+      Image: {
+        title: string;
+        alt: string;
+        image_type: "Png" | "Jpeg" | "Gif" | "SVG" | "Webp" | "Bmp" | "Tiff";
+        align: "Left" | "Center" | "Right" | "None";
+        size: { width: string | null; height: string | null };
+        caption?: string;
+      };
+    }
+  | { Hyperlink: { title: string; url: string; alt: string; size: number } }
+  | { DefinitionList: { items: { term: string; descriptions: CdmElement[] }[] } }
+  | { Math: { latex: string; display: boolean } };
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "CdmDocument")]
+    pub type CdmDocument;
+
+    #[wasm_bindgen(typescript_type = "FormatInfo[]")]
+    pub type FormatInfoArray;
+
+    #[wasm_bindgen(typescript_type = "ParseOptions")]
+    pub type ParseOptions;
+
+    #[wasm_bindgen(typescript_type = "ConvertOptions")]
+    pub type ConvertOptions;
+
+    #[wasm_bindgen(typescript_type = "Array<Uint8Array | ArrayBuffer>")]
+    pub type FontArray;
+}
+
+thread_local! {
+    // The fonts set with `setFonts`, PDF documents are typeset with them
+    static FONTS: RefCell<Vec<Bytes>> = const { RefCell::new(Vec::new()) };
+}
+
+// Asks the `resolveImage` callback of JS for the images, `data:` URIs are decoded here
+struct JsResolver(Function);
+
+impl ResourceResolver for JsResolver {
+    fn resolve(&self, uri: &str) -> anyhow::Result<Bytes> {
+        if uri.starts_with("data:") {
+            return DataUriResolver.resolve(uri);
+        }
+        let value = self
+            .0
+            .call1(&JsValue::NULL, &uri.into())
+            .map_err(|e| anyhow::anyhow!("resolveImage failed for {uri}: {e:?}"))?;
+        if value.is_null() || value.is_undefined() {
+            return Err(anyhow::anyhow!("Image not found: {uri}"));
+        }
+        Ok(Bytes::from(Uint8Array::new(&value).to_vec()))
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct FormatInfo {
+    format: u8,
+    name: String,
+    extensions: Vec<&'static str>,
+    mime_type: &'static str,
+    parse: bool,
+    generate: bool,
+}
+
+// A JS `Error` named `ShivaError` with a `code` that tells the failures apart
+fn shiva_error(code: &str, message: impl std::fmt::Display) -> JsValue {
+    let error = js_sys::Error::new(&message.to_string());
+    error.set_name("ShivaError");
+    let _ = js_sys::Reflect::set(&error, &"code".into(), &code.into());
+    error.into()
+}
+
+fn check_parse(format: FileFormat) -> Result<(), JsValue> {
+    if format.is_enabled() {
+        Ok(())
+    } else {
+        Err(shiva_error(
+            "UnsupportedFormat",
+            format!("{format} documents cannot be read by this build"),
+        ))
+    }
+}
+
+fn check_generate(format: FileFormat) -> Result<(), JsValue> {
+    if format.can_generate() {
+        Ok(())
+    } else {
+        Err(shiva_error(
+            "UnsupportedFormat",
+            format!("{format} documents cannot be written by this build"),
+        ))
+    }
+}
+
+// The function in the `name` option, `None` when it is not set
+fn option_function(options: Option<&JsValue>, name: &str) -> Result<Option<Function>, JsValue> {
+    let Some(options) = options else {
+        return Ok(None);
+    };
+    let value = Reflect::get(options, &name.into())?;
+    if value.is_undefined() || value.is_null() {
+        return Ok(None);
+    }
+    value
+        .dyn_into::<Function>()
+        .map(Some)
+        .map_err(|_| shiva_error("InvalidOptions", format!("{name} must be a function")))
+}
+
+fn options_resolver(options: Option<&JsValue>) -> Result<Option<JsResolver>, JsValue> {
+    Ok(option_function(options, "resolveImage")?.map(JsResolver))
+}
+
+fn parse_document(
+    file: &[u8],
+    input_format: FileFormat,
+    resolver: Option<impl ResourceResolver>,
+) -> Result<Document, JsValue> {
+    check_parse(input_format)?;
+    let file = Bytes::from(file.to_vec());
+    let parsed = match resolver {
+        Some(resolver) => Document::parse_with_resolver(&file, input_format, resolver),
+        None => Document::parse(&file, input_format),
+    };
+    parsed.map_err(|e| shiva_error("ParseError", format!("{input_format}: {e}")))
+}
+
+fn generate_document(document: &Document, output_format: FileFormat) -> Result<Vec<u8>, JsValue> {
+    check_generate(output_format)?;
+    let generated = match output_format {
+        #[cfg(feature = "pdf")]
+        FileFormat::PDF => {
+            let fonts = FONTS.with(|fonts| fonts.borrow().clone());
+            if fonts.is_empty() {
+                return Err(shiva_error(
+                    "FontsMissing",
+                    "PDF documents need fonts, pass them to setFonts first",
+                ));
+            }
+            let options = shiva::pdf::GenerateOptions { fonts };
+            shiva::pdf::Transformer::generate_with_options(document, &options)
+        }
+        _ => document.generate(output_format),
+    };
+    match generated {
+        Ok(generated) => Ok(generated.to_vec()),
+        Err(err) => {
+            log!(" FileFormat::{} err {:#?}", output_format, err);
+            Err(shiva_error(
+                "GenerateError",
+                format!("{output_format}: {err}"),
+            ))
+        }
+    }
+}
+
+// Parses and generates, calling `progress` when each stage starts
+fn convert_with_progress(
+    file: &[u8],
     input_format: FileFormat,
     output_format: FileFormat,
+    resolver: Option<impl ResourceResolver>,
+    progress: impl Fn(u32, &str),
 ) -> Result<Vec<u8>, JsValue> {
+    progress(0, "parse");
+    let document = parse_document(file, input_format, resolver)?;
+    progress(50, "generate");
+    let generated = generate_document(&document, output_format)?;
+    progress(100, "done");
+    Ok(generated)
+}
+
+#[wasm_bindgen(start)]
+pub fn start() {
     set_panic_hook();
+}
+
+/// Sets the fonts PDF documents are typeset with, the bytes of TTF and OTF
+/// files or font collections. PDF cannot be generated before they are set.
+#[wasm_bindgen(js_name = setFonts)]
+pub fn set_fonts(fonts: FontArray) {
+    let fonts: Vec<Bytes> = fonts
+        .unchecked_into::<Array>()
+        .iter()
+        .map(|font| Bytes::from(Uint8Array::new(&font).to_vec()))
+        .collect();
+    FONTS.with(|registered| *registered.borrow_mut() = fonts);
+}
 
-    let parsed_file = match Document::parse(&file.into(), input_format) {
-        Ok(parse_result) => parse_result,
-        Err(e) => {
-            return Err(e.to_string().into());
+/// Converts `file` from `input_format` to `output_format`.
+#[wasm_bindgen]
+pub fn convert(
+    file: &[u8],
+    input_format: FileFormat,
+    output_format: FileFormat,
+    options: Option<ParseOptions>,
+) -> Result<Vec<u8>, JsValue> {
+    let resolver = options_resolver(options.map(JsValue::from).as_ref())?;
+    let document = parse_document(file, input_format, resolver)?;
+    generate_document(&document, output_format)
+}
+
+/// Converts `input` like `convert` and reports the progress to `onProgress`.
+/// The result is a new `ArrayBuffer`, so it can be transferred by `postMessage`.
+#[wasm_bindgen(js_name = convertBuffer)]
+pub fn convert_buffer(
+    input: &ArrayBuffer,
+    input_format: FileFormat,
+    output_format: FileFormat,
+    options: Option<ConvertOptions>,
+) -> Result<ArrayBuffer, JsValue> {
+    let options = options.map(JsValue::from);
+    let resolver = options_resolver(options.as_ref())?;
+    let on_progress = option_function(options.as_ref(), "onProgress")?;
+    let output = convert_with_progress(
+        &Uint8Array::new(input).to_vec(),
+        input_format,
+        output_format,
+        resolver,
+        |percent, stage| {
+            if let Some(on_progress) = &on_progress {
+                let _ = on_progress.call2(&JsValue::NULL, &percent.into(), &stage.into());
+            }
+        },
+    )?;
+    Ok(Uint8Array::from(&output[..]).buffer())
+}
+
+/// Parses `file` into the Common Document Model.
+#[wasm_bindgen]
+pub fn parse(
+    file: &[u8],
+    input_format: FileFormat,
+    options: Option<ParseOptions>,
+) -> Result<CdmDocument, JsValue> {
+    let resolver = options_resolver(options.map(JsValue::from).as_ref())?;
+    let document = parse_document(file, input_format, resolver)?;
+    document
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map(JsCast::unchecked_into)
+        .map_err(|e| shiva_error("InvalidDocument", e))
+}
+
+/// Writes a Common Document Model document, as returned by `parse`, in `output_format`.
+#[wasm_bindgen]
+pub fn generate(document: CdmDocument, output_format: FileFormat) -> Result<Vec<u8>, JsValue> {
+    let document: Document = serde_wasm_bindgen::from_value(document.into())
+        .map_err(|e| shiva_error("InvalidDocument", e))?;
+    generate_document(&document, output_format)
+}
+
+/// The format of `file`, by the extension of `file_name` when it is known and
+/// otherwise by the content; `undefined` when it cannot be read.
+#[wasm_bindgen(js_name = detectFormat)]
+pub fn detect_format(file: &[u8], file_name: Option<String>) -> Option<FileFormat> {
+    file_name
+        .as_deref()
+        .and_then(|name| name.rsplit_once('.'))
+        .and_then(|(_, extension)| FileFormat::from_extension(&extension.to_lowercase()))
+        .or_else(|| FileFormat::detect(file))
+        .filter(FileFormat::is_enabled)
+}
+
+/// The formats this build reads and writes.
+#[wasm_bindgen(js_name = supportedFormats)]
+pub fn supported_formats() -> Result<FormatInfoArray, JsValue> {
+    let formats: Vec<FormatInfo> = FileFormat::variants()
+        .iter()
+        .filter(|format| format.is_enabled())
+        .map(|format| {
+            let mut extensions: Vec<_> = FileFormat::supported_extensions()
+                .into_iter()
+                .filter(|extension| FileFormat::from_extension(extension) == Some(*format))
+                .collect();
+            extensions.sort_unstable();
+            FormatInfo {
+                format: *format as u8,
+                name: format.to_string(),
+                extensions,
+                mime_type: format.mime_type(),
+                parse: true,
+                generate: format.can_generate(),
+            }
+        })
+        .collect();
+
+    formats
+        .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
+        .map(JsCast::unchecked_into)
+        .map_err(|e| shiva_error("InvalidDocument", e))
+}
+
+/// Serves `WorkerRequest` messages in a dedicated worker: converts the
+/// transferred `input` and posts `progress` messages and then the `result`,
+/// whose `output` is transferred back, or an `error`.
+#[wasm_bindgen(js_name = workerMain)]
+pub fn worker_main() -> Result<(), JsValue> {
+    let scope: DedicatedWorkerGlobalScope = js_sys::global()
+        .dyn_into()
+        .map_err(|_| js_sys::Error::new("workerMain must be called in a dedicated worker"))?;
+
+    let worker = scope.clone();
+    let on_message = Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+        let request = event.data();
+        let id = Reflect::get(&request, &"id".into()).unwrap_or(JsValue::UNDEFINED);
+        if let Err(error) = handle_request(&worker, &id, &request) {
+            let code = Reflect::get(&error, &"code".into())
+                .ok()
+                .and_then(|code| code.as_string())
+                .unwrap_or_else(|| "InvalidOptions".to_string());
+            let message = Reflect::get(&error, &"message".into())
+                .ok()
+                .and_then(|message| message.as_string())
+                .unwrap_or_else(|| format!("{error:?}"));
+            let response = response(
+                &id,
+                "error",
+                &[("code", code.into()), ("message", message.into())],
+            );
+            let _ = worker.post_message(&response);
         }
-    };
+    });
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    // The handler lives as long as the worker
+    on_message.forget();
+    Ok(())
+}
 
-    let generated = match parsed_file.generate(output_format) {
-        Ok(res) => res,
-        Err(err) => {
-            log!(" FileFormat::{} err {:#?}", output_format, err);
-            return Err(err.to_string().into());
+fn handle_request(
+    worker: &DedicatedWorkerGlobalScope,
+    id: &JsValue,
+    request: &JsValue,
+) -> Result<(), JsValue> {
+    let kind = Reflect::get(request, &"type".into())?
+        .as_string()
+        .unwrap_or_default();
+    match kind.as_str() {
+        "setFonts" => {
+            set_fonts(Reflect::get(request, &"fonts".into())?.unchecked_into());
+            worker.post_message(&response(id, "result", &[]))
         }
-    };
-    return Ok(generated.to_vec());
+        "convert" => {
+            let input = Uint8Array::new(&Reflect::get(request, &"input".into())?).to_vec();
+            let input_format = request_format(request, "from")?;
+            let output_format = request_format(request, "to")?;
+            let images = Reflect::get(request, &"images".into())?;
+            let resolver = if images.is_undefined() || images.is_null() {
+                None
+            } else {
+                let mut memory = MemoryResolver::new();
+                let images: Object = images.unchecked_into();
+                for uri in Object::keys(&images).iter() {
+                    let image = Uint8Array::new(&Reflect::get(&images, &uri)?);
+                    memory.insert(&uri.as_string().unwrap_or_default(), image.to_vec().into());
+                }
+                Some(ChainResolver::new().with(DataUriResolver).with(memory))
+            };
+
+            let output = convert_with_progress(
+                &input,
+                input_format,
+                output_format,
+                resolver,
+                |percent, stage| {
+                    let progress = response(
+                        id,
+                        "progress",
+                        &[("percent", percent.into()), ("stage", stage.into())],
+                    );
+                    let _ = worker.post_message(&progress);
+                },
+            )?;
+            let output = Uint8Array::from(&output[..]).buffer();
+            let result = response(id, "result", &[("output", output.clone().into())]);
+            worker.post_message_with_transfer(&result, &Array::of1(&output))
+        }
+        _ => Err(shiva_error(
+            "InvalidOptions",
+            format!("Unknown request type: {kind}"),
+        )),
+    }
+}
+
+// A `DocumentType` of a request, which is posted as its number
+fn request_format(request: &JsValue, name: &str) -> Result<FileFormat, JsValue> {
+    let value = Reflect::get(request, &name.into())?;
+    value
+        .as_f64()
+        .and_then(|number| {
+            FileFormat::variants()
+                .iter()
+                .copied()
+                .find(|format| f64::from(*format as u8) == number)
+        })
+        .ok_or_else(|| shiva_error("InvalidOptions", format!("{name} must be a DocumentType")))
+}
+
+fn response(id: &JsValue, kind: &str, fields: &[(&str, JsValue)]) -> Object {
+    let response = Object::new();
+    let _ = Reflect::set(&response, &"id".into(), id);
+    let _ = Reflect::set(&response, &"type".into(), &kind.into());
+    for (name, value) in fields {
+        let _ = Reflect::set(&response, &(*name).into(), value);
+    }
+    response
 }